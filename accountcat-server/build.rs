@@ -4,5 +4,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("../proto/todolist.proto")?;
     tonic_build::compile_protos("../proto/accounting.proto")?;
     tonic_build::compile_protos("../proto/instance_setting.proto")?;
+    tonic_build::compile_protos("../proto/ledger.proto")?;
+    tonic_build::compile_protos("../proto/split.proto")?;
+    tonic_build::compile_protos("../proto/savings.proto")?;
+    tonic_build::compile_protos("../proto/net_worth.proto")?;
+    tonic_build::compile_protos("../proto/investment.proto")?;
+    tonic_build::compile_protos("../proto/forecast.proto")?;
+    tonic_build::compile_protos("../proto/alert.proto")?;
+    tonic_build::compile_protos("../proto/data_import.proto")?;
+    tonic_build::compile_protos("../proto/installment.proto")?;
     Ok(())
 }
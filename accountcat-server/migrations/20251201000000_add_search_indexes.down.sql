@@ -0,0 +1,3 @@
+drop index if exists tags_name_trgm;
+drop index if exists accounting_items_name_fts;
+drop index if exists accounting_items_name_trgm;
@@ -0,0 +1,5 @@
+create extension if not exists pg_trgm;
+
+create index accounting_items_name_trgm on accounting_items using gin (name gin_trgm_ops);
+create index accounting_items_name_fts on accounting_items using gin (to_tsvector('simple', coalesce(name, '')));
+create index tags_name_trgm on tags using gin (name gin_trgm_ops);
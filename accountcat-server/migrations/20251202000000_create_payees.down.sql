@@ -0,0 +1,4 @@
+alter table accounting_items drop column payee_id;
+drop table payee_default_tags;
+drop table payee_aliases;
+drop table payees;
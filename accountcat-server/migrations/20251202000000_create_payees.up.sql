@@ -0,0 +1,29 @@
+create table payees (
+  id serial primary key,
+  user_id integer not null references users(id),
+  name varchar(255) not null,
+  created_at timestamp with time zone not null default now(),
+  constraint payees_name_per_user unique(user_id, name)
+);
+
+create index payees_user_id on payees(user_id);
+create index payees_name_trgm on payees using gin (name gin_trgm_ops);
+
+create table payee_aliases (
+  id serial primary key,
+  payee_id integer not null references payees(id) on delete cascade,
+  alias varchar(255) not null,
+  created_at timestamp with time zone not null default now(),
+  constraint payee_aliases_alias_per_payee unique(payee_id, alias)
+);
+
+create index payee_aliases_alias_trgm on payee_aliases using gin (alias gin_trgm_ops);
+
+create table payee_default_tags (
+  payee_id integer not null references payees(id) on delete cascade,
+  tag_id integer not null references tags(id) on delete cascade,
+  primary key (payee_id, tag_id)
+);
+
+alter table accounting_items add column payee_id integer null references payees(id) on delete set null;
+create index accounting_items_payee_id on accounting_items(payee_id);
@@ -0,0 +1,7 @@
+drop index if exists accounting_items_note_trgm;
+
+alter table accounting_items
+    drop constraint if exists accounting_items_quantity_positive,
+    drop column unit,
+    drop column quantity,
+    drop column note;
@@ -0,0 +1,7 @@
+alter table accounting_items
+    add column note varchar(4096) null,
+    add column quantity numeric(18,4) null,
+    add column unit varchar(32) null,
+    add constraint accounting_items_quantity_positive check (quantity > 0);
+
+create index accounting_items_note_trgm on accounting_items using gin (note gin_trgm_ops);
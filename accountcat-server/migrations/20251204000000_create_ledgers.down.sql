@@ -0,0 +1,15 @@
+alter table payees drop constraint payees_name_per_ledger;
+alter table payees add constraint payees_name_per_user unique(user_id, name);
+alter table payees drop column ledger_id;
+
+alter table tags drop constraint tags_name_per_ledger;
+alter table tags add constraint tags_name_per_user unique(user_id, name);
+alter table tags drop column ledger_id;
+
+alter table accounting_items drop column ledger_id;
+
+drop table ledger_invitations;
+drop table ledger_members;
+drop table ledgers;
+
+alter table users drop column name;
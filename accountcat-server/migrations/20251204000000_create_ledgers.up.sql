@@ -0,0 +1,54 @@
+alter table users add column name varchar(255) null;
+
+create table ledgers (
+  id serial primary key,
+  name varchar(255) not null,
+  -- set for the personal ledger of a user. Every user has exactly one
+  personal_user_id integer null unique references users(id),
+  created_at timestamp with time zone not null default now()
+);
+
+create table ledger_members (
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  user_id integer not null references users(id),
+  role varchar(16) not null check (role in ('owner', 'editor', 'viewer')),
+  created_at timestamp with time zone not null default now(),
+  primary key (ledger_id, user_id)
+);
+
+create index ledger_members_user_id on ledger_members(user_id);
+
+create table ledger_invitations (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  token uuid not null unique,
+  role varchar(16) not null check (role in ('owner', 'editor', 'viewer')),
+  created_by integer not null references users(id),
+  expires_at timestamp with time zone not null,
+  accepted_by integer null references users(id),
+  accepted_at timestamp with time zone null,
+  created_at timestamp with time zone not null default now()
+);
+
+insert into ledgers (name, personal_user_id) select 'Personal', users.id from users;
+insert into ledger_members (ledger_id, user_id, role) select ledgers.id, ledgers.personal_user_id, 'owner' from ledgers;
+
+alter table accounting_items add column ledger_id integer null references ledgers(id);
+update accounting_items set ledger_id = ledgers.id from ledgers where ledgers.personal_user_id = accounting_items.user_id;
+alter table accounting_items alter column ledger_id set not null;
+create index accounting_items_ledger_id_occurred_at on accounting_items(ledger_id, occurred_at);
+
+-- tags without an owner were never reachable
+delete from accounting_item_tags using tags where tags.id = accounting_item_tags.tag_id and tags.user_id is null;
+delete from tags where tags.user_id is null;
+alter table tags add column ledger_id integer null references ledgers(id);
+update tags set ledger_id = ledgers.id from ledgers where ledgers.personal_user_id = tags.user_id;
+alter table tags alter column ledger_id set not null;
+alter table tags drop constraint tags_name_per_user;
+alter table tags add constraint tags_name_per_ledger unique(ledger_id, name);
+
+alter table payees add column ledger_id integer null references ledgers(id);
+update payees set ledger_id = ledgers.id from ledgers where ledgers.personal_user_id = payees.user_id;
+alter table payees alter column ledger_id set not null;
+alter table payees drop constraint payees_name_per_user;
+alter table payees add constraint payees_name_per_ledger unique(ledger_id, name);
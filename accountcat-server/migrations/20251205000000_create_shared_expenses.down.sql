@@ -0,0 +1,4 @@
+drop table settlements;
+drop table shared_expense_shares;
+drop table shared_expenses;
+drop table contacts;
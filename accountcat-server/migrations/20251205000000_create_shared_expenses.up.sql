@@ -0,0 +1,51 @@
+-- people taking part in shared expenses. A contact may be a registered user, or just a name
+create table contacts (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  name varchar(255) not null,
+  user_id integer null references users(id),
+  created_at timestamp with time zone not null default now(),
+  unique (ledger_id, user_id)
+);
+
+create table shared_expenses (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  name varchar(1024) not null,
+  amount numeric(18,2) not null check (amount > 0),
+  currency varchar(3) not null,
+  payer_contact_id integer not null references contacts(id),
+  split_method varchar(16) not null check (split_method in ('equal', 'shares', 'exact')),
+  -- the item recording the payment, when the payer paid from this ledger
+  accounting_item_id integer null references accounting_items(id) on delete set null,
+  occurred_at timestamp with time zone not null default now(),
+  created_at timestamp with time zone not null default now()
+);
+
+create index shared_expenses_ledger_id on shared_expenses(ledger_id, occurred_at);
+
+create table shared_expense_shares (
+  shared_expense_id integer not null references shared_expenses(id) on delete cascade,
+  contact_id integer not null references contacts(id),
+  amount numeric(18,2) not null,
+  -- weight of the participant when split by shares
+  shares numeric(18,4) null,
+  primary key (shared_expense_id, contact_id)
+);
+
+create index shared_expense_shares_contact_id on shared_expense_shares(contact_id);
+
+create table settlements (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  from_contact_id integer not null references contacts(id),
+  to_contact_id integer not null references contacts(id),
+  amount numeric(18,2) not null check (amount > 0),
+  currency varchar(3) not null,
+  accounting_item_id integer null references accounting_items(id) on delete set null,
+  occurred_at timestamp with time zone not null default now(),
+  created_at timestamp with time zone not null default now(),
+  check (from_contact_id <> to_contact_id)
+);
+
+create index settlements_ledger_id on settlements(ledger_id, occurred_at);
@@ -0,0 +1,2 @@
+drop table savings_contributions;
+drop table savings_goals;
@@ -0,0 +1,25 @@
+create table savings_goals (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  name varchar(255) not null,
+  target_amount numeric(18,2) not null check (target_amount > 0),
+  currency varchar(3) not null,
+  deadline date null,
+  -- items with the tag fund the goal
+  tag_id integer null references tags(id) on delete set null,
+  created_at timestamp with time zone not null default now()
+);
+
+create index savings_goals_ledger_id on savings_goals(ledger_id);
+
+create table savings_contributions (
+  id serial primary key,
+  goal_id integer not null references savings_goals(id) on delete cascade,
+  -- negative for withdrawals
+  amount numeric(18,2) not null check (amount <> 0),
+  note varchar(1024) null,
+  occurred_at timestamp with time zone not null default now(),
+  created_at timestamp with time zone not null default now()
+);
+
+create index savings_contributions_goal_id on savings_contributions(goal_id);
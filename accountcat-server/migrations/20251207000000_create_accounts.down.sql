@@ -0,0 +1,5 @@
+drop index accounting_items_account_id;
+alter table accounting_items drop column account_id;
+drop table account_snapshots;
+drop table accounts;
+alter table users drop column base_currency;
@@ -0,0 +1,29 @@
+-- the currency net worth and other summaries across currencies are reported in
+alter table users add column base_currency varchar(3) not null default 'TWD';
+
+-- things owned or owed, like bank balances, property and loans
+create table accounts (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  name varchar(255) not null,
+  kind varchar(16) not null check (kind in ('asset', 'liability')),
+  currency varchar(3) not null,
+  -- value before the first item of the account. For liabilities, the amount owed
+  opening_balance numeric(18,2) not null default 0,
+  created_at timestamp with time zone not null default now(),
+  unique (ledger_id, name)
+);
+
+create table account_snapshots (
+  account_id integer not null references accounts(id) on delete cascade,
+  date date not null,
+  -- for liabilities, the amount owed
+  value numeric(18,2) not null,
+  -- calculated from the items of the account rather than entered
+  derived boolean not null default false,
+  created_at timestamp with time zone not null default now(),
+  primary key (account_id, date)
+);
+
+alter table accounting_items add column account_id integer null references accounts(id) on delete set null;
+create index accounting_items_account_id on accounting_items(account_id, occurred_at);
@@ -0,0 +1,2 @@
+drop table investment_transactions;
+drop table security_prices;
@@ -0,0 +1,27 @@
+-- closing prices of securities, shared by every user
+create table security_prices (
+  symbol varchar(32) not null,
+  date date not null,
+  close numeric(18,4) not null check (close > 0),
+  primary key (symbol, date)
+);
+
+create table investment_transactions (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  symbol varchar(32) not null,
+  kind varchar(16) not null check (kind in ('buy', 'sell', 'dividend')),
+  -- units bought or sold. Null for dividends
+  quantity numeric(18,4) null check (quantity > 0),
+  -- total price of the units, or the dividend received, before the fee
+  amount numeric(18,2) not null check (amount >= 0),
+  fee numeric(18,2) not null default 0 check (fee >= 0),
+  currency varchar(3) not null,
+  -- the cash flow of the transaction
+  accounting_item_id integer null references accounting_items(id) on delete set null,
+  occurred_at timestamp with time zone not null default now(),
+  created_at timestamp with time zone not null default now(),
+  check ((kind = 'dividend') = (quantity is null))
+);
+
+create index investment_transactions_ledger_id on investment_transactions(ledger_id, symbol, occurred_at);
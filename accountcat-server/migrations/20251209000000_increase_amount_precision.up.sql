@@ -0,0 +1,11 @@
+-- three decimal places for currencies like KWD, the integer digits are kept
+alter table accounting_items alter column amount type numeric(19,3);
+alter table shared_expenses alter column amount type numeric(19,3);
+alter table shared_expense_shares alter column amount type numeric(19,3);
+alter table settlements alter column amount type numeric(19,3);
+alter table savings_goals alter column target_amount type numeric(19,3);
+alter table savings_contributions alter column amount type numeric(19,3);
+alter table accounts alter column opening_balance type numeric(19,3);
+alter table account_snapshots alter column value type numeric(19,3);
+alter table investment_transactions alter column amount type numeric(19,3);
+alter table investment_transactions alter column fee type numeric(19,3);
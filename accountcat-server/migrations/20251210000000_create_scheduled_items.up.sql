@@ -0,0 +1,17 @@
+-- expected cash flows used by the forecast. They don't create accounting items
+create table scheduled_items (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  name varchar(1024) not null,
+  -- negative for expenses, like accounting items
+  amount numeric(19,3) not null,
+  currency varchar(3) not null,
+  recurrence varchar(16) not null check (recurrence in ('once', 'weekly', 'monthly')),
+  start_date date not null,
+  end_date date null check (end_date >= start_date),
+  -- spending with the tag is covered by the item, so it's left out of the estimate
+  tag_id integer null references tags(id) on delete set null,
+  created_at timestamp with time zone not null default now()
+);
+
+create index scheduled_items_ledger_id on scheduled_items(ledger_id);
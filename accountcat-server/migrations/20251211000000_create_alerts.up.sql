@@ -0,0 +1,35 @@
+-- thresholds of unusual spending alerts. Users without a row use the defaults
+create table alert_settings (
+  user_id integer primary key references users(id) on delete cascade,
+  enabled boolean not null,
+  tag_ratio numeric(6,2) not null check (tag_ratio > 1),
+  item_ratio numeric(6,2) not null check (item_ratio > 1),
+  min_amount numeric(19,3) not null check (min_amount >= 0)
+);
+
+create table alerts (
+  id serial primary key,
+  user_id integer not null references users(id) on delete cascade,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  kind varchar(16) not null check (kind in ('tag_month', 'large_item')),
+  -- what the alert is about, so later detections update the alert instead of repeating it
+  subject varchar(64) not null,
+  tag_id integer null references tags(id) on delete cascade,
+  payee_id integer null references payees(id) on delete cascade,
+  accounting_item_id integer null references accounting_items(id) on delete cascade,
+  -- first day of the month of tag_month alerts
+  month date null,
+  currency varchar(3) not null,
+  baseline numeric(19,3) not null,
+  actual numeric(19,3) not null,
+  -- actual divided by baseline
+  deviation numeric(12,2) not null,
+  explanation varchar(1024) not null,
+  read_at timestamp with time zone null,
+  dismissed_at timestamp with time zone null,
+  created_at timestamp with time zone not null default now(),
+  updated_at timestamp with time zone not null default now(),
+  unique (user_id, subject)
+);
+
+create index alerts_user_id on alerts(user_id, created_at);
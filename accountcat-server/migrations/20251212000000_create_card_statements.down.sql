@@ -0,0 +1,5 @@
+alter table accounting_items drop column card_statement_id;
+drop table card_statements;
+alter table accounts drop constraint accounts_billing_cycle;
+alter table accounts drop column due_day;
+alter table accounts drop column closing_day;
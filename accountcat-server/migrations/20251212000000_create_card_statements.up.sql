@@ -0,0 +1,22 @@
+-- credit cards are liabilities whose spending is billed in cycles closing on closing_day and
+-- paid by the following due_day. Days past the end of a month fall on its last day
+alter table accounts add column closing_day smallint null check (closing_day between 1 and 31);
+alter table accounts add column due_day smallint null check (due_day between 1 and 31);
+alter table accounts add constraint accounts_billing_cycle check (
+  (closing_day is null and due_day is null)
+  or (closing_day is not null and due_day is not null and kind = 'liability')
+);
+
+-- statements of card accounts reconciled against the balance the bank reports
+create table card_statements (
+  id serial primary key,
+  account_id integer not null references accounts(id) on delete cascade,
+  closing_date date not null,
+  -- the amount owed according to the bank
+  statement_balance numeric(19,3) not null,
+  reconciled_at timestamp with time zone not null default now(),
+  unique (account_id, closing_date)
+);
+
+-- set when the item appears on a reconciled statement
+alter table accounting_items add column card_statement_id integer null references card_statements(id) on delete set null;
@@ -0,0 +1,2 @@
+drop table einvoice_lines;
+drop table einvoices;
@@ -0,0 +1,20 @@
+-- uniform invoices imported from the e-invoice platform. The invoice is kept after its item is
+-- deleted, so importing an overlapping download doesn't bring the item back
+create table einvoices (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  accounting_item_id integer null references accounting_items(id) on delete set null,
+  number varchar(10) not null,
+  seller_tax_id varchar(8) null,
+  unique (ledger_id, number)
+);
+create index einvoices_accounting_item_id on einvoices(accounting_item_id);
+
+create table einvoice_lines (
+  id serial primary key,
+  einvoice_id integer not null references einvoices(id) on delete cascade,
+  position integer not null,
+  name text not null,
+  amount numeric(19,3) not null
+);
+create index einvoice_lines_einvoice_id on einvoice_lines(einvoice_id, position);
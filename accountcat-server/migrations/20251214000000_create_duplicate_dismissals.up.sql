@@ -0,0 +1,8 @@
+-- pairs of items the user confirmed aren't duplicates, with the earlier inserted item first
+create table duplicate_dismissals (
+  item_id integer not null references accounting_items(id) on delete cascade,
+  other_item_id integer not null references accounting_items(id) on delete cascade,
+  primary key (item_id, other_item_id),
+  check (item_id < other_item_id)
+);
+create index duplicate_dismissals_other_item_id on duplicate_dismissals(other_item_id);
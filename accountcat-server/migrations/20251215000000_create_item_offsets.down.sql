@@ -0,0 +1,3 @@
+drop function net_amount(accounting_items);
+drop table item_offsets;
+alter table accounting_items drop column reimbursable;
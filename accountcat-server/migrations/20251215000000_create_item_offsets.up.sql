@@ -0,0 +1,19 @@
+-- expenses expected to be repaid, like work expenses the company reimburses
+alter table accounting_items add column reimbursable boolean not null default false;
+
+-- incomes offsetting expenses, like refunds and reimbursements. Part of an income can offset an
+-- expense, and an income can offset several expenses or the other way around
+create table item_offsets (
+  income_item_id integer not null references accounting_items(id) on delete cascade,
+  expense_item_id integer not null references accounting_items(id) on delete cascade,
+  amount numeric(19,3) not null check (amount > 0),
+  primary key (income_item_id, expense_item_id)
+);
+create index item_offsets_expense_item_id on item_offsets(expense_item_id);
+
+-- amount of an item with its offsets netted, which summaries of incomes and expenses count
+create function net_amount(item accounting_items) returns numeric language sql stable as $$
+  select item.amount
+         + coalesce((select sum(item_offsets.amount) from item_offsets where item_offsets.expense_item_id = item.id), 0)
+         - coalesce((select sum(item_offsets.amount) from item_offsets where item_offsets.income_item_id = item.id), 0)
+$$;
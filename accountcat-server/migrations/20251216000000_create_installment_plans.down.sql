@@ -0,0 +1,4 @@
+drop function counted(accounting_items, integer);
+alter table users drop column spread_installments;
+alter table accounting_items drop column installment_plan_id;
+drop table installment_plans;
@@ -0,0 +1,31 @@
+-- purchases paid in monthly installments, like card purchases split into 3, 6, 12 or 24 payments
+create table installment_plans (
+  id serial primary key,
+  ledger_id integer not null references ledgers(id) on delete cascade,
+  purchase_item_id integer not null unique references accounting_items(id) on delete cascade,
+  installments smallint not null check (installments between 2 and 60),
+  first_due date not null,
+  -- interest or fees charged on top of the purchase, spread over the installments
+  fee numeric(19,3) not null default 0 check (fee >= 0),
+  created_at timestamptz not null default now()
+);
+create index installment_plans_ledger_id on installment_plans(ledger_id);
+
+-- installments generated for a plan are removed with it
+alter table accounting_items add column installment_plan_id integer null references installment_plans(id) on delete cascade;
+create index accounting_items_installment_plan_id on accounting_items(installment_plan_id);
+
+-- whether summaries count purchases paid in installments by their installments instead of up front
+alter table users add column spread_installments boolean not null default false;
+
+-- whether summaries of the user count the item. A purchase paid in installments is counted either
+-- as a whole or by its installments, never both
+create function counted(item accounting_items, user_id integer) returns boolean language sql stable as $$
+  select case
+           when item.installment_plan_id is not null then users.spread_installments
+           when exists (select 1 from installment_plans where installment_plans.purchase_item_id = item.id) then not users.spread_installments
+           else true
+         end
+  from users
+  where users.id = user_id
+$$;
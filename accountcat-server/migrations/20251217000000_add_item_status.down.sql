@@ -0,0 +1,10 @@
+create or replace function counted(item accounting_items, user_id integer) returns boolean language sql stable as $$
+  select case
+           when item.installment_plan_id is not null then users.spread_installments
+           when exists (select 1 from installment_plans where installment_plans.purchase_item_id = item.id) then not users.spread_installments
+           else true
+         end
+  from users
+  where users.id = user_id
+$$;
+alter table accounting_items drop column status;
@@ -0,0 +1,16 @@
+-- pending items wait to post, like card authorizations, and their amounts can still change.
+-- Reconciled items appear on a reconciled statement
+alter table accounting_items add column status varchar(16) not null default 'cleared' check (status in ('pending', 'cleared', 'reconciled'));
+update accounting_items set status = 'reconciled' where card_statement_id is not null;
+
+-- items dated after today aren't counted until their date
+create or replace function counted(item accounting_items, user_id integer) returns boolean language sql stable as $$
+  select case
+           when item.installment_plan_id is not null then users.spread_installments
+           when exists (select 1 from installment_plans where installment_plans.purchase_item_id = item.id) then not users.spread_installments
+           else true
+         end
+         and item.occurred_at < date_trunc('day', now(), 'Asia/Taipei') + interval '1 day'
+  from users
+  where users.id = user_id
+$$;
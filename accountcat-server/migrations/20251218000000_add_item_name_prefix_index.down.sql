@@ -0,0 +1 @@
+drop index accounting_items_ledger_id_lower_name;
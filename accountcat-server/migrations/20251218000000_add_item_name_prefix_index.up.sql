@@ -0,0 +1,2 @@
+-- prefix matching of item names for suggestions, see SuggestItems
+create index accounting_items_ledger_id_lower_name on accounting_items (ledger_id, lower(name) text_pattern_ops);
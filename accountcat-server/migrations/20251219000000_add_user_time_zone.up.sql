@@ -0,0 +1,2 @@
+-- IANA name of the time zone days are counted in, one of pg_timezone_names
+alter table users add column time_zone text not null default 'Asia/Taipei';
@@ -0,0 +1,3 @@
+drop function period_month;
+alter table users drop column week_start;
+alter table users drop column month_start_day;
@@ -0,0 +1,10 @@
+-- day of the month summaries start on, like payday, see Period in src/period.rs
+alter table users add column month_start_day smallint not null default 1 check (month_start_day between 1 and 28);
+-- day weeks start on, from 0 for Monday to 6 for Sunday
+alter table users add column week_start smallint not null default 0 check (week_start between 0 and 6);
+
+-- first day of the month naming the period `day` is in, the same as Period::containing in
+-- src/period.rs. Moving back to the 1st from the start day keeps the days of the period in one month
+create function period_month(day date, month_start_day smallint) returns date language sql immutable as $$
+  select (date_trunc('month', day - (month_start_day - 1)) + case when month_start_day > 15 then interval '1 month' else interval '0' end)::date
+$$;
@@ -0,0 +1 @@
+alter table payees drop column default_account_id;
@@ -0,0 +1,2 @@
+-- account items of the payee go to when they don't name one, in the ledger of the payee
+alter table payees add column default_account_id integer null references accounts(id) on delete set null;
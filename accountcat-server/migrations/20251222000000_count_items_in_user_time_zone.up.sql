@@ -0,0 +1,11 @@
+-- items dated after today in the time zone of the user aren't counted until their date
+create or replace function counted(item accounting_items, user_id integer) returns boolean language sql stable as $$
+  select case
+           when item.installment_plan_id is not null then users.spread_installments
+           when exists (select 1 from installment_plans where installment_plans.purchase_item_id = item.id) then not users.spread_installments
+           else true
+         end
+         and item.occurred_at < date_trunc('day', now(), users.time_zone) + interval '1 day'
+  from users
+  where users.id = user_id
+$$;
@@ -0,0 +1 @@
+drop index accounting_item_tags_tag_id;
@@ -0,0 +1,2 @@
+-- tag completion ranks tags by their recent uses
+create index accounting_item_tags_tag_id on accounting_item_tags(tag_id, created_at);
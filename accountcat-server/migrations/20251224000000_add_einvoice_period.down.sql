@@ -0,0 +1,3 @@
+alter table einvoices drop constraint einvoices_ledger_id_number_period_key;
+alter table einvoices add constraint einvoices_ledger_id_number_key unique (ledger_id, number);
+alter table einvoices drop column period;
@@ -0,0 +1,9 @@
+-- invoice numbers are reissued every two-month period, which starts in an odd month. Invoices
+-- whose item was deleted before periods were recorded have none
+alter table einvoices add column period date null;
+update einvoices
+set period = make_date(extract(year from item.day)::integer, (extract(month from item.day)::integer + 1) / 2 * 2 - 1, 1)
+from (select accounting_items.id, (accounting_items.occurred_at at time zone 'Asia/Taipei')::date as day from accounting_items) item
+where item.id = einvoices.accounting_item_id;
+alter table einvoices drop constraint einvoices_ledger_id_number_key;
+alter table einvoices add constraint einvoices_ledger_id_number_period_key unique (ledger_id, number, period);
@@ -0,0 +1,213 @@
+use iso_currency::Currency;
+use num_traits::Signed;
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, FieldViolation, StatusExt};
+
+/// Decimal places amounts are stored with, enough for currencies like KWD
+pub const STORAGE_SCALE: i64 = 3;
+/// Integer digits amounts are stored with, as `numeric(19,3)`
+const STORAGE_INTEGER_DIGITS: u32 = 16;
+/// Decimal places quantities are stored with, as `numeric(18,4)`
+const QUANTITY_SCALE: i64 = 4;
+/// Integer digits quantities are stored with
+const QUANTITY_INTEGER_DIGITS: u32 = 14;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("unknown currency")]
+    UnknownCurrency,
+    #[error("amount isn't numeric")]
+    NotNumeric,
+    #[error("amount must not be negative")]
+    Negative,
+    #[error("amount must be less than 10^{STORAGE_INTEGER_DIGITS}")]
+    OutOfRange,
+    #[error("{currency} amounts have at most {exponent} decimal places")]
+    TooPrecise { currency: String, exponent: u16 },
+    #[error("quantity must be a positive number")]
+    QuantityNotPositive,
+    #[error("quantity must be less than 10^{QUANTITY_INTEGER_DIGITS}")]
+    QuantityOutOfRange,
+    #[error("quantities have at most {QUANTITY_SCALE} decimal places")]
+    QuantityTooPrecise,
+}
+
+/// Parse a currency code. Only currencies listed by `ListCurrency` are accepted
+pub fn parse_currency(code: &str) -> Result<Currency, AmountError> {
+    match Currency::from_code(code) {
+        Some(currency) if currency.flags().is_empty() => Ok(currency),
+        _ => Err(AmountError::UnknownCurrency),
+    }
+}
+
+/// Number of decimal places of the minor unit of the currency
+pub fn exponent(currency: Currency) -> u16 {
+    currency.exponent().unwrap_or(STORAGE_SCALE as u16)
+}
+
+/// Parse a non-negative amount of the currency. Amounts can't be more precise than the minor unit
+/// of the currency, and have to fit into the storage
+pub fn parse_amount(amount: &str, currency: Currency) -> Result<BigDecimal, AmountError> {
+    let Ok(amount) = amount.trim().parse::<BigDecimal>() else {
+        return Err(AmountError::NotNumeric);
+    };
+    if amount.is_negative() {
+        return Err(AmountError::Negative);
+    }
+    if amount >= BigDecimal::from(10u64.pow(STORAGE_INTEGER_DIGITS)) {
+        return Err(AmountError::OutOfRange);
+    }
+    let amount = amount.normalized();
+    let exponent = exponent(currency);
+    if amount.fractional_digit_count() > i64::from(exponent) {
+        return Err(AmountError::TooPrecise {
+            currency: String::from(currency.code()),
+            exponent,
+        });
+    }
+    Ok(amount)
+}
+
+/// Parse a positive quantity that fits into the storage without rounding
+pub fn parse_quantity(quantity: &str) -> Result<BigDecimal, AmountError> {
+    let quantity = match quantity.trim().parse::<BigDecimal>() {
+        Ok(quantity) if quantity.is_positive() => quantity.normalized(),
+        _ => return Err(AmountError::QuantityNotPositive),
+    };
+    if quantity >= BigDecimal::from(10u64.pow(QUANTITY_INTEGER_DIGITS)) {
+        return Err(AmountError::QuantityOutOfRange);
+    }
+    if quantity.fractional_digit_count() > QUANTITY_SCALE {
+        return Err(AmountError::QuantityTooPrecise);
+    }
+    Ok(quantity)
+}
+
+/// Parse an amount together with its currency, reporting problems of both fields at once
+pub fn parse_money(
+    amount_field: &str,
+    amount: &str,
+    currency_field: &str,
+    currency: &str,
+) -> tonic::Result<(BigDecimal, Currency)> {
+    let currency = match parse_currency(currency) {
+        Ok(currency) => currency,
+        Err(err) => {
+            let mut violations = vec![(currency_field, err)];
+            if amount.trim().parse::<BigDecimal>().is_err() {
+                violations.push((amount_field, AmountError::NotNumeric));
+            }
+            return Err(bad_request(violations));
+        }
+    };
+    match parse_amount(amount, currency) {
+        Ok(amount) => Ok((amount, currency)),
+        Err(err) => Err(bad_request(vec![(amount_field, err)])),
+    }
+}
+
+/// `invalid_argument` status with a google.rpc.BadRequest detail listing the violations
+pub fn bad_request(violations: Vec<(&str, AmountError)>) -> Status {
+    let message = violations
+        .iter()
+        .map(|(field, err)| format!("{field}: {err}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+    Status::with_error_details(
+        Code::InvalidArgument,
+        message,
+        ErrorDetails::with_bad_request(
+            violations
+                .into_iter()
+                .map(|(field, err)| FieldViolation::new(field, err.to_string()))
+                .collect::<Vec<FieldViolation>>(),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_currency() {
+        assert_eq!(Ok(Currency::TWD), parse_currency("TWD"));
+        assert_eq!(Err(AmountError::UnknownCurrency), parse_currency("twd"));
+        assert_eq!(Err(AmountError::UnknownCurrency), parse_currency("XYZ"));
+        // precious metals aren't listed
+        assert_eq!(Err(AmountError::UnknownCurrency), parse_currency("XAU"));
+    }
+
+    #[test]
+    fn test_parse_amount_precision() {
+        assert_eq!(
+            Ok("100".parse().unwrap()),
+            parse_amount("100.00", Currency::JPY)
+        );
+        assert_eq!(
+            Err(AmountError::TooPrecise {
+                currency: String::from("JPY"),
+                exponent: 0
+            }),
+            parse_amount("100.5", Currency::JPY)
+        );
+        assert_eq!(
+            Ok("1.234".parse().unwrap()),
+            parse_amount("1.234", Currency::KWD)
+        );
+        assert!(parse_amount("1.234", Currency::TWD).is_err());
+    }
+
+    #[test]
+    fn test_parse_amount_rejects() {
+        assert_eq!(
+            Err(AmountError::NotNumeric),
+            parse_amount("NaN", Currency::TWD)
+        );
+        assert_eq!(
+            Err(AmountError::NotNumeric),
+            parse_amount("twelve", Currency::TWD)
+        );
+        assert_eq!(
+            Err(AmountError::OutOfRange),
+            parse_amount("1e30", Currency::TWD)
+        );
+        assert_eq!(
+            Err(AmountError::Negative),
+            parse_amount("-1", Currency::TWD)
+        );
+        assert!(parse_amount("9999999999999999.99", Currency::TWD).is_ok());
+    }
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(Ok("0.5".parse().unwrap()), parse_quantity("0.5000"));
+        assert_eq!(Err(AmountError::QuantityNotPositive), parse_quantity("0"));
+        assert_eq!(Err(AmountError::QuantityNotPositive), parse_quantity("-1"));
+        assert_eq!(Err(AmountError::QuantityNotPositive), parse_quantity("a"));
+        assert_eq!(
+            Err(AmountError::QuantityTooPrecise),
+            parse_quantity("0.00001")
+        );
+        assert_eq!(
+            Err(AmountError::QuantityOutOfRange),
+            parse_quantity("100000000000000")
+        );
+        assert!(parse_quantity("99999999999999.9999").is_ok());
+    }
+
+    #[test]
+    fn test_bad_request_details() {
+        let status = parse_money("amount", "abc", "currency", "XYZ").unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+        let details = status.get_details_bad_request().unwrap();
+        let fields: Vec<&str> = details
+            .field_violations
+            .iter()
+            .map(|x| x.field.as_str())
+            .collect();
+        assert_eq!(vec!["currency", "amount"], fields);
+    }
+}
@@ -0,0 +1,340 @@
+use std::{collections::HashMap, time::Duration};
+
+use sqlx::{PgPool, types::BigDecimal};
+use time::Date;
+use tracing::{error, info};
+
+use crate::{
+    anomaly::rules::{
+        BASELINE_MONTHS, Finding, ItemBaseline, Subject, Thresholds, check, item_baseline,
+        monthly_baseline,
+    },
+    dateutils::format_date,
+    service::accounting::format_amount,
+};
+
+/// How often the detector looks for unusual spending
+pub const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Items that occurred within this many days are compared with their payee or tag
+const RECENT_ITEM_DAYS: i32 = 31;
+
+/// Run the detector every [INTERVAL] until the server stops
+pub async fn run(database: PgPool) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        match detect(&database).await {
+            Ok(count) => info!(action = "detect unusual spending", alerts = count),
+            Err(err) => error!(action = "detect unusual spending", error = ?err),
+        }
+    }
+}
+
+struct Alert {
+    user_id: i32,
+    ledger_id: i32,
+    kind: &'static str,
+    subject: String,
+    tag_id: Option<i32>,
+    payee_id: Option<i32>,
+    accounting_item_id: Option<i32>,
+    month: Option<Date>,
+    currency: String,
+    finding: Finding,
+    explanation: String,
+}
+
+struct TagHistory {
+    tag_name: String,
+    currency: String,
+    /// First day of the month naming the current period of the user
+    current_month: Date,
+    months: Vec<(Date, BigDecimal)>,
+}
+
+struct RecentItem {
+    ledger_id: i32,
+    name: String,
+    spent: BigDecimal,
+    currency: String,
+    candidates: Vec<ItemBaseline>,
+}
+
+/// Look for unusual spending in the ledgers of every user, in their base currency, and record
+/// alerts for it. Returns the number of alerts created or updated
+pub async fn detect(database: &PgPool) -> sqlx::Result<u64> {
+    let thresholds = load_thresholds(database).await?;
+    let thresholds_of = |user_id: i32| thresholds.get(&user_id).cloned().unwrap_or_default();
+    let mut alerts = Vec::new();
+
+    let rows = sqlx::query!(
+        r#"select ledger_members.user_id,
+       accounting_items.ledger_id,
+       accounting_item_tags.tag_id,
+       tags.name as tag_name,
+       users.base_currency,
+       period_month((now() at time zone users.time_zone)::date, users.month_start_day) as "current_month!",
+       period_month((accounting_items.occurred_at at time zone users.time_zone)::date, users.month_start_day) as "month!",
+       -sum(net_amount(accounting_items)) as "spent!"
+from accounting_items
+join accounting_item_tags on accounting_item_tags.accounting_item_id = accounting_items.id
+join tags on tags.id = accounting_item_tags.tag_id
+join ledger_members on ledger_members.ledger_id = accounting_items.ledger_id
+join users on users.id = ledger_members.user_id
+where accounting_items.amount < 0
+      and counted(accounting_items, users.id)
+      and accounting_items.currency = users.base_currency
+      and accounting_items.occurred_at >= (date_trunc('month', now() at time zone users.time_zone) - make_interval(months => $1 + 1)) at time zone users.time_zone
+group by 1, 2, 3, 4, 5, 6, 7
+order by 1, 2, 3, 7"#,
+        BASELINE_MONTHS,
+    )
+    .fetch_all(database)
+    .await?;
+    let mut histories: HashMap<(i32, i32, i32), TagHistory> = HashMap::new();
+    for row in rows {
+        histories
+            .entry((row.user_id, row.ledger_id, row.tag_id))
+            .or_insert_with(|| TagHistory {
+                tag_name: row.tag_name,
+                currency: row.base_currency,
+                current_month: row.current_month,
+                months: Vec::new(),
+            })
+            .months
+            .push((row.month, row.spent));
+    }
+    for ((user_id, ledger_id, tag_id), history) in histories {
+        let TagHistory {
+            tag_name,
+            currency,
+            current_month,
+            months,
+        } = history;
+        let thresholds = thresholds_of(user_id);
+        if !thresholds.enabled {
+            continue;
+        }
+        let Some(actual) = months
+            .iter()
+            .find(|(month, _)| *month == current_month)
+            .map(|(_, spent)| spent)
+        else {
+            continue;
+        };
+        let Some(baseline) = monthly_baseline(&months, current_month) else {
+            continue;
+        };
+        let Some(finding) = check(
+            actual,
+            &baseline.round(3),
+            &thresholds.tag_ratio,
+            &thresholds.min_amount,
+        ) else {
+            continue;
+        };
+        let explanation = format!(
+            "Spending on {tag_name} this month is {} {currency}, {} times the monthly average of {} {currency}",
+            format_amount(&finding.actual),
+            format_amount(&finding.deviation),
+            format_amount(&finding.baseline),
+        );
+        alerts.push(Alert {
+            user_id,
+            ledger_id,
+            kind: "tag_month",
+            subject: format!("tag:{tag_id}:{}", format_date(current_month)),
+            tag_id: Some(tag_id),
+            payee_id: None,
+            accounting_item_id: None,
+            month: Some(current_month),
+            currency,
+            finding,
+            explanation,
+        });
+    }
+
+    // earlier items of the same payee or tag within a year make the baseline of an item
+    let rows = sqlx::query!(
+        r#"select ledger_members.user_id,
+       accounting_items.id,
+       accounting_items.ledger_id,
+       accounting_items.name as "name!",
+       -net_amount(accounting_items) as "spent!",
+       users.base_currency,
+       baselines.subject as "subject!",
+       baselines.id as "subject_id!",
+       baselines.name as "subject_name!",
+       baselines.count as "count!",
+       baselines.average as "average!"
+from accounting_items
+join ledger_members on ledger_members.ledger_id = accounting_items.ledger_id
+join users on users.id = ledger_members.user_id
+cross join lateral (
+    select 'payee' as subject, payees.id, payees.name, count(*) as count, avg(-net_amount(earlier)) as average
+    from payees
+    join accounting_items earlier on earlier.payee_id = payees.id
+    where payees.id = accounting_items.payee_id
+          and net_amount(earlier) < 0
+          and counted(earlier, users.id)
+          and earlier.currency = accounting_items.currency
+          and earlier.occurred_at < accounting_items.occurred_at
+          and earlier.occurred_at >= accounting_items.occurred_at - interval '1 year'
+    group by payees.id, payees.name
+    union all
+    select 'tag', tags.id, tags.name, count(*), avg(-net_amount(earlier))
+    from accounting_item_tags
+    join tags on tags.id = accounting_item_tags.tag_id
+    join accounting_item_tags earlier_tags on earlier_tags.tag_id = tags.id
+    join accounting_items earlier on earlier.id = earlier_tags.accounting_item_id
+    where accounting_item_tags.accounting_item_id = accounting_items.id
+          and net_amount(earlier) < 0
+          and counted(earlier, users.id)
+          and earlier.currency = accounting_items.currency
+          and earlier.occurred_at < accounting_items.occurred_at
+          and earlier.occurred_at >= accounting_items.occurred_at - interval '1 year'
+    group by tags.id, tags.name
+) baselines
+where net_amount(accounting_items) < 0
+      and counted(accounting_items, users.id)
+      and accounting_items.currency = users.base_currency
+      and accounting_items.occurred_at >= now() - make_interval(days => $1)
+      and accounting_items.occurred_at <= now()
+order by 1, 2"#,
+        RECENT_ITEM_DAYS,
+    )
+    .fetch_all(database)
+    .await?;
+    let mut items: HashMap<(i32, i32), RecentItem> = HashMap::new();
+    for row in rows {
+        items
+            .entry((row.user_id, row.id))
+            .or_insert_with(|| RecentItem {
+                ledger_id: row.ledger_id,
+                name: row.name,
+                spent: row.spent,
+                currency: row.base_currency,
+                candidates: Vec::new(),
+            })
+            .candidates
+            .push(ItemBaseline {
+                subject: if row.subject == "payee" {
+                    Subject::Payee
+                } else {
+                    Subject::Tag
+                },
+                id: row.subject_id,
+                name: row.subject_name,
+                count: row.count,
+                average: row.average,
+            });
+    }
+    for ((user_id, item_id), item) in items {
+        let RecentItem {
+            ledger_id,
+            name,
+            spent,
+            currency,
+            candidates,
+        } = item;
+        let thresholds = thresholds_of(user_id);
+        if !thresholds.enabled {
+            continue;
+        }
+        let Some(baseline) = item_baseline(&candidates) else {
+            continue;
+        };
+        let Some(finding) = check(
+            &spent,
+            &baseline.average.round(3),
+            &thresholds.item_ratio,
+            &thresholds.min_amount,
+        ) else {
+            continue;
+        };
+        let subject = match baseline.subject {
+            Subject::Payee => "payee",
+            Subject::Tag => "tag",
+        };
+        let explanation = format!(
+            "{name} costs {} {currency}, {} times the average of {} {currency} for {subject} {}",
+            format_amount(&finding.actual),
+            format_amount(&finding.deviation),
+            format_amount(&finding.baseline),
+            baseline.name,
+        );
+        alerts.push(Alert {
+            user_id,
+            ledger_id,
+            kind: "large_item",
+            subject: format!("item:{item_id}"),
+            tag_id: (baseline.subject == Subject::Tag).then_some(baseline.id),
+            payee_id: (baseline.subject == Subject::Payee).then_some(baseline.id),
+            accounting_item_id: Some(item_id),
+            month: None,
+            currency,
+            finding,
+            explanation,
+        });
+    }
+
+    let mut count = 0;
+    for alert in alerts {
+        count += sqlx::query!(
+            "insert into alerts (user_id, ledger_id, kind, subject, tag_id, payee_id, accounting_item_id, month, currency, baseline, actual, deviation, explanation)
+values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+on conflict (user_id, subject) do update
+set baseline = excluded.baseline,
+    actual = excluded.actual,
+    deviation = excluded.deviation,
+    explanation = excluded.explanation,
+    updated_at = now()
+where alerts.baseline <> excluded.baseline or alerts.actual <> excluded.actual",
+            alert.user_id,
+            alert.ledger_id,
+            alert.kind,
+            alert.subject,
+            alert.tag_id,
+            alert.payee_id,
+            alert.accounting_item_id,
+            alert.month,
+            alert.currency,
+            alert.finding.baseline,
+            alert.finding.actual,
+            alert.finding.deviation,
+            alert.explanation,
+        )
+        .execute(database)
+        .await?
+        .rows_affected();
+    }
+    Ok(count)
+}
+
+async fn load_thresholds(database: &PgPool) -> sqlx::Result<HashMap<i32, Thresholds>> {
+    let rows = sqlx::query!(
+        "select alert_settings.user_id,
+       alert_settings.enabled,
+       alert_settings.tag_ratio,
+       alert_settings.item_ratio,
+       alert_settings.min_amount
+from alert_settings"
+    )
+    .fetch_all(database)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.user_id,
+                Thresholds {
+                    enabled: row.enabled,
+                    tag_ratio: row.tag_ratio,
+                    item_ratio: row.item_ratio,
+                    min_amount: row.min_amount,
+                },
+            )
+        })
+        .collect())
+}
@@ -0,0 +1,197 @@
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::Date;
+
+/// Months of history the monthly spending of a tag is averaged over
+pub const BASELINE_MONTHS: i32 = 6;
+/// Months of history a tag needs before its spending is checked
+pub const MIN_HISTORY_MONTHS: i32 = 2;
+/// Earlier items of a payee or tag needed before an item is compared with them
+pub const MIN_SAMPLES: i64 = 3;
+
+/// Thresholds a user picked for alerts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thresholds {
+    pub enabled: bool,
+    /// alert when a tag's spending of the month reaches this many times its monthly average
+    pub tag_ratio: BigDecimal,
+    /// alert when an item reaches this many times the average of its payee or tag
+    pub item_ratio: BigDecimal,
+    /// differences from the baseline below this amount aren't reported
+    pub min_amount: BigDecimal,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tag_ratio: BigDecimal::new(15.into(), 1),
+            item_ratio: BigDecimal::from(3),
+            min_amount: BigDecimal::zero(),
+        }
+    }
+}
+
+/// Spending found far above its baseline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub baseline: BigDecimal,
+    pub actual: BigDecimal,
+    /// actual divided by baseline, rounded to 2 decimal places
+    pub deviation: BigDecimal,
+}
+
+/// Compare spending with its baseline
+pub fn check(
+    actual: &BigDecimal,
+    baseline: &BigDecimal,
+    ratio: &BigDecimal,
+    min_amount: &BigDecimal,
+) -> Option<Finding> {
+    if *baseline <= BigDecimal::zero() {
+        return None;
+    }
+    if *actual < baseline * ratio || actual - baseline < *min_amount {
+        return None;
+    }
+    Some(Finding {
+        baseline: baseline.clone(),
+        actual: actual.clone(),
+        deviation: (actual / baseline).round(2),
+    })
+}
+
+fn month_index(date: Date) -> i32 {
+    date.year() * 12 + i32::from(u8::from(date.month()))
+}
+
+/// Average monthly spending before the month of `current`. Months from the first one with
+/// spending count, including those without spending, up to [BASELINE_MONTHS]. `None` without
+/// [MIN_HISTORY_MONTHS] of history
+pub fn monthly_baseline(months: &[(Date, BigDecimal)], current: Date) -> Option<BigDecimal> {
+    let current = month_index(current);
+    let previous: Vec<(i32, &BigDecimal)> = months
+        .iter()
+        .map(|(date, spent)| (month_index(*date), spent))
+        .filter(|(month, _)| *month < current && current - month <= BASELINE_MONTHS)
+        .collect();
+    let first = previous.iter().map(|(month, _)| *month).min()?;
+    let count = current - first;
+    if count < MIN_HISTORY_MONTHS {
+        return None;
+    }
+    let sum: BigDecimal = previous.into_iter().map(|(_, spent)| spent).sum();
+    Some(sum / BigDecimal::from(count))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject {
+    Payee,
+    Tag,
+}
+
+/// Average of the earlier items of a payee or tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemBaseline {
+    pub subject: Subject,
+    pub id: i32,
+    pub name: String,
+    pub count: i64,
+    pub average: BigDecimal,
+}
+
+/// Baseline to compare an item with. The payee is the most specific, otherwise the tag with the
+/// highest average is used, so items with several tags aren't reported by the cheapest of them
+pub fn item_baseline(candidates: &[ItemBaseline]) -> Option<&ItemBaseline> {
+    let candidates = candidates.iter().filter(|x| x.count >= MIN_SAMPLES);
+    let (payees, tags): (Vec<&ItemBaseline>, Vec<&ItemBaseline>) =
+        candidates.partition(|x| x.subject == Subject::Payee);
+    payees
+        .into_iter()
+        .next()
+        .or_else(|| tags.into_iter().max_by(|a, b| a.average.cmp(&b.average)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dateutils::parse_date;
+
+    fn date(s: &str) -> Date {
+        parse_date(s).unwrap()
+    }
+
+    fn amount(s: &str) -> BigDecimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_check() {
+        let ratio = amount("1.5");
+        assert_eq!(
+            Some(Finding {
+                baseline: amount("100"),
+                actual: amount("250"),
+                deviation: amount("2.5"),
+            }),
+            check(&amount("250"), &amount("100"), &ratio, &amount("0"))
+        );
+        assert_eq!(
+            None,
+            check(&amount("149"), &amount("100"), &ratio, &amount("0"))
+        );
+        // too small to bother
+        assert_eq!(
+            None,
+            check(&amount("250"), &amount("100"), &ratio, &amount("200"))
+        );
+        assert_eq!(
+            None,
+            check(&amount("250"), &amount("0"), &ratio, &amount("0"))
+        );
+    }
+
+    #[test]
+    fn test_monthly_baseline() {
+        let current = date("2025-10-19");
+        // September has no spending, but counts
+        let months = vec![
+            (date("2025-07-01"), amount("300")),
+            (date("2025-08-01"), amount("300")),
+            (date("2025-10-01"), amount("5000")),
+        ];
+        assert_eq!(Some(amount("200")), monthly_baseline(&months, current));
+        // only a month of history
+        let months = vec![(date("2025-09-01"), amount("300"))];
+        assert_eq!(None, monthly_baseline(&months, current));
+        // older months are ignored
+        let months = vec![
+            (date("2025-01-01"), amount("6000")),
+            (date("2025-08-01"), amount("600")),
+        ];
+        assert_eq!(Some(amount("300")), monthly_baseline(&months, current));
+    }
+
+    #[test]
+    fn test_item_baseline() {
+        let baseline = |subject, id, count, average: &str| ItemBaseline {
+            subject,
+            id,
+            name: String::new(),
+            count,
+            average: amount(average),
+        };
+        let candidates = vec![
+            baseline(Subject::Tag, 1, 10, "100"),
+            baseline(Subject::Tag, 2, 10, "300"),
+            baseline(Subject::Tag, 3, 2, "900"),
+        ];
+        assert_eq!(Some(2), item_baseline(&candidates).map(|x| x.id));
+        let mut with_payee = candidates.clone();
+        with_payee.push(baseline(Subject::Payee, 4, 3, "50"));
+        assert_eq!(Some(4), item_baseline(&with_payee).map(|x| x.id));
+        with_payee[3].count = 1;
+        assert_eq!(Some(2), item_baseline(&with_payee).map(|x| x.id));
+        assert_eq!(None, item_baseline(&candidates[2..]));
+    }
+}
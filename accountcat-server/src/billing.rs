@@ -0,0 +1,114 @@
+use time::{Date, Duration, Month};
+
+/// Billing cycle of a card, with both ends inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: Date,
+    pub closing: Date,
+    pub due: Date,
+}
+
+/// Whether `day` can be a closing or due day. Days past the end of a month fall on its last day
+pub fn valid_day(day: i32) -> bool {
+    (1..=31).contains(&day)
+}
+
+fn day_of_month(year: i32, month: Month, day: u8) -> Date {
+    let day = day.min(time::util::days_in_month(month, year));
+    Date::from_calendar_date(year, month, day).unwrap()
+}
+
+fn next_month(date: Date) -> (i32, Month) {
+    match date.month() {
+        Month::December => (date.year() + 1, Month::January),
+        month => (date.year(), month.next()),
+    }
+}
+
+fn previous_month(date: Date) -> (i32, Month) {
+    match date.month() {
+        Month::January => (date.year() - 1, Month::December),
+        month => (date.year(), month.previous()),
+    }
+}
+
+/// The cycle containing `date`, for a card closing on `closing_day` with the payment due on the
+/// following `due_day`
+pub fn cycle_containing(date: Date, closing_day: u8, due_day: u8) -> Cycle {
+    let this_month = day_of_month(date.year(), date.month(), closing_day);
+    let closing = if date <= this_month {
+        this_month
+    } else {
+        let (year, month) = next_month(date);
+        day_of_month(year, month, closing_day)
+    };
+    let (year, month) = previous_month(closing);
+    let start = day_of_month(year, month, closing_day) + Duration::days(1);
+    let due = day_of_month(closing.year(), closing.month(), due_day);
+    let due = if due > closing {
+        due
+    } else {
+        let (year, month) = next_month(closing);
+        day_of_month(year, month, due_day)
+    };
+    Cycle {
+        start,
+        closing,
+        due,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dateutils::parse_date;
+
+    fn cycle(date: &str, closing_day: u8, due_day: u8) -> (String, String, String) {
+        let cycle = cycle_containing(parse_date(date).unwrap(), closing_day, due_day);
+        (
+            cycle.start.to_string(),
+            cycle.closing.to_string(),
+            cycle.due.to_string(),
+        )
+    }
+
+    fn expected(start: &str, closing: &str, due: &str) -> (String, String, String) {
+        (start.to_string(), closing.to_string(), due.to_string())
+    }
+
+    #[test]
+    fn test_cycle_containing() {
+        assert_eq!(
+            expected("2025-02-16", "2025-03-15", "2025-04-05"),
+            cycle("2025-03-15", 15, 5)
+        );
+        assert_eq!(
+            expected("2025-03-16", "2025-04-15", "2025-05-05"),
+            cycle("2025-03-16", 15, 5)
+        );
+        // due in the month of closing
+        assert_eq!(
+            expected("2025-02-11", "2025-03-10", "2025-03-25"),
+            cycle("2025-03-01", 10, 25)
+        );
+        // across years
+        assert_eq!(
+            expected("2024-12-16", "2025-01-15", "2025-02-05"),
+            cycle("2024-12-20", 15, 5)
+        );
+    }
+
+    #[test]
+    fn test_short_months() {
+        assert_eq!(
+            expected("2025-02-01", "2025-02-28", "2025-03-28"),
+            cycle("2025-02-10", 31, 28)
+        );
+        assert_eq!(
+            expected("2025-03-01", "2025-03-31", "2025-04-30"),
+            cycle("2025-03-01", 31, 31)
+        );
+        assert!(valid_day(31));
+        assert!(!valid_day(0));
+    }
+}
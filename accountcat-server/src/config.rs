@@ -1,6 +1,6 @@
 use std::{path::PathBuf, str::FromStr};
 
-use crate::secret_se::{serialize_optional_secret, serialize_secret};
+use crate::secret_se::{serialize_optional_secret, serialize_optional_secrets, serialize_secret};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use sqlx::{
@@ -52,6 +52,9 @@ pub struct ConfigFile {
 #[derive(Serialize, Deserialize, Default)]
 pub struct Server {
     pub port: Option<u16>,
+    /// Command converting HTML reports into PDF, which reads HTML from stdin and writes PDF to
+    /// stdout, like `["wkhtmltopdf", "--quiet", "-", "-"]`. PDF reports are disabled without it
+    pub pdf_command: Option<Vec<String>>,
 }
 
 impl Server {
@@ -60,12 +63,19 @@ impl Server {
             port: std::env::var("PORT")
                 .ok()
                 .and_then(|p| p.parse::<u16>().ok()),
+            pdf_command: std::env::var("PDF_COMMAND")
+                .ok()
+                .map(|c| c.split_whitespace().map(String::from).collect()),
         }
     }
 
     fn or(mut self, other: Option<Self>) -> Self {
-        let port = other.and_then(|o| o.port);
+        let (port, pdf_command) = match other {
+            Some(other) => (other.port, other.pdf_command),
+            None => (None, None),
+        };
         self.port = self.port.or(port);
+        self.pdf_command = self.pdf_command.or(pdf_command);
         self
     }
 }
@@ -80,6 +90,9 @@ pub struct Login {
 pub struct HashIds {
     #[serde(serialize_with = "serialize_secret")]
     pub salt: SecretString,
+    /// Salts used before the current one. Ids encoded with them are still accepted
+    #[serde(default, serialize_with = "serialize_optional_secrets")]
+    pub previous_salts: Option<Vec<SecretString>>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -113,7 +126,7 @@ fn load_from_string(config: Option<String>) -> Result<Config, LoadError> {
             Err(err) => return Err(LoadError::Parse(err)),
         }
     }
-    let (server, login, database, hashids, general, pki) = match config_file {
+    let (server, login, database, mut hashids, general, pki) = match config_file {
         Some(config_file) => (
             config_file.server,
             config_file.login,
@@ -134,13 +147,19 @@ fn load_from_string(config: Option<String>) -> Result<Config, LoadError> {
     let database = Database::from_env()
         .or(database)
         .or(Some(Default::default()));
-    let hashids = std::env::var("HASHIDS_SALT")
+    let previous_salts = std::env::var("HASHIDS_PREVIOUS_SALTS")
+        .ok()
+        .map(|salts| salts.split(",").map(SecretString::from).collect())
+        .or_else(|| hashids.as_mut().and_then(|h| h.previous_salts.take()));
+    let mut hashids = std::env::var("HASHIDS_SALT")
         .ok()
         .map(|salt| HashIds {
             salt: SecretString::from(salt),
+            previous_salts: None,
         })
         .or(hashids)
         .ok_or(LoadError::MissingEssentialValue("hashids.salt"))?;
+    hashids.previous_salts = previous_salts;
     let pki = std::env::var("PKI_CA")
         .ok()
         .map(|directory| Pki {
@@ -0,0 +1,57 @@
+use sqlx::PgPool;
+use time::{Date, Month, OffsetDateTime, UtcOffset};
+
+/// Parse a date in the form of `YYYY-MM-DD`
+pub fn parse_date(date: &str) -> Option<Date> {
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+}
+
+/// Current time in the Asia/Taipei time zone, the default time zone of users
+pub fn now() -> OffsetDateTime {
+    OffsetDateTime::now_utc().to_offset(UtcOffset::from_hms(8, 0, 0).unwrap())
+}
+
+/// Current time in the time zone of a user
+pub async fn user_now(database: &PgPool, user_id: i32) -> sqlx::Result<OffsetDateTime> {
+    let offset = sqlx::query_scalar!(
+        r#"select extract(epoch from (now() at time zone users.time_zone) - (now() at time zone 'UTC'))::integer as "offset!"
+from users
+where users.id = $1"#,
+        user_id
+    )
+    .fetch_one(database)
+    .await?;
+    let offset = UtcOffset::from_whole_seconds(offset).unwrap_or(UtcOffset::UTC);
+    Ok(OffsetDateTime::now_utc().to_offset(offset))
+}
+
+/// Today in the Asia/Taipei time zone, the default time zone of users
+pub fn today() -> Date {
+    now().date()
+}
+
+pub fn format_date(date: Date) -> String {
+    format!(
+        "{:04}-{:02}-{:02}",
+        date.year(),
+        u8::from(date.month()),
+        date.day()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!("2025-02-28", format_date(parse_date("2025-02-28").unwrap()));
+        assert!(parse_date("2025-02-30").is_none());
+        assert!(parse_date("2025-13-01").is_none());
+        assert!(parse_date("tomorrow").is_none());
+    }
+}
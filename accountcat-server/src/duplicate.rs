@@ -0,0 +1,174 @@
+use sqlx::{PgExecutor, PgPool};
+
+/// Items of the same amount this far apart can still be the same payment, like a card charge
+/// entered by hand and imported again when it posts a day later
+pub const WINDOW_HOURS: i32 = 48;
+
+fn normalize(name: &str) -> Vec<char> {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether two item names likely describe the same payment. Case, spaces and punctuation are
+/// ignored. Names containing each other or sharing most of their character pairs are similar, and
+/// names with nothing left are similar to none
+pub fn similar_names(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a == b {
+        return true;
+    }
+    let (shorter, longer) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    if shorter.len() >= 2 && longer.windows(shorter.len()).any(|x| x == &shorter[..]) {
+        return true;
+    }
+    // Sørensen–Dice coefficient of character bigrams
+    let a_pairs: Vec<&[char]> = a.windows(2).collect();
+    let mut b_pairs: Vec<&[char]> = b.windows(2).collect();
+    if a_pairs.is_empty() || b_pairs.is_empty() {
+        return false;
+    }
+    let total = a_pairs.len() + b_pairs.len();
+    let mut shared = 0;
+    for pair in a_pairs {
+        if let Some(position) = b_pairs.iter().position(|x| *x == pair) {
+            b_pairs.swap_remove(position);
+            shared += 1;
+        }
+    }
+    shared * 2 * 2 >= total
+}
+
+/// Whether two items of the same amount around the same time likely are the same payment, by
+/// their names, or by their payees when neither is named
+fn same_payment(
+    name: Option<&str>,
+    payee_id: Option<i32>,
+    other_name: Option<&str>,
+    other_payee_id: Option<i32>,
+) -> bool {
+    let (name, other_name) = (name.unwrap_or_default(), other_name.unwrap_or_default());
+    if normalize(name).is_empty() && normalize(other_name).is_empty() {
+        return payee_id.is_some() && payee_id == other_payee_id;
+    }
+    similar_names(name, other_name)
+}
+
+/// A pair of likely duplicates, the earlier inserted first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pair {
+    pub item_id: i32,
+    pub other_item_id: i32,
+}
+
+/// Items likely duplicating the given item
+pub async fn duplicates_of<'e, E: PgExecutor<'e>>(
+    executor: E,
+    item_id: i32,
+) -> sqlx::Result<Vec<i32>> {
+    let candidates = sqlx::query!(
+        "select items.name, items.payee_id, others.id, others.name as other_name, others.payee_id as other_payee_id
+from accounting_items items
+join accounting_items others on others.ledger_id = items.ledger_id
+     and others.currency = items.currency
+     and others.amount = items.amount
+     and others.id <> items.id
+     and others.occurred_at between items.occurred_at - make_interval(hours => $2)
+                                and items.occurred_at + make_interval(hours => $2)
+where items.id = $1
+order by others.id",
+        item_id,
+        WINDOW_HOURS,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(candidates
+        .into_iter()
+        .filter(|x| {
+            same_payment(
+                x.name.as_deref(),
+                x.payee_id,
+                x.other_name.as_deref(),
+                x.other_payee_id,
+            )
+        })
+        .map(|x| x.id)
+        .collect())
+}
+
+/// Likely duplicates in the history of a ledger, latest first, except dismissed pairs
+pub async fn find(pool: &PgPool, ledger_id: i32, limit: usize) -> sqlx::Result<Vec<Pair>> {
+    let candidates = sqlx::query!(
+        "select items.id, items.name, items.payee_id, others.id as other_id, others.name as other_name, others.payee_id as other_payee_id
+from accounting_items items
+join accounting_items others on others.ledger_id = items.ledger_id
+     and others.currency = items.currency
+     and others.amount = items.amount
+     and others.id > items.id
+     and others.occurred_at between items.occurred_at - make_interval(hours => $2)
+                                and items.occurred_at + make_interval(hours => $2)
+where items.ledger_id = $1
+      and not exists (
+        select 1 from duplicate_dismissals
+        where duplicate_dismissals.item_id = items.id
+              and duplicate_dismissals.other_item_id = others.id
+      )
+order by greatest(items.occurred_at, others.occurred_at) desc, others.id desc",
+        ledger_id,
+        WINDOW_HOURS,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(candidates
+        .into_iter()
+        .filter(|x| {
+            same_payment(
+                x.name.as_deref(),
+                x.payee_id,
+                x.other_name.as_deref(),
+                x.other_payee_id,
+            )
+        })
+        .map(|x| Pair {
+            item_id: x.id,
+            other_item_id: x.other_id,
+        })
+        .take(limit)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similar_names() {
+        assert!(similar_names("Lunch", "lunch "));
+        assert!(similar_names("Uber", "UBER *TRIP"));
+        assert!(similar_names("全家便利商店", "全家"));
+        assert!(similar_names("Netflix subscription", "Netflix subscr."));
+        assert!(!similar_names("", ""));
+        assert!(!similar_names(" - ", ""));
+        assert!(!similar_names("Lunch", ""));
+        assert!(!similar_names("Lunch", "Dinner"));
+        assert!(!similar_names("午餐", "晚餐"));
+        assert!(!similar_names("a", "ab"));
+    }
+
+    #[test]
+    fn test_same_payment() {
+        assert!(same_payment(Some("Lunch"), None, Some("lunch"), Some(1)));
+        assert!(same_payment(None, Some(1), Some(""), Some(1)));
+        assert!(!same_payment(None, None, None, None));
+        assert!(!same_payment(None, Some(1), None, Some(2)));
+        assert!(!same_payment(Some("Lunch"), Some(1), None, Some(1)));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::{Date, Duration, Month};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recurrence {
+    Once,
+    Weekly,
+    Monthly,
+}
+
+impl Recurrence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Recurrence::Once => "once",
+            Recurrence::Weekly => "weekly",
+            Recurrence::Monthly => "monthly",
+        }
+    }
+
+    pub fn from_record(recurrence: &str) -> Option<Self> {
+        match recurrence {
+            "once" => Some(Recurrence::Once),
+            "weekly" => Some(Recurrence::Weekly),
+            "monthly" => Some(Recurrence::Monthly),
+            _ => None,
+        }
+    }
+}
+
+/// Dates within `from..=until` a schedule starting on `start` falls on. Monthly schedules
+/// starting on a day shorter months don't have fall on the last day of those months
+pub fn occurrences(
+    start: Date,
+    end: Option<Date>,
+    recurrence: Recurrence,
+    from: Date,
+    until: Date,
+) -> Vec<Date> {
+    let until = match end {
+        Some(end) if end < until => end,
+        _ => until,
+    };
+    let mut dates = Vec::new();
+    match recurrence {
+        Recurrence::Once => {
+            if from <= start && start <= until {
+                dates.push(start);
+            }
+        }
+        Recurrence::Weekly => {
+            let mut date = start;
+            if date < from {
+                let weeks = ((from - start).whole_days() + 6) / 7;
+                date = start + Duration::weeks(weeks);
+            }
+            while date <= until {
+                dates.push(date);
+                date += Duration::weeks(1);
+            }
+        }
+        Recurrence::Monthly => {
+            let start_month = start.year() * 12 + i32::from(u8::from(start.month())) - 1;
+            for month in start_month.. {
+                let year = month.div_euclid(12);
+                let Ok(m) = Month::try_from((month.rem_euclid(12) + 1) as u8) else {
+                    break;
+                };
+                let day = start.day().min(m.length(year));
+                let Ok(date) = Date::from_calendar_date(year, m, day) else {
+                    break;
+                };
+                if date > until {
+                    break;
+                }
+                if date >= from {
+                    dates.push(date);
+                }
+            }
+        }
+    }
+    dates
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Day {
+    pub date: Date,
+    /// sum of future-dated and scheduled items of the day
+    pub committed: BigDecimal,
+    /// estimate of variable spending of the day
+    pub estimated: BigDecimal,
+    /// balance at the end of the day, counting both committed and estimated amounts
+    pub balance: BigDecimal,
+    /// balance at the end of the day, counting committed amounts only
+    pub committed_balance: BigDecimal,
+}
+
+/// Project the balance over the `days` days after `today`. `committed` holds known cash flows,
+/// and `daily_estimate` is added to every day
+pub fn project(
+    today: Date,
+    days: u16,
+    balance: &BigDecimal,
+    committed: &[(Date, BigDecimal)],
+    daily_estimate: &BigDecimal,
+) -> Vec<Day> {
+    let mut committed_by_date: HashMap<Date, BigDecimal> = HashMap::new();
+    for (date, amount) in committed {
+        *committed_by_date
+            .entry(*date)
+            .or_insert_with(BigDecimal::zero) += amount;
+    }
+    let mut balance = balance.clone();
+    let mut committed_balance = balance.clone();
+    (1..=i64::from(days))
+        .map(|n| {
+            let date = today + Duration::days(n);
+            let committed = committed_by_date.remove(&date).unwrap_or_default();
+            balance += &committed + daily_estimate;
+            committed_balance += &committed;
+            Day {
+                date,
+                committed,
+                estimated: daily_estimate.clone(),
+                balance: balance.clone(),
+                committed_balance: committed_balance.clone(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dateutils::{format_date, parse_date};
+
+    fn date(s: &str) -> Date {
+        parse_date(s).unwrap()
+    }
+
+    fn dates(
+        start: &str,
+        end: Option<&str>,
+        recurrence: Recurrence,
+        from: &str,
+        until: &str,
+    ) -> Vec<String> {
+        occurrences(
+            date(start),
+            end.map(date),
+            recurrence,
+            date(from),
+            date(until),
+        )
+        .into_iter()
+        .map(format_date)
+        .collect()
+    }
+
+    #[test]
+    fn test_monthly_occurrences() {
+        assert_eq!(
+            vec!["2025-11-30", "2025-12-31", "2026-01-31", "2026-02-28"],
+            dates(
+                "2025-01-31",
+                None,
+                Recurrence::Monthly,
+                "2025-11-01",
+                "2026-03-30"
+            )
+        );
+        assert_eq!(
+            vec!["2025-11-05"],
+            dates(
+                "2025-10-05",
+                Some("2025-12-01"),
+                Recurrence::Monthly,
+                "2025-10-06",
+                "2026-01-30"
+            )
+        );
+    }
+
+    #[test]
+    fn test_weekly_and_once_occurrences() {
+        assert_eq!(
+            vec!["2025-10-22", "2025-10-29"],
+            dates(
+                "2025-10-01",
+                None,
+                Recurrence::Weekly,
+                "2025-10-20",
+                "2025-11-04"
+            )
+        );
+        assert_eq!(
+            vec!["2025-10-20"],
+            dates(
+                "2025-10-20",
+                None,
+                Recurrence::Once,
+                "2025-10-20",
+                "2025-10-30"
+            )
+        );
+        assert!(
+            dates(
+                "2025-10-19",
+                None,
+                Recurrence::Once,
+                "2025-10-20",
+                "2025-10-30"
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_project() {
+        let days = project(
+            date("2025-10-19"),
+            3,
+            &BigDecimal::from(100),
+            &[
+                (date("2025-10-21"), BigDecimal::from(-150)),
+                (date("2025-10-21"), BigDecimal::from(20)),
+                // outside of the range
+                (date("2025-10-19"), BigDecimal::from(-1000)),
+            ],
+            &BigDecimal::from(-10),
+        );
+        let summary: Vec<(String, String, String, String)> = days
+            .iter()
+            .map(|x| {
+                (
+                    format_date(x.date),
+                    x.committed.to_string(),
+                    x.balance.to_string(),
+                    x.committed_balance.to_string(),
+                )
+            })
+            .collect();
+        let expected: Vec<(String, String, String, String)> = [
+            ("2025-10-20", "0", "90", "100"),
+            ("2025-10-21", "-130", "-50", "-30"),
+            ("2025-10-22", "0", "-60", "-30"),
+        ]
+        .into_iter()
+        .map(|(a, b, c, d)| (a.into(), b.into(), c.into(), d.into()))
+        .collect();
+        assert_eq!(expected, summary);
+    }
+}
@@ -0,0 +1,66 @@
+use sqlx::types::BigDecimal;
+
+/// Most days a heatmap covers, a leap year
+pub const MAX_DAYS: i64 = 366;
+
+/// Quantiles of the daily expenses that separate the levels of a heatmap. Days without expenses
+/// are level 0, and the rest are split into quarters
+const QUANTILES: [(u32, u32); 3] = [(1, 4), (2, 4), (3, 4)];
+
+/// Expenses at the quantiles of the days with expenses, by the nearest-rank method. Empty when no
+/// day has expenses
+pub fn thresholds(expenses: &[BigDecimal]) -> Vec<BigDecimal> {
+    let mut spent: Vec<&BigDecimal> = expenses
+        .iter()
+        .filter(|x| **x > BigDecimal::from(0))
+        .collect();
+    if spent.is_empty() {
+        return Vec::new();
+    }
+    spent.sort();
+    QUANTILES
+        .iter()
+        .map(|(numerator, denominator)| {
+            let rank = (spent.len() * *numerator as usize).div_ceil(*denominator as usize);
+            spent[rank.max(1) - 1].clone()
+        })
+        .collect()
+}
+
+/// Level of a day from 0 to the number of thresholds plus one. A day above a threshold is a level
+/// higher than the days at or below it
+pub fn level(expense: &BigDecimal, thresholds: &[BigDecimal]) -> u32 {
+    if *expense <= BigDecimal::from(0) {
+        return 0;
+    }
+    1 + thresholds.iter().filter(|x| expense > *x).count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amounts(values: &[i32]) -> Vec<BigDecimal> {
+        values.iter().map(|x| BigDecimal::from(*x)).collect()
+    }
+
+    #[test]
+    fn test_thresholds() {
+        assert_eq!(
+            amounts(&[20, 40, 60]),
+            thresholds(&amounts(&[0, 80, 20, 0, 60, 40, 10, 30, 50, 70]))
+        );
+        assert_eq!(amounts(&[5, 5, 5]), thresholds(&amounts(&[5])));
+        assert!(thresholds(&amounts(&[0, 0])).is_empty());
+    }
+
+    #[test]
+    fn test_level() {
+        let thresholds = amounts(&[20, 40, 60]);
+        assert_eq!(0, level(&BigDecimal::from(0), &thresholds));
+        assert_eq!(1, level(&BigDecimal::from(20), &thresholds));
+        assert_eq!(2, level(&BigDecimal::from(21), &thresholds));
+        assert_eq!(4, level(&BigDecimal::from(61), &thresholds));
+        assert_eq!(1, level(&BigDecimal::from(100), &[]));
+    }
+}
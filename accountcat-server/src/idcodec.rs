@@ -0,0 +1,147 @@
+use hash_ids::HashIds;
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::config;
+
+/// Entity types of opaque ids. An id encodes its namespace, so the id of one type is never
+/// accepted as another. The values are part of the encoded ids and must never change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Ledger = 1,
+    User = 2,
+    Item = 3,
+    Tag = 4,
+    Payee = 5,
+    Task = 6,
+    Account = 7,
+    Goal = 8,
+    Contribution = 9,
+    Contact = 10,
+    SharedExpense = 11,
+    Settlement = 12,
+    InvestmentTransaction = 13,
+    ScheduledItem = 14,
+    Alert = 15,
+    InstallmentPlan = 16,
+}
+
+/// Converts database ids into the opaque ids exposed by the API and back
+pub struct IdCodec {
+    /// Newest salt first
+    codecs: Vec<HashIds>,
+}
+
+impl IdCodec {
+    /// New ids are encoded with `salt`. Ids encoded with any of `previous_salts` are still
+    /// accepted, so the salt can be rotated without breaking the ids users already have
+    pub fn new(salt: &SecretString, previous_salts: &[SecretString]) -> Self {
+        let codecs = std::iter::once(salt)
+            .chain(previous_salts)
+            .map(|salt| HashIds::builder().with_salt(salt.expose_secret()).finish())
+            .collect();
+        Self { codecs }
+    }
+
+    pub fn encode(&self, namespace: Namespace, id: i32) -> String {
+        self.codecs[0].encode(&[namespace as u64, id as u64])
+    }
+
+    pub fn decode(&self, namespace: Namespace, id: &str) -> Option<i32> {
+        self.codecs.iter().find_map(|codec| {
+            let numbers = codec.decode(id).ok()?;
+            let decoded_id = match numbers[..] {
+                [decoded_namespace, decoded_id] if decoded_namespace == namespace as u64 => {
+                    decoded_id
+                }
+                // item ids were issued without a namespace before namespaces existed
+                [decoded_id] if namespace == Namespace::Item => decoded_id,
+                _ => return None,
+            };
+            // decoding with another salt can succeed with garbage, which doesn't encode back
+            if codec.encode(&numbers) != id {
+                return None;
+            }
+            i32::try_from(decoded_id).ok()
+        })
+    }
+}
+
+impl From<&config::HashIds> for IdCodec {
+    fn from(value: &config::HashIds) -> Self {
+        Self::new(
+            &value.salt,
+            value.previous_salts.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codec(salt: &str, previous_salts: &[&str]) -> IdCodec {
+        let previous_salts: Vec<SecretString> = previous_salts
+            .iter()
+            .map(|x| SecretString::from(*x))
+            .collect();
+        IdCodec::new(&SecretString::from(salt), &previous_salts)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let codec = codec("salt", &[]);
+        let id = codec.encode(Namespace::Item, 42);
+        assert_eq!(Some(42), codec.decode(Namespace::Item, &id));
+        assert_eq!(None, codec.decode(Namespace::Item, "42"));
+        assert_eq!(None, codec.decode(Namespace::Item, ""));
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let codec = codec("salt", &[]);
+        let tag = codec.encode(Namespace::Tag, 1);
+        assert_ne!(tag, codec.encode(Namespace::Payee, 1));
+        assert_eq!(None, codec.decode(Namespace::Payee, &tag));
+    }
+
+    #[test]
+    fn test_salt_rotation() {
+        let old = codec("old", &[]);
+        let rotated = codec("new", &["old"]);
+        let id = old.encode(Namespace::Ledger, 7);
+        assert_eq!(Some(7), rotated.decode(Namespace::Ledger, &id));
+        assert_ne!(id, rotated.encode(Namespace::Ledger, 7));
+        let id = rotated.encode(Namespace::Ledger, 7);
+        assert_eq!(Some(7), rotated.decode(Namespace::Ledger, &id));
+        assert_eq!(None, old.decode(Namespace::Ledger, &id));
+        assert_eq!(
+            None,
+            codec("new", &[]).decode(Namespace::Ledger, &old.encode(Namespace::Ledger, 7))
+        );
+    }
+
+    #[test]
+    fn test_legacy_item_ids() {
+        // how item ids were encoded before namespaces
+        let encode_id = |salt: &str, id: i32| {
+            HashIds::builder()
+                .with_salt(salt)
+                .finish()
+                .encode(&[id as u64])
+        };
+        let rotated = codec("new", &["old"]);
+        assert_eq!(
+            Some(42),
+            rotated.decode(Namespace::Item, &encode_id("new", 42))
+        );
+        assert_eq!(
+            Some(42),
+            rotated.decode(Namespace::Item, &encode_id("old", 42))
+        );
+        assert_eq!(None, rotated.decode(Namespace::Tag, &encode_id("new", 42)));
+        assert_eq!(
+            None,
+            rotated.decode(Namespace::Item, &encode_id("other", 42))
+        );
+    }
+}
@@ -12,3 +12,39 @@ pub mod accounting {
 pub mod instance_setting {
     tonic::include_proto!("accountcat.instance_setting");
 }
+
+pub mod ledger {
+    tonic::include_proto!("accountcat.ledger");
+}
+
+pub mod split {
+    tonic::include_proto!("accountcat.split");
+}
+
+pub mod savings {
+    tonic::include_proto!("accountcat.savings");
+}
+
+pub mod net_worth {
+    tonic::include_proto!("accountcat.net_worth");
+}
+
+pub mod investment {
+    tonic::include_proto!("accountcat.investment");
+}
+
+pub mod forecast {
+    tonic::include_proto!("accountcat.forecast");
+}
+
+pub mod alert {
+    tonic::include_proto!("accountcat.alert");
+}
+
+pub mod data_import {
+    tonic::include_proto!("accountcat.data_import");
+}
+
+pub mod installment {
+    tonic::include_proto!("accountcat.installment");
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use iso_currency::Currency;
+use sqlx::{PgConnection, types::BigDecimal};
+use thiserror::Error;
+use time::{Date, Month};
+
+use crate::{amount, dateutils::parse_date};
+
+/// Uniform invoice downloaded from the e-invoice platform of the Ministry of Finance
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invoice {
+    /// Two letters and eight digits, unique across Taiwan within a [period]
+    pub number: String,
+    pub date: Date,
+    pub seller_tax_id: Option<String>,
+    pub seller_name: String,
+    pub total: BigDecimal,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub name: String,
+    pub amount: BigDecimal,
+}
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("malformed csv {0}")]
+    Csv(#[from] csv::Error),
+    #[error("line {line}: expecting a M or D record")]
+    Record { line: u64 },
+    #[error("line {line}: bad invoice number {value}")]
+    Number { line: u64, value: String },
+    #[error("line {line}: bad date {value}, expecting YYYYMMDD")]
+    Date { line: u64, value: String },
+    #[error("line {line}: bad amount {value}")]
+    Amount { line: u64, value: String },
+    #[error("line {line}: bad seller tax id {value}, expecting 8 digits")]
+    TaxId { line: u64, value: String },
+    #[error("line {line}: item of unknown invoice {number}")]
+    UnknownInvoice { line: u64, number: String },
+}
+
+/// Status of invoices cancelled by the seller
+const VOIDED: &str = "作廢";
+
+/// Parse the invoices of a carrier download. Each invoice is a `M` record of the carrier name,
+/// carrier number, date, seller tax id, seller name, invoice number, total and status, followed
+/// by `D` records of its number, amount and item name. Fields are separated by `|`, or commas
+/// for files converted by spreadsheets. Voided invoices are left out
+pub fn parse_csv(content: &str) -> Result<Vec<Invoice>, ParseError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let delimiter = if content.contains('|') { b'|' } else { b',' };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let mut invoices: Vec<Invoice> = Vec::new();
+    let mut index: HashMap<String, Option<usize>> = HashMap::new();
+    // number of the last M record, and whether it lists an invoice again
+    let mut last: Option<(String, bool)> = None;
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |x| x.line());
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        match field(0) {
+            "M" => {
+                let number = parse_number(field(6), line)?;
+                // the same invoice can be listed more than once, with its items each time
+                let repeated = index.contains_key(&number);
+                last = Some((number.clone(), repeated));
+                if field(8) == VOIDED {
+                    index.insert(number, None);
+                    continue;
+                }
+                let invoice = Invoice {
+                    date: parse_invoice_date(field(3), line)?,
+                    seller_tax_id: parse_tax_id(field(4), line)?,
+                    seller_name: field(5).to_string(),
+                    total: parse_amount(field(7), line)?,
+                    lines: Vec::new(),
+                    number,
+                };
+                if !repeated {
+                    index.insert(invoice.number.clone(), Some(invoices.len()));
+                    invoices.push(invoice);
+                }
+            }
+            "D" => {
+                let number = parse_number(field(1), line)?;
+                let Some(position) = index.get(&number) else {
+                    return Err(ParseError::UnknownInvoice { line, number });
+                };
+                let Some(position) = position else {
+                    continue;
+                };
+                if last.as_ref() == Some(&(number, true)) {
+                    continue;
+                }
+                invoices[*position].lines.push(Line {
+                    name: field(3).to_string(),
+                    amount: parse_amount(field(2), line)?,
+                });
+            }
+            // descriptions of the columns like `表頭=M|載具名稱|...`
+            x if x.contains('=') => continue,
+            "" if record.len() <= 1 => continue,
+            _ => return Err(ParseError::Record { line }),
+        }
+    }
+    Ok(invoices)
+}
+
+fn parse_number(number: &str, line: u64) -> Result<String, ParseError> {
+    let number = number.to_uppercase();
+    let chars: Vec<char> = number.chars().collect();
+    let valid = chars.len() == 10
+        && chars[..2].iter().all(char::is_ascii_uppercase)
+        && chars[2..].iter().all(char::is_ascii_digit);
+    if valid {
+        Ok(number)
+    } else {
+        Err(ParseError::Number {
+            line,
+            value: number,
+        })
+    }
+}
+
+fn parse_invoice_date(date: &str, line: u64) -> Result<Date, ParseError> {
+    let parsed = if date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()) {
+        let month = Month::try_from(date[4..6].parse::<u8>().unwrap()).ok();
+        month.and_then(|month| {
+            Date::from_calendar_date(
+                date[..4].parse().unwrap(),
+                month,
+                date[6..].parse().unwrap(),
+            )
+            .ok()
+        })
+    } else {
+        parse_date(date)
+    };
+    parsed.ok_or_else(|| ParseError::Date {
+        line,
+        value: date.to_string(),
+    })
+}
+
+/// Empty for sellers without tax ids, like street vendors
+fn parse_tax_id(tax_id: &str, line: u64) -> Result<Option<String>, ParseError> {
+    if tax_id.is_empty() {
+        return Ok(None);
+    }
+    if tax_id.len() == 8 && tax_id.chars().all(|c| c.is_ascii_digit()) {
+        Ok(Some(tax_id.to_string()))
+    } else {
+        Err(ParseError::TaxId {
+            line,
+            value: tax_id.to_string(),
+        })
+    }
+}
+
+/// Amounts in TWD the items can hold. Items of discounts are negative
+fn parse_amount(amount: &str, line: u64) -> Result<BigDecimal, ParseError> {
+    let plain = amount.replace(',', "");
+    let parsed = match plain.strip_prefix('-') {
+        Some(magnitude) => amount::parse_amount(magnitude, Currency::TWD).map(|x| -x),
+        None => amount::parse_amount(&plain, Currency::TWD),
+    };
+    parsed.map_err(|_err| ParseError::Amount {
+        line,
+        value: amount.to_string(),
+    })
+}
+
+/// First day of the two-month period of `date` invoice numbers are issued for. Numbers are
+/// reissued in later periods
+pub fn period(date: Date) -> Date {
+    let month = u8::from(date.month()).div_ceil(2) * 2 - 1;
+    Date::from_calendar_date(date.year(), Month::try_from(month).unwrap(), 1).unwrap()
+}
+
+/// Result of an import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub created: u32,
+    /// Invoices imported before, including ones whose item was deleted since
+    pub duplicated: u32,
+}
+
+/// Add an expense item for each invoice not imported into the ledger before
+pub async fn import(
+    connection: &mut PgConnection,
+    ledger_id: i32,
+    user_id: i32,
+    invoices: &[Invoice],
+) -> sqlx::Result<Summary> {
+    let mut summary = Summary::default();
+    for invoice in invoices {
+        let Some(einvoice_id) = sqlx::query_scalar!(
+            "insert into einvoices (ledger_id, number, seller_tax_id, period)
+select $1, $2::varchar, $3::varchar, $4::date
+where not exists (
+    select 1 from einvoices
+    where einvoices.ledger_id = $1 and einvoices.number = $2 and einvoices.period is null
+)
+on conflict (ledger_id, number, period) do nothing
+returning einvoices.id",
+            ledger_id,
+            invoice.number,
+            invoice.seller_tax_id,
+            period(invoice.date),
+        )
+        .fetch_optional(&mut *connection)
+        .await?
+        else {
+            summary.duplicated += 1;
+            continue;
+        };
+        let name = if invoice.seller_name.is_empty() {
+            &invoice.number
+        } else {
+            &invoice.seller_name
+        };
+        sqlx::query!(
+            "with item as (
+  insert into accounting_items (ledger_id, user_id, name, amount, currency, occurred_at)
+  select $2, $3, $4, $5, 'TWD', $6::date::timestamp at time zone users.time_zone
+  from users
+  where users.id = $3
+  returning accounting_items.id
+)
+update einvoices set accounting_item_id = item.id from item where einvoices.id = $1",
+            einvoice_id,
+            ledger_id,
+            user_id,
+            name,
+            -invoice.total.clone(),
+            invoice.date,
+        )
+        .execute(&mut *connection)
+        .await?;
+        let names: Vec<&str> = invoice.lines.iter().map(|x| x.name.as_str()).collect();
+        let amounts: Vec<BigDecimal> = invoice.lines.iter().map(|x| x.amount.clone()).collect();
+        sqlx::query!(
+            "insert into einvoice_lines (einvoice_id, position, name, amount)
+select $1, lines.position::integer, lines.name, lines.amount
+from unnest($2::text[], $3::numeric[]) with ordinality as lines(name, amount, position)",
+            einvoice_id,
+            &names as &[&str],
+            &amounts[..],
+        )
+        .execute(&mut *connection)
+        .await?;
+        summary.created += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWNLOAD: &str =
+        "表頭=M|載具名稱|載具號碼|發票日期|商店統編|商店店名|發票號碼|總金額|發票狀態|
+明細=D|發票號碼|小計|品項名稱|
+M|手機條碼|/ABC1234|20250301|12345678|全家便利商店|AB12345678|85|開立|
+D|AB12345678|45|鮮奶|
+D|AB12345678|40|麵包|
+M|手機條碼|/ABC1234|20250302|87654321|麥當勞|CD00000001|120|作廢|
+D|CD00000001|120|大麥克|
+M|手機條碼|/ABC1234|20250303||夜市攤販|ef00000002|1,200|開立|
+";
+
+    #[test]
+    fn test_parse_csv() {
+        let invoices = parse_csv(DOWNLOAD).unwrap();
+        assert_eq!(2, invoices.len());
+        assert_eq!("AB12345678", invoices[0].number);
+        assert_eq!(parse_date("2025-03-01"), Some(invoices[0].date));
+        assert_eq!(Some(String::from("12345678")), invoices[0].seller_tax_id);
+        assert_eq!("全家便利商店", invoices[0].seller_name);
+        assert_eq!(BigDecimal::from(85), invoices[0].total);
+        assert_eq!(
+            vec![
+                Line {
+                    name: String::from("鮮奶"),
+                    amount: BigDecimal::from(45)
+                },
+                Line {
+                    name: String::from("麵包"),
+                    amount: BigDecimal::from(40)
+                },
+            ],
+            invoices[0].lines
+        );
+        assert_eq!("EF00000002", invoices[1].number);
+        assert_eq!(None, invoices[1].seller_tax_id);
+        assert_eq!(BigDecimal::from(1200), invoices[1].total);
+        assert!(invoices[1].lines.is_empty());
+    }
+
+    #[test]
+    fn test_parse_repeated_invoice() {
+        let invoices = parse_csv(
+            "M|手機條碼|/ABC1234|20250301|12345678|全家|AB12345678|85|開立|
+D|AB12345678|45|鮮奶|
+D|AB12345678|40|麵包|
+M|手機條碼|/ABC1234|20250301|12345678|全家|AB12345678|85|開立|
+D|AB12345678|45|鮮奶|
+D|AB12345678|40|麵包|
+M|手機條碼|/ABC1234|20250302||夜市攤販|CD00000001|100|開立|
+D|CD00000001|120|雞排|
+D|CD00000001|-20|折扣|
+",
+        )
+        .unwrap();
+        assert_eq!(2, invoices.len());
+        assert_eq!(2, invoices[0].lines.len());
+        assert_eq!(BigDecimal::from(-20), invoices[1].lines[1].amount);
+    }
+
+    #[test]
+    fn test_period() {
+        let period = |date: &str| super::period(parse_date(date).unwrap());
+        assert_eq!(parse_date("2025-01-01"), Some(period("2025-02-28")));
+        assert_eq!(parse_date("2025-03-01"), Some(period("2025-03-01")));
+        assert_eq!(parse_date("2025-11-01"), Some(period("2025-12-31")));
+    }
+
+    #[test]
+    fn test_parse_comma_separated() {
+        let invoices = parse_csv(
+            "\u{feff}M,手機條碼,/ABC1234,2025-03-01,12345678,全家,AB12345678,85,開立\nD,AB12345678,85,鮮奶\n",
+        )
+        .unwrap();
+        assert_eq!(1, invoices.len());
+        assert_eq!(1, invoices[0].lines.len());
+    }
+
+    #[test]
+    fn test_parse_csv_errors() {
+        assert!(matches!(
+            parse_csv("M|手機條碼|/ABC1234|2025031|12345678|a|AB12345678|85|開立|\n"),
+            Err(ParseError::Date { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("M|手機條碼|/ABC1234|20250301|12345678|a|AB1234|85|開立|\n"),
+            Err(ParseError::Number { line: 1, .. })
+        ));
+        // 10 bytes but not 10 characters
+        assert!(matches!(
+            parse_csv("M|手機條碼|/ABC1234|20250301|12345678|a|A你123456|85|開立|\n"),
+            Err(ParseError::Number { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv(
+                "M|手機條碼|/ABC1234|20250301|12345678|a|AB12345678|85|開立|\nD|AB12345678|x|a|\n"
+            ),
+            Err(ParseError::Amount { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_csv(
+                "M|手機條碼|/ABC1234|20250301|12345678|a|AB12345678|10000000000000000|開立|\n"
+            ),
+            Err(ParseError::Amount { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("M|手機條碼|/ABC1234|20250301|123456789|a|AB12345678|85|開立|\n"),
+            Err(ParseError::TaxId { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("D|AB12345678|1|a|\n"),
+            Err(ParseError::UnknownInvoice { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("X|1|2\n"),
+            Err(ParseError::Record { line: 1 })
+        ));
+    }
+}
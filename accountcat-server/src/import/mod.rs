@@ -0,0 +1,2 @@
+pub mod einvoice;
+pub mod profile;
@@ -0,0 +1,91 @@
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+use time::{PrimitiveDateTime, UtcOffset};
+
+pub mod money_lover;
+pub mod moze;
+pub mod spendee;
+
+/// An income or expense read from the export of another app
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub name: String,
+    /// Negative for expenses
+    pub amount: BigDecimal,
+    pub currency: String,
+    /// Local time, in the time zone of the user when the export has no time zones
+    pub occurred_at: PrimitiveDateTime,
+    pub offset: Option<UtcOffset>,
+    /// Category name of the app, mapped onto a tag
+    pub category: String,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Export {
+    pub records: Vec<Record>,
+    /// Rows that are neither incomes nor expenses, like transfers between accounts
+    pub skipped: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("malformed csv {0}")]
+    Csv(#[from] csv::Error),
+    #[error("line {line}: bad date {value}")]
+    Date { line: u64, value: String },
+    #[error("line {line}: bad amount {value}")]
+    Amount { line: u64, value: String },
+}
+
+/// Layout of the CSV export of a personal finance app
+pub trait Profile {
+    fn parse(&self, content: &str) -> Result<Export, ParseError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum App {
+    Moze,
+    MoneyLover,
+    Spendee,
+}
+
+impl App {
+    pub fn profile(self) -> &'static dyn Profile {
+        match self {
+            App::Moze => &moze::Moze,
+            App::MoneyLover => &money_lover::MoneyLover,
+            App::Spendee => &spendee::Spendee,
+        }
+    }
+}
+
+fn reader(content: &str, delimiter: u8) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes())
+}
+
+fn line(record: &csv::StringRecord) -> u64 {
+    record.position().map_or(0, |x| x.line())
+}
+
+fn parse_amount(amount: &str, line: u64) -> Result<BigDecimal, ParseError> {
+    amount
+        .replace(',', "")
+        .parse()
+        .map_err(|_err| ParseError::Amount {
+            line,
+            value: amount.to_string(),
+        })
+}
+
+/// The first non-empty value
+fn first_of<'a>(values: impl IntoIterator<Item = &'a str>) -> String {
+    values
+        .into_iter()
+        .find(|x| !x.is_empty())
+        .unwrap_or_default()
+        .to_string()
+}
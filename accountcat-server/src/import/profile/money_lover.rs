@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use time::{Date, Month};
+
+use super::{Export, ParseError, Profile, Record, first_of, line, parse_amount, reader};
+
+/// Money Lover, which exports semicolon separated files with signed amounts
+pub struct MoneyLover;
+
+#[derive(Deserialize)]
+struct Row {
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "Category")]
+    category: String,
+    #[serde(rename = "Amount")]
+    amount: String,
+    #[serde(rename = "Currency")]
+    currency: String,
+    #[serde(rename = "Note")]
+    note: String,
+}
+
+/// `DD/MM/YYYY`
+fn parse_date(date: &str) -> Option<Date> {
+    let mut parts = date.splitn(3, '/');
+    let day = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let year = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+}
+
+impl Profile for MoneyLover {
+    fn parse(&self, content: &str) -> Result<Export, ParseError> {
+        let header = content.lines().next().unwrap_or_default();
+        let delimiter = if header.contains(';') { b';' } else { b',' };
+        let mut reader = reader(content, delimiter);
+        let headers = reader.headers()?.clone();
+        let mut export = Export::default();
+        for record in reader.records() {
+            let record = record?;
+            let line = line(&record);
+            let row: Row = record.deserialize(Some(&headers))?;
+            // transfers between wallets are recorded in pairs under these categories
+            if matches!(
+                row.category.as_str(),
+                "Transfer" | "Outgoing Transfer" | "Incoming Transfer"
+            ) {
+                export.skipped += 1;
+                continue;
+            }
+            let date = parse_date(&row.date).ok_or_else(|| ParseError::Date {
+                line,
+                value: row.date.clone(),
+            })?;
+            export.records.push(Record {
+                name: first_of([row.note.as_str(), &row.category]),
+                amount: parse_amount(&row.amount, line)?,
+                currency: row.currency,
+                occurred_at: date.midnight(),
+                offset: None,
+                category: row.category,
+                note: None,
+            });
+        }
+        Ok(export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::BigDecimal;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let export = MoneyLover
+            .parse(
+                "Id;Date;Category;Amount;Currency;Note;Wallet
+1;15/01/2023;Food & Beverage;-120;TWD;Beef noodles;Cash
+2;31/01/2023;Salary;50000;TWD;;Bank
+3;01/02/2023;Outgoing Transfer;-1000;TWD;;Cash
+",
+            )
+            .unwrap();
+        assert_eq!(1, export.skipped);
+        assert_eq!(2, export.records.len());
+        assert_eq!("Beef noodles", export.records[0].name);
+        assert_eq!(BigDecimal::from(-120), export.records[0].amount);
+        assert_eq!("Food & Beverage", export.records[0].category);
+        assert_eq!(
+            parse_date("15/01/2023").unwrap().midnight(),
+            export.records[0].occurred_at
+        );
+        assert_eq!(None, export.records[0].offset);
+        assert_eq!("Salary", export.records[1].name);
+        assert!(matches!(
+            MoneyLover.parse(
+                "Id;Date;Category;Amount;Currency;Note;Wallet\n1;2023-01-15;Food;-1;TWD;;Cash\n"
+            ),
+            Err(ParseError::Date { line: 2, .. })
+        ));
+    }
+}
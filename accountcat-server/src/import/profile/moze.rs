@@ -0,0 +1,166 @@
+use serde::Deserialize;
+use time::{Date, Month, Time};
+
+use super::{Export, ParseError, Profile, Record, first_of, line, parse_amount, reader};
+
+/// Moze, which exports comma separated files with headers in the language of the app
+pub struct Moze;
+
+#[derive(Deserialize)]
+struct Row {
+    #[serde(rename = "Currency", alias = "幣種")]
+    currency: String,
+    #[serde(rename = "Record Type", alias = "記錄類型")]
+    record_type: String,
+    #[serde(rename = "Main Category", alias = "主類別")]
+    main_category: String,
+    #[serde(rename = "Subcategory", alias = "子類別")]
+    subcategory: String,
+    #[serde(rename = "Price", alias = "金額")]
+    price: String,
+    #[serde(rename = "Name", alias = "名稱")]
+    name: String,
+    #[serde(rename = "Store", alias = "商家")]
+    store: String,
+    #[serde(rename = "Date", alias = "日期")]
+    date: String,
+    #[serde(rename = "Time", alias = "時間")]
+    time: String,
+    #[serde(rename = "Description", alias = "描述")]
+    description: String,
+}
+
+/// `YYYY/MM/DD`
+fn parse_date(date: &str) -> Option<Date> {
+    let mut parts = date.splitn(3, '/');
+    let year = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+}
+
+/// `HH:MM`, midnight when empty
+fn parse_time(time: &str) -> Option<Time> {
+    if time.is_empty() {
+        return Some(Time::MIDNIGHT);
+    }
+    let (hour, minute) = time.split_once(':')?;
+    Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+}
+
+impl Profile for Moze {
+    fn parse(&self, content: &str) -> Result<Export, ParseError> {
+        let mut reader = reader(content, b',');
+        let headers = reader.headers()?.clone();
+        let mut export = Export::default();
+        for record in reader.records() {
+            let record = record?;
+            let line = line(&record);
+            let row: Row = record.deserialize(Some(&headers))?;
+            let expense = match row.record_type.as_str() {
+                "Expense" | "支出" => true,
+                "Income" | "收入" => false,
+                // transfers, receivables, payables and balance adjustments
+                _ => {
+                    export.skipped += 1;
+                    continue;
+                }
+            };
+            let date = parse_date(&row.date).ok_or_else(|| ParseError::Date {
+                line,
+                value: row.date.clone(),
+            })?;
+            let time = parse_time(&row.time).ok_or_else(|| ParseError::Date {
+                line,
+                value: row.time.clone(),
+            })?;
+            let amount = parse_amount(&row.price, line)?.abs();
+            export.records.push(Record {
+                name: first_of([
+                    row.name.as_str(),
+                    &row.store,
+                    &row.subcategory,
+                    &row.main_category,
+                ]),
+                amount: if expense { -amount } else { amount },
+                currency: row.currency,
+                occurred_at: date.with_time(time),
+                offset: None,
+                category: row.main_category,
+                note: Some(row.description).filter(|x| !x.is_empty()),
+            });
+        }
+        Ok(export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::BigDecimal;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let export = Moze
+            .parse(
+                "Account,Currency,Record Type,Main Category,Subcategory,Price,Fee,Bonus,Name,Store,Date,Time,Project,Description,Tags,Target
+Cash,TWD,Expense,Food,Lunch,-120,0,0,,Noodle shop,2023/01/15,12:30,,,,
+Bank,TWD,Income,Salary,,\"50,000\",0,0,January,,2023/01/31,,,bonus included,,
+Cash,TWD,Transfer Out,Transfer,,-1000,0,0,,,2023/02/01,09:00,,,,
+",
+            )
+            .unwrap();
+        assert_eq!(1, export.skipped);
+        assert_eq!(2, export.records.len());
+        let lunch = &export.records[0];
+        assert_eq!("Noodle shop", lunch.name);
+        assert_eq!(BigDecimal::from(-120), lunch.amount);
+        assert_eq!("Food", lunch.category);
+        assert_eq!(
+            parse_date("2023/01/15")
+                .unwrap()
+                .with_time(Time::from_hms(12, 30, 0).unwrap()),
+            lunch.occurred_at
+        );
+        assert_eq!(None, lunch.offset);
+        let salary = &export.records[1];
+        assert_eq!(BigDecimal::from(50000), salary.amount);
+        assert_eq!(Some(String::from("bonus included")), salary.note);
+    }
+
+    #[test]
+    fn test_parse_chinese_headers() {
+        let export = Moze
+            .parse(
+                "帳戶,幣種,記錄類型,主類別,子類別,金額,手續費,折扣,名稱,商家,日期,時間,專案,描述,標籤,對象
+現金,JPY,支出,交通,計程車,-350,0,0,,,2023/03/01,21:00,,,,
+",
+            )
+            .unwrap();
+        assert_eq!("計程車", export.records[0].name);
+        assert_eq!("JPY", export.records[0].currency);
+        assert_eq!("交通", export.records[0].category);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let header = "Account,Currency,Record Type,Main Category,Subcategory,Price,Fee,Bonus,Name,Store,Date,Time,Project,Description,Tags,Target\n";
+        assert!(matches!(
+            Moze.parse(&format!(
+                "{header}Cash,TWD,Expense,Food,,-1,0,0,,,2023-01-15,,,,,\n"
+            )),
+            Err(ParseError::Date { line: 2, .. })
+        ));
+        assert!(matches!(
+            Moze.parse(&format!(
+                "{header}Cash,TWD,Expense,Food,,x,0,0,,,2023/01/15,,,,,\n"
+            )),
+            Err(ParseError::Amount { line: 2, .. })
+        ));
+        assert!(matches!(
+            Moze.parse("Date,Price\n2023/01/15,1\n"),
+            Err(ParseError::Csv(_))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use time::{OffsetDateTime, PrimitiveDateTime, format_description::well_known::Rfc3339};
+
+use super::{Export, ParseError, Profile, Record, first_of, line, parse_amount, reader};
+
+/// Spendee, which exports comma separated files with RFC 3339 timestamps
+pub struct Spendee;
+
+#[derive(Deserialize)]
+struct Row {
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "Type")]
+    r#type: String,
+    #[serde(rename = "Category name")]
+    category: String,
+    #[serde(rename = "Amount")]
+    amount: String,
+    #[serde(rename = "Currency")]
+    currency: String,
+    #[serde(rename = "Note")]
+    note: String,
+}
+
+impl Profile for Spendee {
+    fn parse(&self, content: &str) -> Result<Export, ParseError> {
+        let mut reader = reader(content, b',');
+        let headers = reader.headers()?.clone();
+        let mut export = Export::default();
+        for record in reader.records() {
+            let record = record?;
+            let line = line(&record);
+            let row: Row = record.deserialize(Some(&headers))?;
+            let expense = match row.r#type.as_str() {
+                "Expense" => true,
+                "Income" => false,
+                _ => {
+                    export.skipped += 1;
+                    continue;
+                }
+            };
+            let occurred_at =
+                OffsetDateTime::parse(&row.date, &Rfc3339).map_err(|_err| ParseError::Date {
+                    line,
+                    value: row.date.clone(),
+                })?;
+            let amount = parse_amount(&row.amount, line)?.abs();
+            export.records.push(Record {
+                name: first_of([row.note.as_str(), &row.category]),
+                amount: if expense { -amount } else { amount },
+                currency: row.currency,
+                occurred_at: PrimitiveDateTime::new(occurred_at.date(), occurred_at.time()),
+                offset: Some(occurred_at.offset()),
+                category: row.category,
+                note: None,
+            });
+        }
+        Ok(export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::BigDecimal;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let export = Spendee
+            .parse(
+                "Date,Wallet,Type,Category name,Amount,Currency,Note,Labels,Author
+2023-01-15T12:30:00+08:00,Cash,Expense,Food & Drink,-120,TWD,,,me
+2023-01-31T09:00:00+00:00,Bank,Income,Salary,50000,TWD,January,,me
+2023-02-01T09:00:00+00:00,Cash,Transfer,Transfer,-1000,TWD,,,me
+",
+            )
+            .unwrap();
+        assert_eq!(1, export.skipped);
+        assert_eq!("Food & Drink", export.records[0].name);
+        assert_eq!(BigDecimal::from(-120), export.records[0].amount);
+        assert_eq!(
+            OffsetDateTime::from_unix_timestamp(1673757000).unwrap(),
+            export.records[0]
+                .occurred_at
+                .assume_offset(export.records[0].offset.unwrap())
+        );
+        assert_eq!("January", export.records[1].name);
+        assert_eq!(BigDecimal::from(50000), export.records[1].amount);
+        assert!(matches!(
+            Spendee.parse(
+                "Date,Wallet,Type,Category name,Amount,Currency,Note,Labels,Author\n2023-01-15,Cash,Expense,Food,-1,TWD,,,me\n"
+            ),
+            Err(ParseError::Date { line: 2, .. })
+        ));
+    }
+}
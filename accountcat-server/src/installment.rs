@@ -0,0 +1,105 @@
+use sqlx::types::BigDecimal;
+use time::{Date, Duration};
+
+use crate::forecast::{Recurrence, occurrences};
+
+/// Fewest installments of a plan
+pub const MIN_INSTALLMENTS: u32 = 2;
+
+/// Most installments of a plan, five years of monthly payments
+pub const MAX_INSTALLMENTS: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Installment {
+    /// Starting from 1
+    pub number: u32,
+    pub due: Date,
+    /// Positive amount of the payment
+    pub amount: BigDecimal,
+}
+
+/// Monthly installments paying `total` off, due on the day of `first_due` in the following months
+/// or the last day of shorter months. The payments are equal at `scale` decimal places, and the
+/// first takes the remainder that doesn't divide evenly, the way card issuers bill them
+pub fn schedule(total: &BigDecimal, scale: i64, count: u32, first_due: Date) -> Vec<Installment> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let payment = (total / BigDecimal::from(count)).with_scale(scale);
+    let first = total - &payment * BigDecimal::from(count - 1);
+    let until = first_due + Duration::days(31 * i64::from(count));
+    occurrences(first_due, None, Recurrence::Monthly, first_due, until)
+        .into_iter()
+        .take(count as usize)
+        .enumerate()
+        .map(|(i, due)| Installment {
+            number: i as u32 + 1,
+            due,
+            amount: if i == 0 {
+                first.clone()
+            } else {
+                payment.clone()
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Month;
+
+    use super::*;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_schedule() {
+        let installments = schedule(
+            &BigDecimal::from(10000),
+            0,
+            3,
+            date(2025, Month::November, 30),
+        );
+        assert_eq!(
+            vec![
+                Installment {
+                    number: 1,
+                    due: date(2025, Month::November, 30),
+                    amount: BigDecimal::from(3334),
+                },
+                Installment {
+                    number: 2,
+                    due: date(2025, Month::December, 30),
+                    amount: BigDecimal::from(3333),
+                },
+                Installment {
+                    number: 3,
+                    due: date(2026, Month::January, 30),
+                    amount: BigDecimal::from(3333),
+                },
+            ],
+            installments
+        );
+    }
+
+    #[test]
+    fn test_schedule_clamps_day_and_keeps_total() {
+        let total: BigDecimal = "100.00".parse().unwrap();
+        let installments = schedule(&total, 2, 6, date(2025, Month::January, 31));
+        assert_eq!(6, installments.len());
+        assert_eq!(date(2025, Month::February, 28), installments[1].due);
+        assert_eq!(date(2025, Month::March, 31), installments[2].due);
+        assert_eq!(
+            "16.70".parse::<BigDecimal>().unwrap(),
+            installments[0].amount
+        );
+        assert_eq!(
+            "16.66".parse::<BigDecimal>().unwrap(),
+            installments[5].amount
+        );
+        let sum: BigDecimal = installments.iter().map(|x| &x.amount).sum();
+        assert_eq!(total, sum);
+    }
+}
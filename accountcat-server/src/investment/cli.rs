@@ -0,0 +1,56 @@
+use std::{fs::File, path::PathBuf, process::exit};
+
+use clap::{Parser, Subcommand};
+use sqlx::PgPool;
+
+use crate::{config::Config, investment::price};
+
+#[derive(Parser)]
+pub struct Command {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Load security prices from CSV files with symbol, date (YYYY-MM-DD) and close columns
+    ImportPrices(ImportPricesArgs),
+}
+
+#[derive(Parser)]
+struct ImportPricesArgs {
+    files: Vec<PathBuf>,
+}
+
+impl ImportPricesArgs {
+    async fn run(&self, config: &Config) {
+        let pool: PgPool = config.database.clone().into();
+        for path in self.files.iter() {
+            let prices = match File::open(path)
+                .map_err(|err| err.to_string())
+                .and_then(|f| price::parse_csv(f).map_err(|err| err.to_string()))
+            {
+                Ok(prices) => prices,
+                Err(err) => {
+                    println!("failed to read {}: {err}", path.to_string_lossy());
+                    exit(1);
+                }
+            };
+            match price::import(&pool, &prices).await {
+                Ok(count) => println!("{}: {count} prices imported", path.to_string_lossy()),
+                Err(err) => {
+                    println!("failed to import {}: {err:?}", path.to_string_lossy());
+                    exit(1);
+                }
+            }
+        }
+    }
+}
+
+impl Command {
+    pub async fn run(&self, config: &Config) {
+        match &self.action {
+            Action::ImportPrices(args) => args.run(config).await,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Buy,
+    Sell,
+    Dividend,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Buy => "buy",
+            Kind::Sell => "sell",
+            Kind::Dividend => "dividend",
+        }
+    }
+}
+
+impl std::str::FromStr for Kind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buy" => Ok(Kind::Buy),
+            "sell" => Ok(Kind::Sell),
+            "dividend" => Ok(Kind::Dividend),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A transaction of a security, in the order it occurred
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub kind: Kind,
+    /// Units bought or sold. Ignored for dividends
+    pub quantity: BigDecimal,
+    /// Total price of the units, or the dividend received, before the fee
+    pub amount: BigDecimal,
+    pub fee: BigDecimal,
+    pub year: i32,
+}
+
+/// The holding of a security after replaying its transactions, using the average cost method
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    pub quantity: BigDecimal,
+    /// Total cost of the units still held, including fees
+    pub cost_basis: BigDecimal,
+    pub realized_gain: BigDecimal,
+    /// Dividends received after fees, by year
+    pub dividends: BTreeMap<i32, BigDecimal>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error("selling {sold} units while holding {held}")]
+    Oversold { sold: BigDecimal, held: BigDecimal },
+}
+
+impl Position {
+    /// Cost of a unit held on average. `None` when nothing is held
+    pub fn average_cost(&self) -> Option<BigDecimal> {
+        if self.quantity.is_zero() {
+            return None;
+        }
+        Some((&self.cost_basis / &self.quantity).round(4))
+    }
+
+    pub fn apply(&mut self, transaction: &Transaction) -> Result<(), ReplayError> {
+        match transaction.kind {
+            Kind::Buy => {
+                self.quantity += &transaction.quantity;
+                self.cost_basis += &transaction.amount + &transaction.fee;
+            }
+            Kind::Sell => {
+                if transaction.quantity > self.quantity {
+                    return Err(ReplayError::Oversold {
+                        sold: transaction.quantity.clone(),
+                        held: self.quantity.clone(),
+                    });
+                }
+                let cost = if transaction.quantity == self.quantity {
+                    self.cost_basis.clone()
+                } else {
+                    (&self.cost_basis * &transaction.quantity / &self.quantity).round(2)
+                };
+                self.realized_gain += &transaction.amount - &transaction.fee - &cost;
+                self.quantity -= &transaction.quantity;
+                self.cost_basis -= cost;
+            }
+            Kind::Dividend => {
+                *self.dividends.entry(transaction.year).or_default() +=
+                    &transaction.amount - &transaction.fee;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replay the transactions of a security from the beginning
+pub fn replay<'a>(
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+) -> Result<Position, ReplayError> {
+    let mut position = Position::default();
+    for transaction in transactions {
+        position.apply(transaction)?;
+    }
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(kind: Kind, quantity: &str, amount: &str, fee: &str) -> Transaction {
+        Transaction {
+            kind,
+            quantity: quantity.parse().unwrap(),
+            amount: amount.parse().unwrap(),
+            fee: fee.parse().unwrap(),
+            year: 2025,
+        }
+    }
+
+    fn decimal(s: &str) -> BigDecimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_average_cost() {
+        let position = replay(&[
+            transaction(Kind::Buy, "1000", "50000", "20"),
+            transaction(Kind::Buy, "1000", "60000", "20"),
+        ])
+        .unwrap();
+        assert_eq!(decimal("2000"), position.quantity);
+        assert_eq!(decimal("110040"), position.cost_basis);
+        assert_eq!(Some(decimal("55.02")), position.average_cost());
+    }
+
+    #[test]
+    fn test_realized_gain() {
+        let position = replay(&[
+            transaction(Kind::Buy, "1000", "50000", "0"),
+            transaction(Kind::Buy, "1000", "60000", "0"),
+            transaction(Kind::Sell, "500", "35000", "50"),
+        ])
+        .unwrap();
+        assert_eq!(decimal("1500"), position.quantity);
+        assert_eq!(decimal("82500"), position.cost_basis);
+        assert_eq!(decimal("7450"), position.realized_gain);
+        let position = replay(&[
+            transaction(Kind::Buy, "3", "100", "0"),
+            transaction(Kind::Sell, "3", "90", "0"),
+        ])
+        .unwrap();
+        assert!(position.cost_basis.is_zero());
+        assert_eq!(None, position.average_cost());
+        assert_eq!(decimal("-10"), position.realized_gain);
+    }
+
+    #[test]
+    fn test_oversold() {
+        assert_eq!(
+            Err(ReplayError::Oversold {
+                sold: decimal("2"),
+                held: decimal("1"),
+            }),
+            replay(&[
+                transaction(Kind::Buy, "1", "100", "0"),
+                transaction(Kind::Sell, "2", "200", "0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_dividends_by_year() {
+        let mut next_year = transaction(Kind::Dividend, "0", "800", "10");
+        next_year.year = 2026;
+        let position = replay(&[
+            transaction(Kind::Dividend, "0", "1200", "10"),
+            next_year.clone(),
+            next_year,
+        ])
+        .unwrap();
+        assert_eq!(Some(&decimal("1190")), position.dividends.get(&2025));
+        assert_eq!(Some(&decimal("1580")), position.dividends.get(&2026));
+        assert!(position.quantity.is_zero());
+    }
+}
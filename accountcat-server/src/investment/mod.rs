@@ -0,0 +1,3 @@
+pub mod cli;
+pub mod holding;
+pub mod price;
@@ -0,0 +1,124 @@
+use std::io::Read;
+
+use num_traits::Zero;
+use serde::Deserialize;
+use sqlx::{PgExecutor, types::BigDecimal};
+use thiserror::Error;
+use time::Date;
+
+use crate::dateutils::parse_date;
+
+/// Closing price of a security on a date
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    pub symbol: String,
+    pub date: Date,
+    pub close: BigDecimal,
+}
+
+#[derive(Deserialize)]
+struct PriceRow {
+    symbol: String,
+    date: String,
+    close: String,
+}
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("malformed csv {0}")]
+    Csv(#[from] csv::Error),
+    #[error("line {line}: bad date {value}, expecting YYYY-MM-DD")]
+    Date { line: u64, value: String },
+    #[error("line {line}: bad price {value}")]
+    Price { line: u64, value: String },
+    #[error("line {line}: missing symbol")]
+    Symbol { line: u64 },
+}
+
+/// Parse prices from a CSV file with `symbol`, `date` and `close` columns, in any order
+pub fn parse_csv<R: Read>(reader: R) -> Result<Vec<Price>, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let mut prices = Vec::new();
+    for row in reader.deserialize() {
+        let row: PriceRow = row?;
+        let line = prices.len() as u64 + 2;
+        if row.symbol.is_empty() {
+            return Err(ParseError::Symbol { line });
+        }
+        let Some(date) = parse_date(&row.date) else {
+            return Err(ParseError::Date {
+                line,
+                value: row.date,
+            });
+        };
+        let close = match row.close.replace(',', "").parse::<BigDecimal>() {
+            Ok(close) if close > BigDecimal::zero() => close,
+            _ => {
+                return Err(ParseError::Price {
+                    line,
+                    value: row.close,
+                });
+            }
+        };
+        prices.push(Price {
+            symbol: row.symbol.to_uppercase(),
+            date,
+            close,
+        });
+    }
+    Ok(prices)
+}
+
+/// Store the prices, replacing existing prices of the same security and date
+pub async fn import<'e, E: PgExecutor<'e>>(executor: E, prices: &[Price]) -> sqlx::Result<u64> {
+    let symbols: Vec<&str> = prices.iter().map(|x| x.symbol.as_str()).collect();
+    let dates: Vec<Date> = prices.iter().map(|x| x.date).collect();
+    let closes: Vec<BigDecimal> = prices.iter().map(|x| x.close.clone()).collect();
+    let result = sqlx::query!(
+        "insert into security_prices (symbol, date, close)
+select * from unnest($1::text[], $2::date[], $3::numeric[])
+on conflict (symbol, date) do update set close = excluded.close",
+        &symbols as &[&str],
+        &dates[..],
+        &closes[..],
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let prices = parse_csv(
+            "date,symbol,close\n2025-10-01, 0050 ,\"1,012.5\"\n2025-10-02,vt,130.25\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(2, prices.len());
+        assert_eq!("0050", prices[0].symbol);
+        assert_eq!("1012.5".parse::<BigDecimal>().unwrap(), prices[0].close);
+        assert_eq!("VT", prices[1].symbol);
+        assert_eq!(parse_date("2025-10-02").unwrap(), prices[1].date);
+    }
+
+    #[test]
+    fn test_parse_csv_errors() {
+        assert!(matches!(
+            parse_csv("symbol,date,close\n0050,2025/10/01,100\n".as_bytes()),
+            Err(ParseError::Date { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_csv("symbol,date,close\n0050,2025-10-01,100\n0050,2025-10-02,-1\n".as_bytes()),
+            Err(ParseError::Price { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse_csv("symbol,close\n0050,100\n".as_bytes()),
+            Err(ParseError::Csv(_))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use std::str::FromStr;
+
+use sqlx::{PgExecutor, PgPool};
+use tonic::{Request, Status};
+use tracing::error;
+
+use crate::{auth::claims_from_request, idcodec::Namespace, idl, server::ServerState};
+
+/// Request metadata selecting the ledger to operate on. The personal ledger is used without it
+pub const LEDGER_ID_METADATA: &str = "x-ledger-id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<idl::ledger::Role> for Role {
+    fn from(value: idl::ledger::Role) -> Self {
+        match value {
+            idl::ledger::Role::Viewer => Role::Viewer,
+            idl::ledger::Role::Editor => Role::Editor,
+            idl::ledger::Role::Owner => Role::Owner,
+        }
+    }
+}
+
+impl From<Role> for idl::ledger::Role {
+    fn from(value: Role) -> Self {
+        match value {
+            Role::Viewer => idl::ledger::Role::Viewer,
+            Role::Editor => idl::ledger::Role::Editor,
+            Role::Owner => idl::ledger::Role::Owner,
+        }
+    }
+}
+
+/// Membership of the requesting user in a ledger
+#[derive(Debug, Clone, Copy)]
+pub struct LedgerAccess {
+    pub ledger_id: i32,
+    pub user_id: i32,
+    pub role: Role,
+}
+
+/// Raw value of the ledger metadata, if the request has one
+pub fn requested_ledger<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get(LEDGER_ID_METADATA)
+        .and_then(|x| x.to_str().ok())
+        .filter(|x| !x.is_empty())
+}
+
+/// Resolve the ledger the user operates on and check the user has at least the required role.
+/// The personal ledger of the user is used when `ledger_id` is `None`.
+pub async fn authorize(
+    database: &PgPool,
+    sub: &str,
+    ledger_id: Option<i32>,
+    required: Role,
+) -> tonic::Result<LedgerAccess> {
+    let access = match ledger_id {
+        Some(ledger_id) => find_membership(database, sub, ledger_id).await,
+        None => match find_personal_ledger(database, sub).await {
+            Ok(None) => {
+                ensure_personal_ledger(database, sub).await.map_err(|err| {
+                    error!(action = "create personal ledger", error = ?err);
+                    Status::internal(String::new())
+                })?;
+                find_personal_ledger(database, sub).await
+            }
+            x => x,
+        },
+    };
+    let access = match access {
+        Ok(Some(access)) => access,
+        Ok(None) => return Err(Status::permission_denied("not a member of the ledger")),
+        Err(err) => {
+            error!(action = "authorize ledger", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+    };
+    if access.role < required {
+        return Err(Status::permission_denied(format!(
+            "requires {} role of the ledger",
+            required.as_str()
+        )));
+    }
+    Ok(access)
+}
+
+/// [authorize] the user of the request in the ledger its metadata selects
+pub async fn authorize_request<T>(
+    state: &ServerState,
+    request: &Request<T>,
+    required: Role,
+) -> tonic::Result<LedgerAccess> {
+    let claims = claims_from_request(request)?;
+    let ledger_id = match requested_ledger(request) {
+        Some(id) => Some(
+            state
+                .ids
+                .decode(Namespace::Ledger, id)
+                .ok_or_else(|| Status::invalid_argument("bad ledger id"))?,
+        ),
+        None => None,
+    };
+    authorize(&state.database, &claims.sub, ledger_id, required).await
+}
+
+/// Create the personal ledger of the user if it doesn't have one yet
+pub async fn ensure_personal_ledger<'e, E: PgExecutor<'e>>(
+    executor: E,
+    sub: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "with ledger as (
+    insert into ledgers (name, personal_user_id)
+    select 'Personal', users.id
+    from users
+    where users.google_sub = $1
+    on conflict (personal_user_id) do nothing
+    returning ledgers.id, ledgers.personal_user_id
+)
+insert into ledger_members (ledger_id, user_id, role)
+select ledger.id, ledger.personal_user_id, 'owner'
+from ledger",
+        sub
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+async fn find_membership(
+    database: &PgPool,
+    sub: &str,
+    ledger_id: i32,
+) -> sqlx::Result<Option<LedgerAccess>> {
+    let record = sqlx::query!(
+        "select ledger_members.ledger_id, ledger_members.user_id, ledger_members.role
+from ledger_members
+join users on users.id = ledger_members.user_id
+where users.google_sub = $1 and ledger_members.ledger_id = $2",
+        sub,
+        ledger_id
+    )
+    .fetch_optional(database)
+    .await?;
+    Ok(record.and_then(|r| {
+        Some(LedgerAccess {
+            ledger_id: r.ledger_id,
+            user_id: r.user_id,
+            role: r.role.parse().ok()?,
+        })
+    }))
+}
+
+async fn find_personal_ledger(database: &PgPool, sub: &str) -> sqlx::Result<Option<LedgerAccess>> {
+    let record = sqlx::query!(
+        "select ledger_members.ledger_id, ledger_members.user_id, ledger_members.role
+from ledger_members
+join ledgers on ledgers.id = ledger_members.ledger_id
+join users on users.id = ledger_members.user_id
+where users.google_sub = $1 and ledgers.personal_user_id = users.id",
+        sub
+    )
+    .fetch_optional(database)
+    .await?;
+    Ok(record.and_then(|r| {
+        Some(LedgerAccess {
+            ledger_id: r.ledger_id,
+            user_id: r.user_id,
+            role: r.role.parse().ok()?,
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_order() {
+        assert!(Role::Owner > Role::Editor);
+        assert!(Role::Editor > Role::Viewer);
+    }
+
+    #[test]
+    fn test_role_round_trip() {
+        for role in [Role::Viewer, Role::Editor, Role::Owner] {
+            assert_eq!(Ok(role), role.as_str().parse());
+        }
+        assert!("admin".parse::<Role>().is_err());
+    }
+}
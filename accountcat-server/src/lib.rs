@@ -1,14 +1,32 @@
+pub mod amount;
+pub mod anomaly;
 mod auth;
+pub mod billing;
 pub mod config;
 pub mod csp;
+pub mod dateutils;
+pub mod duplicate;
+pub mod forecast;
+pub mod heatmap;
+pub mod idcodec;
 pub mod idl;
+pub mod import;
+pub mod installment;
+pub mod investment;
 pub mod jwtutils;
+pub mod ledger;
 pub mod middleware;
 pub mod migration;
+pub mod period;
 pub mod pki;
 pub mod protobufutils;
+pub mod quickadd;
+pub mod report;
+pub mod savings;
+pub mod search;
 mod secret_se;
 pub mod serve_dist;
 pub mod server;
 pub mod service;
+pub mod split;
 pub mod testing;
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use accountcat::{
     config::Config,
-    pki,
+    investment, pki, report,
     server::{self, ServerArg},
 };
 use clap::{Parser, Subcommand};
@@ -26,6 +26,10 @@ enum Command {
     Settings,
     /// Public key infrastructure management
     Pki(pki::cli::Command),
+    /// Investment data management
+    Investment(investment::cli::Command),
+    /// Render reports
+    Report(report::cli::Command),
 }
 
 impl Default for Command {
@@ -43,5 +47,7 @@ async fn main() {
         Command::Migrate => accountcat::migration::run(&config).await,
         Command::Settings => config.print_settings(),
         Command::Pki(pki_cli) => pki_cli.run(&config).await,
+        Command::Investment(investment_cli) => investment_cli.run(&config).await,
+        Command::Report(report_cli) => report_cli.run(&config).await,
     }
 }
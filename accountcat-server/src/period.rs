@@ -0,0 +1,169 @@
+use time::{Date, Duration, Month, Weekday};
+
+use crate::dateutils::format_date;
+
+/// Latest day months can start on, so that every month has it
+pub const MAX_MONTH_START_DAY: u8 = 28;
+
+/// A month of summaries, which runs from payday to payday when the user starts months on a day
+/// other than the 1st
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    /// First day of the calendar month the period is named after
+    pub month: Date,
+    pub start: Date,
+    /// First day after the period
+    pub end: Date,
+}
+
+fn next_month(month: Date) -> Date {
+    let month = month.replace_day(1).unwrap();
+    month + Duration::days(i64::from(month.month().length(month.year())))
+}
+
+fn previous_month(month: Date) -> Date {
+    (month.replace_day(1).unwrap() - Duration::days(1))
+        .replace_day(1)
+        .unwrap()
+}
+
+/// First day of the period named after the month of `month`. Periods starting after the 15th are
+/// named after the month they end in, where most of their days are, so with months starting on the
+/// 25th, March runs from February 25 to March 24
+fn start_of(month: Date, start_day: u8) -> Date {
+    let month = month.replace_day(1).unwrap();
+    let start_day = start_day.clamp(1, MAX_MONTH_START_DAY);
+    if start_day > 15 {
+        previous_month(month).replace_day(start_day).unwrap()
+    } else {
+        month.replace_day(start_day).unwrap()
+    }
+}
+
+impl Period {
+    /// The period named after the month of `month`
+    pub fn month(month: Date, start_day: u8) -> Period {
+        let month = month.replace_day(1).unwrap();
+        Period {
+            month,
+            start: start_of(month, start_day),
+            end: start_of(next_month(month), start_day),
+        }
+    }
+
+    /// The period `date` is in
+    pub fn containing(date: Date, start_day: u8) -> Period {
+        let period = Period::month(date, start_day);
+        if date < period.start {
+            Period::month(previous_month(date), start_day)
+        } else if date >= period.end {
+            Period::month(next_month(date), start_day)
+        } else {
+            period
+        }
+    }
+
+    pub fn previous(&self, start_day: u8) -> Period {
+        Period::month(previous_month(self.month), start_day)
+    }
+
+    /// Last day of the period
+    pub fn last_day(&self) -> Date {
+        self.end - Duration::days(1)
+    }
+
+    /// `YYYY-MM` of calendar months, otherwise the dates the period runs between
+    pub fn label(&self) -> String {
+        if self.start == self.month && self.end == next_month(self.month) {
+            format!(
+                "{:04}-{:02}",
+                self.month.year(),
+                u8::from(self.month.month())
+            )
+        } else {
+            format!(
+                "{} – {}",
+                format_date(self.start),
+                format_date(self.last_day())
+            )
+        }
+    }
+}
+
+/// The periods named after the months of `year`
+pub fn year(year: i32, start_day: u8) -> Vec<Period> {
+    let mut month = Date::from_calendar_date(year, Month::January, 1).unwrap();
+    let mut periods = Vec::with_capacity(12);
+    for _ in 0..12 {
+        periods.push(Period::month(month, start_day));
+        month = next_month(month);
+    }
+    periods
+}
+
+/// First day of the week `date` is in, for weeks starting on `week_start`
+pub fn week_start(date: Date, week_start: Weekday) -> Date {
+    let days =
+        (7 + date.weekday().number_days_from_monday() - week_start.number_days_from_monday()) % 7;
+    date - Duration::days(i64::from(days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: Month, day: u8) -> Date {
+        Date::from_calendar_date(2025, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_calendar_month() {
+        let march = Period::month(date(Month::March, 10), 1);
+        assert_eq!(date(Month::March, 1), march.start);
+        assert_eq!(date(Month::April, 1), march.end);
+        assert_eq!("2025-03", march.label());
+        assert_eq!(date(Month::February, 1), march.previous(1).start);
+    }
+
+    #[test]
+    fn test_early_start_day() {
+        let march = Period::month(date(Month::March, 1), 5);
+        assert_eq!(date(Month::March, 5), march.start);
+        assert_eq!(date(Month::April, 4), march.last_day());
+        assert_eq!("2025-03-05 – 2025-04-04", march.label());
+        assert_eq!(
+            date(Month::February, 1),
+            Period::containing(date(Month::March, 4), 5).month
+        );
+    }
+
+    #[test]
+    fn test_late_start_day() {
+        let march = Period::month(date(Month::March, 1), 25);
+        assert_eq!(date(Month::February, 25), march.start);
+        assert_eq!(date(Month::March, 24), march.last_day());
+        assert_eq!(
+            date(Month::April, 1),
+            Period::containing(date(Month::March, 25), 25).month
+        );
+        let periods = year(2025, 25);
+        assert_eq!(
+            Date::from_calendar_date(2024, Month::December, 25).unwrap(),
+            periods[0].start
+        );
+        assert!(periods.windows(2).all(|x| x[0].end == x[1].start));
+        assert_eq!(date(Month::December, 25), periods[11].end);
+    }
+
+    #[test]
+    fn test_week_start() {
+        // a Wednesday
+        let today = date(Month::March, 12);
+        assert_eq!(date(Month::March, 10), week_start(today, Weekday::Monday));
+        assert_eq!(date(Month::March, 9), week_start(today, Weekday::Sunday));
+        assert_eq!(
+            date(Month::March, 12),
+            week_start(today, Weekday::Wednesday)
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use sqlx::types::BigDecimal;
+use time::{Date, Duration, Month, Time, Weekday};
+
+use crate::{amount::parse_currency, dateutils::parse_date};
+
+/// Interpretation of a quick entry line like `午餐 120 #food 昨天` or
+/// `taxi 350 JPY yesterday 21:00`. Words are separated by whitespace
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Entry {
+    /// Words that aren't anything else
+    pub name: String,
+    /// Absolute amount. The last plain number of the line
+    pub amount: Option<BigDecimal>,
+    /// The amount has a `+` sign
+    pub income: bool,
+    pub currency: Option<String>,
+    /// Names of the `#tags`
+    pub tags: Vec<String>,
+    pub date: Option<Date>,
+    pub time: Option<Time>,
+}
+
+enum Token {
+    Word,
+    Amount(BigDecimal, bool),
+    Currency(String),
+    Tag(String),
+    Date(Date),
+    Time(Time),
+}
+
+/// Parse a quick entry. Relative dates are relative to `today`
+pub fn parse(text: &str, today: Date) -> Entry {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut tokens: Vec<(Token, String)> = Vec::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        // phrases like `2 days ago` or `last monday`
+        if let Some((date, length)) = parse_phrase(&words[i..], today) {
+            tokens.push((Token::Date(date), words[i..i + length].join(" ")));
+            i += length;
+            continue;
+        }
+        tokens.push((classify(words[i], today), words[i].to_string()));
+        i += 1;
+    }
+    // only the last plain number is the amount, like the price in `iphone 15 30000`
+    let amount_index = tokens
+        .iter()
+        .rposition(|(token, _)| matches!(token, Token::Amount(..)));
+    let mut entry = Entry::default();
+    let mut name = Vec::new();
+    for (i, (token, word)) in tokens.into_iter().enumerate() {
+        match token {
+            Token::Amount(amount, income) if Some(i) == amount_index => {
+                entry.amount = Some(amount);
+                entry.income = income;
+            }
+            Token::Currency(currency) if entry.currency.is_none() => {
+                entry.currency = Some(currency)
+            }
+            Token::Tag(tag) => entry.tags.push(tag),
+            Token::Date(date) if entry.date.is_none() => entry.date = Some(date),
+            Token::Time(time) if entry.time.is_none() => entry.time = Some(time),
+            _ => name.push(word),
+        }
+    }
+    entry.name = name.join(" ");
+    entry
+}
+
+fn parse_phrase(words: &[&str], today: Date) -> Option<(Date, usize)> {
+    match words {
+        [n, "days" | "day", "ago", ..] => Some((days_before(today, n.parse().ok()?)?, 3)),
+        ["last", weekday, ..] => {
+            let weekday = parse_weekday(weekday)?;
+            Some((previous_weekday(today - Duration::days(1), weekday), 2))
+        }
+        _ => None,
+    }
+}
+
+fn classify(word: &str, today: Date) -> Token {
+    if let Some(tag) = word.strip_prefix('#').or_else(|| word.strip_prefix('＃'))
+        && !tag.is_empty()
+    {
+        return Token::Tag(tag.to_string());
+    }
+    if let Some((amount, income)) = parse_amount(word) {
+        return Token::Amount(amount, income);
+    }
+    if word.len() == 3 && word.chars().all(|c| c.is_ascii_alphabetic()) {
+        let code = word.to_ascii_uppercase();
+        if parse_currency(&code).is_ok() {
+            return Token::Currency(code);
+        }
+    }
+    if let Some(date) = parse_date_word(word, today) {
+        return Token::Date(date);
+    }
+    if let Some(time) = parse_time(word) {
+        return Token::Time(time);
+    }
+    Token::Word
+}
+
+fn parse_amount(word: &str) -> Option<(BigDecimal, bool)> {
+    let (word, income) = match word.strip_prefix('+') {
+        Some(x) => (x, true),
+        None => (word.strip_prefix('-').unwrap_or(word), false),
+    };
+    let word = word
+        .strip_prefix('$')
+        .unwrap_or(word)
+        .trim_end_matches(['元', '塊']);
+    if word.is_empty()
+        || !word.starts_with(|c: char| c.is_ascii_digit())
+        || !word
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    {
+        return None;
+    }
+    let amount = word.replace(',', "").parse().ok()?;
+    Some((amount, income))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    let word = word.to_lowercase();
+    let english = match word.as_str() {
+        "monday" | "mon" => Some(Weekday::Monday),
+        "tuesday" | "tue" => Some(Weekday::Tuesday),
+        "wednesday" | "wed" => Some(Weekday::Wednesday),
+        "thursday" | "thu" => Some(Weekday::Thursday),
+        "friday" | "fri" => Some(Weekday::Friday),
+        "saturday" | "sat" => Some(Weekday::Saturday),
+        "sunday" | "sun" => Some(Weekday::Sunday),
+        _ => None,
+    };
+    if english.is_some() {
+        return english;
+    }
+    let day = ["週", "周", "星期", "禮拜", "礼拜"]
+        .iter()
+        .find_map(|prefix| word.strip_prefix(prefix))?;
+    match day {
+        "一" => Some(Weekday::Monday),
+        "二" => Some(Weekday::Tuesday),
+        "三" => Some(Weekday::Wednesday),
+        "四" => Some(Weekday::Thursday),
+        "五" => Some(Weekday::Friday),
+        "六" => Some(Weekday::Saturday),
+        "日" | "天" => Some(Weekday::Sunday),
+        _ => None,
+    }
+}
+
+/// The date `days` before `date`, or none if out of the supported range
+fn days_before(date: Date, days: i64) -> Option<Date> {
+    let day = i64::from(date.to_julian_day()).checked_sub(days)?;
+    Date::from_julian_day(day.try_into().ok()?).ok()
+}
+
+/// The latest `weekday` on or before `date`
+fn previous_weekday(date: Date, weekday: Weekday) -> Date {
+    let days = (7 + date.weekday().number_days_from_monday() as i64
+        - weekday.number_days_from_monday() as i64)
+        % 7;
+    date - Duration::days(days)
+}
+
+fn parse_date_word(word: &str, today: Date) -> Option<Date> {
+    let lower = word.to_lowercase();
+    let offset = match lower.as_str() {
+        "today" | "今天" | "今日" => Some(0),
+        "yesterday" | "昨天" | "昨日" => Some(-1),
+        "前天" => Some(-2),
+        "大前天" => Some(-3),
+        "tomorrow" | "明天" => Some(1),
+        _ => None,
+    };
+    if let Some(offset) = offset {
+        return Some(today + Duration::days(offset));
+    }
+    if let Some(days) = lower
+        .strip_suffix("天前")
+        .and_then(|x| x.parse::<i64>().ok())
+    {
+        return days_before(today, days);
+    }
+    if let Some(weekday) = parse_weekday(&lower) {
+        return Some(previous_weekday(today, weekday));
+    }
+    if let Some(date) = parse_date(word) {
+        return Some(date);
+    }
+    // month and day of the latest year not after today, like `3/5` or `3月5日`
+    let (month, day) = match word.split_once('/') {
+        Some(x) => x,
+        None => {
+            let (month, day) = word.split_once('月')?;
+            (month, day.strip_suffix(['日', '號', '号'])?)
+        }
+    };
+    let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+    let day: u8 = day.parse().ok()?;
+    let date = Date::from_calendar_date(today.year(), month, day).ok()?;
+    if date > today {
+        Date::from_calendar_date(today.year() - 1, month, day).ok()
+    } else {
+        Some(date)
+    }
+}
+
+fn parse_time(word: &str) -> Option<Time> {
+    let lower = word.to_lowercase();
+    if let Some((hour, minute)) = lower.split_once(':') {
+        return Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok();
+    }
+    // 9pm, 11am
+    for (suffix, offset) in [("am", 0), ("pm", 12)] {
+        if let Some(hour) = lower.strip_suffix(suffix) {
+            let hour: u8 = hour.parse().ok()?;
+            if !(1..=12).contains(&hour) {
+                return None;
+            }
+            return Time::from_hms(hour % 12 + offset, 0, 0).ok();
+        }
+    }
+    // 晚上9點, 下午3點半
+    let (offset, rest) = [
+        ("凌晨", 0),
+        ("早上", 0),
+        ("上午", 0),
+        ("中午", 12),
+        ("下午", 12),
+        ("晚上", 12),
+    ]
+    .iter()
+    .find_map(|(prefix, offset)| lower.strip_prefix(prefix).map(|rest| (*offset, rest)))
+    .unwrap_or((0, lower.as_str()));
+    let (hour, minute) = rest
+        .split_once(['點', '点'])
+        .or_else(|| rest.split_once('時'))?;
+    let hour: u8 = hour.parse().ok()?;
+    let minute = match minute.trim_end_matches('分') {
+        "" => 0,
+        "半" => 30,
+        x => x.parse().ok()?,
+    };
+    let hour = if offset == 12 && hour < 12 {
+        hour + 12
+    } else {
+        hour
+    };
+    Time::from_hms(hour, minute, 0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> Date {
+        // a Wednesday
+        Date::from_calendar_date(2025, Month::March, 12).unwrap()
+    }
+
+    fn date(month: Month, day: u8) -> Option<Date> {
+        Some(Date::from_calendar_date(2025, month, day).unwrap())
+    }
+
+    #[test]
+    fn test_parse_chinese() {
+        let entry = parse("午餐 120 #food 昨天", today());
+        assert_eq!("午餐", entry.name);
+        assert_eq!(Some(BigDecimal::from(120)), entry.amount);
+        assert!(!entry.income);
+        assert_eq!(None, entry.currency);
+        assert_eq!(vec![String::from("food")], entry.tags);
+        assert_eq!(date(Month::March, 11), entry.date);
+        assert_eq!(None, entry.time);
+
+        let entry = parse("薪水 +50,000元 3月5日 晚上9點半", today());
+        assert_eq!("薪水", entry.name);
+        assert_eq!(Some(BigDecimal::from(50000)), entry.amount);
+        assert!(entry.income);
+        assert_eq!(date(Month::March, 5), entry.date);
+        assert_eq!(Time::from_hms(21, 30, 0).ok(), entry.time);
+
+        assert_eq!(date(Month::March, 10), parse("前天", today()).date);
+        assert_eq!(date(Month::March, 9), parse("週日", today()).date);
+        assert_eq!(date(Month::March, 7), parse("5天前", today()).date);
+    }
+
+    #[test]
+    fn test_parse_english() {
+        let entry = parse("taxi 350 JPY yesterday 21:00", today());
+        assert_eq!("taxi", entry.name);
+        assert_eq!(Some(BigDecimal::from(350)), entry.amount);
+        assert_eq!(Some(String::from("JPY")), entry.currency);
+        assert_eq!(date(Month::March, 11), entry.date);
+        assert_eq!(Time::from_hms(21, 0, 0).ok(), entry.time);
+
+        let entry = parse("iphone 15 case 19.99 usd 2 days ago 9pm #gadget", today());
+        assert_eq!("iphone 15 case", entry.name);
+        assert_eq!(Some("19.99".parse().unwrap()), entry.amount);
+        assert_eq!(Some(String::from("USD")), entry.currency);
+        assert_eq!(date(Month::March, 10), entry.date);
+        assert_eq!(Time::from_hms(21, 0, 0).ok(), entry.time);
+        assert_eq!(vec![String::from("gadget")], entry.tags);
+
+        assert_eq!(date(Month::March, 12), parse("wednesday", today()).date);
+        assert_eq!(date(Month::March, 5), parse("last wednesday", today()).date);
+        assert_eq!(
+            date(Month::March, 3),
+            parse("lunch 2025-03-03", today()).date
+        );
+        // not in the future
+        assert_eq!(
+            Date::from_calendar_date(2024, Month::December, 24).ok(),
+            parse("12/24", today()).date
+        );
+    }
+
+    #[test]
+    fn test_parse_out_of_range_dates() {
+        let entry = parse("lunch 99999999999 days ago 120", today());
+        assert_eq!("lunch 99999999999 days ago", entry.name);
+        assert_eq!(Some(BigDecimal::from(120)), entry.amount);
+        assert_eq!(None, entry.date);
+        let entry = parse("午餐 120 99999999天前", today());
+        assert_eq!("午餐 99999999天前", entry.name);
+        assert_eq!(None, entry.date);
+    }
+
+    #[test]
+    fn test_parse_without_amount() {
+        let entry = parse("coffee with tom", today());
+        assert_eq!("coffee with tom", entry.name);
+        assert_eq!(None, entry.amount);
+        assert_eq!(None, entry.date);
+    }
+}
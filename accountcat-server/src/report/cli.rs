@@ -0,0 +1,117 @@
+use std::{io::Write, path::PathBuf, process::exit};
+
+use clap::{Parser, Subcommand};
+use sqlx::PgPool;
+
+use crate::{
+    config::Config,
+    idcodec::{IdCodec, Namespace},
+    ledger::{self, Role},
+    report::{self, Format, current_month, format::Locale, parse_month},
+};
+
+#[derive(Parser)]
+pub struct Command {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Render the monthly statement of a ledger
+    Monthly(MonthlyArgs),
+}
+
+#[derive(Parser)]
+struct MonthlyArgs {
+    /// Google subject of the user the report is generated for
+    #[arg(short, long)]
+    user: String,
+    /// Month in the form of YYYY-MM [default: the current month]
+    #[arg(short, long)]
+    month: Option<String>,
+    /// Id of the ledger [default: the personal ledger of the user]
+    #[arg(short, long)]
+    ledger: Option<String>,
+    /// Language tag for formatting numbers, like en or zh-TW
+    #[arg(long, default_value = "en")]
+    locale: String,
+    /// Render PDF with the configured PDF command instead of HTML
+    #[arg(long)]
+    pdf: bool,
+    /// File to write the report to [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl MonthlyArgs {
+    async fn run(&self, config: &Config) {
+        let pool: PgPool = config.database.clone().into();
+        let month = self.month.as_deref().map(|month| {
+            parse_month(month).unwrap_or_else(|| {
+                println!("month must be in the form of YYYY-MM");
+                exit(1);
+            })
+        });
+        let locale: Locale = self.locale.parse().unwrap_or_else(|_| {
+            println!("unsupported locale {}", self.locale);
+            exit(1);
+        });
+        let ledger_id = self.ledger.as_deref().map(|id| {
+            IdCodec::from(&config.hashids)
+                .decode(Namespace::Ledger, id)
+                .unwrap_or_else(|| {
+                    println!("bad ledger id {id}");
+                    exit(1);
+                })
+        });
+        let access = ledger::authorize(&pool, &self.user, ledger_id, Role::Viewer)
+            .await
+            .unwrap_or_else(|status| {
+                println!("failed to access the ledger: {}", status.message());
+                exit(1);
+            });
+        let month = match month {
+            Some(month) => month,
+            None => current_month(&pool, access.user_id)
+                .await
+                .unwrap_or_else(|err| {
+                    println!("failed to get the current month: {err}");
+                    exit(1);
+                }),
+        };
+        let format = if self.pdf { Format::Pdf } else { Format::Html };
+        let report = match report::monthly(
+            &pool,
+            &access,
+            month,
+            locale,
+            format,
+            config.server.pdf_command.as_deref(),
+        )
+        .await
+        {
+            Ok(report) => report,
+            Err(err) => {
+                println!("failed to render the report: {err}");
+                exit(1);
+            }
+        };
+        let written = match &self.output {
+            Some(path) => std::fs::write(path, report),
+            None => std::io::stdout().write_all(&report),
+        };
+        if let Err(err) = written {
+            println!("failed to write the report: {err}");
+            exit(1);
+        }
+    }
+}
+
+impl Command {
+    pub async fn run(&self, config: &Config) {
+        match &self.action {
+            Action::Monthly(args) => args.run(config).await,
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use sqlx::{PgPool, types::BigDecimal};
+use time::Date;
+
+use crate::period::Period;
+
+/// Number of tags listed by their spending
+pub const TOP_TAGS: i64 = 5;
+
+/// Number of the biggest expenses listed
+pub const BIGGEST_TRANSACTIONS: i64 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Totals {
+    pub income: BigDecimal,
+    /// Positive sum of the expenses
+    pub expense: BigDecimal,
+    pub count: i64,
+}
+
+impl Totals {
+    pub fn net(&self) -> BigDecimal {
+        &self.income - &self.expense
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagTotal {
+    pub name: String,
+    pub expense: BigDecimal,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub date: Date,
+    pub name: String,
+    pub expense: BigDecimal,
+    pub tags: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayTotal {
+    pub date: Date,
+    pub income: BigDecimal,
+    pub expense: BigDecimal,
+    pub count: i64,
+}
+
+/// Figures of a ledger for a month in the time zone of the user, from the month start day of the
+/// user. Only the items in the base currency of the user are counted, and purchases paid in
+/// installments are counted the way the user prefers
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlyReport {
+    pub ledger_name: String,
+    pub currency: String,
+    pub period: Period,
+    pub previous_period: Period,
+    pub current: Totals,
+    pub previous: Totals,
+    pub top_tags: Vec<TagTotal>,
+    pub biggest: Vec<Transaction>,
+    pub days: Vec<DayTotal>,
+    /// Items of the month in other currencies
+    pub unsupported_count: i64,
+}
+
+/// Load the report of the period named after the month of `month`
+pub async fn load(
+    database: &PgPool,
+    ledger_id: i32,
+    user_id: i32,
+    month: Date,
+) -> sqlx::Result<MonthlyReport> {
+    let header = sqlx::query!(
+        "select ledgers.name, users.base_currency, users.month_start_day, users.time_zone
+from ledgers, users
+where ledgers.id = $1 and users.id = $2",
+        ledger_id,
+        user_id
+    )
+    .fetch_one(database)
+    .await?;
+    let currency = header.base_currency;
+    let time_zone = header.time_zone;
+    let start_day = header.month_start_day as u8;
+    let period = Period::month(month, start_day);
+    let previous_period = period.previous(start_day);
+    let (current, unsupported_count) =
+        load_totals(database, ledger_id, user_id, &currency, &period, &time_zone).await?;
+    let (previous, _) = load_totals(
+        database,
+        ledger_id,
+        user_id,
+        &currency,
+        &previous_period,
+        &time_zone,
+    )
+    .await?;
+    let top_tags = sqlx::query_as!(
+        TagTotal,
+        r#"select tags.name, -sum(net_amount(accounting_items)) as "expense!", count(*) as "count!"
+from accounting_items
+join accounting_item_tags on accounting_item_tags.accounting_item_id = accounting_items.id
+join tags on tags.id = accounting_item_tags.tag_id
+where accounting_items.ledger_id = $1
+      and accounting_items.currency = $2
+      and accounting_items.amount < 0
+      and accounting_items.occurred_at >= $3::date::timestamp at time zone $7
+      and accounting_items.occurred_at < $6::date::timestamp at time zone $7
+      and counted(accounting_items, $5)
+group by tags.id, tags.name
+order by 2 desc, tags.name
+limit $4"#,
+        ledger_id,
+        currency,
+        period.start,
+        TOP_TAGS,
+        user_id,
+        period.end,
+        &time_zone,
+    )
+    .fetch_all(database)
+    .await?;
+    let biggest = sqlx::query_as!(
+        Transaction,
+        r#"select (accounting_items.occurred_at at time zone $7)::date as "date!",
+       coalesce(accounting_items.name, '') as "name!",
+       -accounting_items.amount as "expense!",
+       coalesce((
+           select string_agg(tags.name, ', ' order by tags.name)
+           from accounting_item_tags
+           join tags on tags.id = accounting_item_tags.tag_id
+           where accounting_item_tags.accounting_item_id = accounting_items.id
+       ), '') as "tags!"
+from accounting_items
+where accounting_items.ledger_id = $1
+      and accounting_items.currency = $2
+      and accounting_items.amount < 0
+      and accounting_items.occurred_at >= $3::date::timestamp at time zone $7
+      and accounting_items.occurred_at < $6::date::timestamp at time zone $7
+      and counted(accounting_items, $5)
+order by accounting_items.amount, accounting_items.occurred_at
+limit $4"#,
+        ledger_id,
+        currency,
+        period.start,
+        BIGGEST_TRANSACTIONS,
+        user_id,
+        period.end,
+        &time_zone,
+    )
+    .fetch_all(database)
+    .await?;
+    let days = sqlx::query_as!(
+        DayTotal,
+        r#"select days.day::date as "date!",
+       coalesce(sum(net_amount(accounting_items)) filter (where accounting_items.amount > 0), 0) as "income!",
+       coalesce(-sum(net_amount(accounting_items)) filter (where accounting_items.amount < 0), 0) as "expense!",
+       count(accounting_items.id) as "count!"
+from generate_series($3::date::timestamp, ($5::date - 1)::timestamp, interval '1 day') days(day)
+left join accounting_items
+       on accounting_items.ledger_id = $1
+      and accounting_items.currency = $2
+      and (accounting_items.occurred_at at time zone $6)::date = days.day::date
+      and counted(accounting_items, $4)
+group by days.day
+order by days.day"#,
+        ledger_id,
+        currency,
+        period.start,
+        user_id,
+        period.end,
+        &time_zone,
+    )
+    .fetch_all(database)
+    .await?;
+    Ok(MonthlyReport {
+        ledger_name: header.name,
+        currency,
+        period,
+        previous_period,
+        current,
+        previous,
+        top_tags,
+        biggest,
+        days,
+        unsupported_count,
+    })
+}
+
+async fn load_totals(
+    database: &PgPool,
+    ledger_id: i32,
+    user_id: i32,
+    currency: &str,
+    period: &Period,
+    time_zone: &str,
+) -> sqlx::Result<(Totals, i64)> {
+    let row = sqlx::query!(
+        r#"select coalesce(sum(net_amount(accounting_items)) filter (where currency = $2 and amount > 0), 0) as "income!",
+       coalesce(-sum(net_amount(accounting_items)) filter (where currency = $2 and amount < 0), 0) as "expense!",
+       count(*) filter (where currency = $2) as "count!",
+       count(*) filter (where currency <> $2) as "unsupported!"
+from accounting_items
+where ledger_id = $1
+      and occurred_at >= $3::date::timestamp at time zone $6
+      and occurred_at < $5::date::timestamp at time zone $6
+      and counted(accounting_items, $4)"#,
+        ledger_id,
+        currency,
+        period.start,
+        user_id,
+        period.end,
+        time_zone,
+    )
+    .fetch_one(database)
+    .await?;
+    Ok((
+        Totals {
+            income: row.income,
+            expense: row.expense,
+            count: row.count,
+        },
+        row.unsupported,
+    ))
+}
@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+
+/// Locales reports format numbers for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    ZhTw,
+    Ja,
+    De,
+    Fr,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("unsupported locale")]
+pub struct UnsupportedLocale;
+
+impl FromStr for Locale {
+    type Err = UnsupportedLocale;
+
+    /// Parse a language tag like `zh-TW` or `de-AT`. Only the language is considered
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        let language = tag.trim().split(['-', '_']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Ok(Locale::En),
+            "zh" => Ok(Locale::ZhTw),
+            "ja" => Ok(Locale::Ja),
+            "de" => Ok(Locale::De),
+            "fr" => Ok(Locale::Fr),
+            _ => Err(UnsupportedLocale),
+        }
+    }
+}
+
+impl Locale {
+    /// First supported locale of an `Accept-Language` header, in the order of preference
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut languages: Vec<(f32, &str)> = header
+            .split(',')
+            .map(|x| {
+                let mut parts = x.split(';');
+                let tag = parts.next().unwrap_or_default();
+                let quality = parts
+                    .find_map(|x| x.trim().strip_prefix("q="))
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or(1.0);
+                (quality, tag)
+            })
+            .collect();
+        languages.sort_by(|a, b| b.0.total_cmp(&a.0));
+        languages.into_iter().find_map(|(_, tag)| tag.parse().ok())
+    }
+
+    /// Language tag for the `lang` attribute of HTML
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::ZhTw => "zh-TW",
+            Locale::Ja => "ja",
+            Locale::De => "de",
+            Locale::Fr => "fr",
+        }
+    }
+
+    fn separators(self) -> (&'static str, &'static str) {
+        match self {
+            Locale::En | Locale::ZhTw | Locale::Ja => (",", "."),
+            Locale::De => (".", ","),
+            // narrow no-break space
+            Locale::Fr => ("\u{202f}", ","),
+        }
+    }
+
+    /// Format a number with `scale` decimal places, grouping the digits by thousands
+    pub fn format_number(self, value: &BigDecimal, scale: i64) -> String {
+        let (group, decimal) = self.separators();
+        let plain = value.round(scale).with_scale(scale).to_plain_string();
+        let (sign, plain) = match plain.strip_prefix('-') {
+            Some(x) => ("-", x),
+            None => ("", plain.as_str()),
+        };
+        let (integer, fraction) = match plain.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (plain, None),
+        };
+        let mut grouped = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push_str(group);
+            }
+            grouped.push(digit);
+        }
+        match fraction {
+            Some(fraction) => format!("{sign}{grouped}{decimal}{fraction}"),
+            None => format!("{sign}{grouped}"),
+        }
+    }
+
+    /// Format a change like `+12.5%`
+    pub fn format_change(self, ratio: &BigDecimal) -> String {
+        let percent = self.format_number(&(ratio * BigDecimal::from(100)), 1);
+        if percent.starts_with('-') {
+            format!("{percent}%")
+        } else {
+            format!("+{percent}%")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(s: &str) -> BigDecimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!(Ok(Locale::ZhTw), "zh-TW".parse());
+        assert_eq!(Ok(Locale::De), "de_AT".parse());
+        assert_eq!(Err(UnsupportedLocale), "xx".parse::<Locale>());
+        assert_eq!(
+            Some(Locale::Fr),
+            Locale::from_accept_language("xx, en;q=0.5, fr-CA;q=0.8")
+        );
+        assert_eq!(None, Locale::from_accept_language("xx"));
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(
+            "1,234,567.50",
+            Locale::En.format_number(&number("1234567.5"), 2)
+        );
+        assert_eq!(
+            "-1.234,57",
+            Locale::De.format_number(&number("-1234.567"), 2)
+        );
+        assert_eq!(
+            "12\u{202f}345",
+            Locale::Fr.format_number(&number("12345"), 0)
+        );
+        assert_eq!("999", Locale::Ja.format_number(&number("999.4"), 0));
+        assert_eq!("0.000", Locale::En.format_number(&number("0"), 3));
+    }
+
+    #[test]
+    fn test_format_change() {
+        assert_eq!("+12.5%", Locale::En.format_change(&number("0.125")));
+        assert_eq!("-50,0%", Locale::De.format_change(&number("-0.5")));
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+use tonic::Code;
+use tower_sessions::Session;
+use tracing::error;
+
+use crate::{
+    idcodec::Namespace,
+    jwtutils::Claims,
+    ledger::{self, Role},
+    report::{
+        self, Format, ReportError, current_month, format::Locale, parse_month, render::PdfError,
+    },
+    server::{SESSION_KEY_CLAIMS, ServerState},
+};
+
+#[derive(Clone)]
+struct ReportState {
+    state: Arc<ServerState>,
+    pdf_command: Option<Arc<[String]>>,
+}
+
+#[derive(Deserialize, Default)]
+struct MonthlyQuery {
+    /// `YYYY-MM`, the current month by default
+    month: Option<String>,
+    /// `html` or `pdf`
+    format: Option<String>,
+    /// Language tag for formatting numbers. Accept-Language is used without it
+    locale: Option<String>,
+    /// Id of the ledger. The personal ledger is used without it
+    ledger: Option<String>,
+}
+
+/// Routes of the reports, which require a login session
+pub fn router(state: Arc<ServerState>, pdf_command: Option<Vec<String>>) -> Router {
+    Router::new()
+        .route("/report/monthly", get(monthly))
+        .with_state(ReportState {
+            state,
+            pdf_command: pdf_command.map(Arc::from),
+        })
+}
+
+fn bad_request(message: &'static str) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+async fn monthly(
+    State(state): State<ReportState>,
+    session: Session,
+    headers: HeaderMap,
+    Query(query): Query<MonthlyQuery>,
+) -> Response {
+    let claims = match session.get::<Claims>(SESSION_KEY_CLAIMS).await {
+        Ok(Some(claims)) => claims,
+        Ok(None) => return StatusCode::UNAUTHORIZED.into_response(),
+        Err(err) => {
+            error!(action = "load session claims", error = ?err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let month = match query.month {
+        Some(month) => match parse_month(&month) {
+            Some(month) => Some(month),
+            None => return bad_request("month must be in the form of YYYY-MM"),
+        },
+        None => None,
+    };
+    let format = match query.format.as_deref().map(str::parse) {
+        Some(Ok(format)) => format,
+        Some(Err(())) => return bad_request("format must be html or pdf"),
+        None => Format::Html,
+    };
+    let locale = match query.locale.as_deref().map(str::parse) {
+        Some(Ok(locale)) => locale,
+        Some(Err(_)) => return bad_request("unsupported locale"),
+        None => headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|x| x.to_str().ok())
+            .and_then(Locale::from_accept_language)
+            .unwrap_or_default(),
+    };
+    let ledger_id = match query.ledger.as_deref() {
+        Some(id) => match state.state.ids.decode(Namespace::Ledger, id) {
+            Some(id) => Some(id),
+            None => return bad_request("bad ledger id"),
+        },
+        None => None,
+    };
+    let access = match ledger::authorize(
+        &state.state.database,
+        &claims.sub,
+        ledger_id,
+        Role::Viewer,
+    )
+    .await
+    {
+        Ok(access) => access,
+        Err(status) if status.code() == Code::PermissionDenied => {
+            return (StatusCode::FORBIDDEN, status.message().to_string()).into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let month = match month {
+        Some(month) => month,
+        None => match current_month(&state.state.database, access.user_id).await {
+            Ok(month) => month,
+            Err(err) => {
+                error!(action = "get current month", error = ?err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        },
+    };
+    match report::monthly(
+        &state.state.database,
+        &access,
+        month,
+        locale,
+        format,
+        state.pdf_command.as_deref(),
+    )
+    .await
+    {
+        Ok(body) => ([(header::CONTENT_TYPE, format.content_type())], body).into_response(),
+        Err(ReportError::Pdf(PdfError::NotConfigured)) => {
+            (StatusCode::NOT_IMPLEMENTED, "PDF reports aren't enabled").into_response()
+        }
+        Err(err) => {
+            error!(action = "render monthly report", error = ?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use sqlx::PgPool;
+use thiserror::Error;
+use time::{Date, Month};
+
+use crate::{
+    dateutils::user_now,
+    ledger::LedgerAccess,
+    report::{format::Locale, render::PdfError},
+};
+
+pub mod cli;
+pub mod data;
+pub mod format;
+pub mod http;
+pub mod render;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Html,
+    Pdf,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(Format::Html),
+            "pdf" => Ok(Format::Pdf),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Html => "text/html; charset=utf-8",
+            Format::Pdf => "application/pdf",
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ReportError {
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("failed to render the template: {0}")]
+    Template(#[from] askama::Error),
+    #[error(transparent)]
+    Pdf(#[from] PdfError),
+}
+
+/// Parse a month in the form of `YYYY-MM` into its first day
+pub fn parse_month(month: &str) -> Option<Date> {
+    let (year, month) = month.split_once('-')?;
+    let month: u8 = month.parse().ok()?;
+    Date::from_calendar_date(year.parse().ok()?, Month::try_from(month).ok()?, 1).ok()
+}
+
+/// First day of the current month in the time zone of the user
+pub async fn current_month(database: &PgPool, user_id: i32) -> sqlx::Result<Date> {
+    Ok(user_now(database, user_id)
+        .await?
+        .date()
+        .replace_day(1)
+        .unwrap())
+}
+
+/// Render the monthly statement of a ledger. `pdf_command` converts the HTML into PDF, see
+/// [render::render_pdf]
+pub async fn monthly(
+    database: &PgPool,
+    access: &LedgerAccess,
+    month: Date,
+    locale: Locale,
+    format: Format,
+    pdf_command: Option<&[String]>,
+) -> Result<Vec<u8>, ReportError> {
+    let report = data::load(database, access.ledger_id, access.user_id, month).await?;
+    let html = render::render_html(&report, locale)?;
+    match format {
+        Format::Html => Ok(html.into_bytes()),
+        Format::Pdf => Ok(render::render_pdf(pdf_command.unwrap_or_default(), &html).await?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(
+            Some(Date::from_calendar_date(2025, Month::February, 1).unwrap()),
+            parse_month("2025-02")
+        );
+        assert_eq!(None, parse_month("2025-13"));
+        assert_eq!(None, parse_month("2025-02-01"));
+        assert_eq!(None, parse_month("last month"));
+    }
+}
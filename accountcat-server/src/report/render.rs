@@ -0,0 +1,190 @@
+use std::process::Stdio;
+
+use askama::Template;
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{
+    amount::{exponent, parse_currency},
+    dateutils::format_date,
+    report::{data::MonthlyReport, format::Locale},
+};
+
+struct SummaryRow {
+    label: &'static str,
+    current: String,
+    previous: String,
+    change: String,
+}
+
+struct TagRow {
+    name: String,
+    expense: String,
+    share: String,
+    count: i64,
+}
+
+struct TransactionRow {
+    date: String,
+    name: String,
+    tags: String,
+    expense: String,
+}
+
+struct DayRow {
+    date: String,
+    income: String,
+    expense: String,
+    count: i64,
+}
+
+#[derive(Template)]
+#[template(path = "monthly_report.html")]
+struct MonthlyReportHtml {
+    lang: &'static str,
+    ledger_name: String,
+    month: String,
+    previous_month: String,
+    currency: String,
+    summary: Vec<SummaryRow>,
+    top_tags: Vec<TagRow>,
+    biggest: Vec<TransactionRow>,
+    days: Vec<DayRow>,
+    unsupported_count: i64,
+}
+
+pub fn render_html(report: &MonthlyReport, locale: Locale) -> askama::Result<String> {
+    let scale = parse_currency(&report.currency)
+        .map(|currency| i64::from(exponent(currency)))
+        .unwrap_or(2);
+    let number = |value: &BigDecimal| locale.format_number(value, scale);
+    let change = |current: &BigDecimal, previous: &BigDecimal| {
+        if previous.is_zero() {
+            String::from("-")
+        } else {
+            locale.format_change(&((current - previous) / previous.abs()))
+        }
+    };
+    let summary_row = |label, current: BigDecimal, previous: BigDecimal| SummaryRow {
+        label,
+        current: number(&current),
+        previous: number(&previous),
+        change: change(&current, &previous),
+    };
+    let summary = vec![
+        summary_row(
+            "Income",
+            report.current.income.clone(),
+            report.previous.income.clone(),
+        ),
+        summary_row(
+            "Expense",
+            report.current.expense.clone(),
+            report.previous.expense.clone(),
+        ),
+        summary_row("Net", report.current.net(), report.previous.net()),
+        SummaryRow {
+            label: "Transactions",
+            current: locale.format_number(&BigDecimal::from(report.current.count), 0),
+            previous: locale.format_number(&BigDecimal::from(report.previous.count), 0),
+            change: change(
+                &BigDecimal::from(report.current.count),
+                &BigDecimal::from(report.previous.count),
+            ),
+        },
+    ];
+    let top_tags = report
+        .top_tags
+        .iter()
+        .map(|tag| TagRow {
+            name: tag.name.clone(),
+            expense: number(&tag.expense),
+            share: if report.current.expense.is_zero() {
+                String::from("-")
+            } else {
+                format!(
+                    "{}%",
+                    locale.format_number(
+                        &(&tag.expense * BigDecimal::from(100) / &report.current.expense),
+                        1
+                    )
+                )
+            },
+            count: tag.count,
+        })
+        .collect();
+    let biggest = report
+        .biggest
+        .iter()
+        .map(|item| TransactionRow {
+            date: format_date(item.date),
+            name: item.name.clone(),
+            tags: item.tags.clone(),
+            expense: number(&item.expense),
+        })
+        .collect();
+    let days = report
+        .days
+        .iter()
+        .map(|day| DayRow {
+            date: format_date(day.date),
+            income: number(&day.income),
+            expense: number(&day.expense),
+            count: day.count,
+        })
+        .collect();
+    MonthlyReportHtml {
+        lang: locale.tag(),
+        ledger_name: report.ledger_name.clone(),
+        month: report.period.label(),
+        previous_month: report.previous_period.label(),
+        currency: report.currency.clone(),
+        summary,
+        top_tags,
+        biggest,
+        days,
+        unsupported_count: report.unsupported_count,
+    }
+    .render()
+}
+
+#[derive(Error, Debug)]
+pub enum PdfError {
+    #[error("no PDF command is configured")]
+    NotConfigured,
+    #[error("failed to run the PDF command: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("the PDF command failed: {0}")]
+    Failed(String),
+}
+
+/// Convert HTML to PDF with an external command, like `wkhtmltopdf - -`, which reads the HTML
+/// from stdin and writes the PDF to stdout
+pub async fn render_pdf(command: &[String], html: &str) -> Result<Vec<u8>, PdfError> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(PdfError::NotConfigured);
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let html = html.as_bytes().to_vec();
+    let write = tokio::spawn(async move {
+        stdin.write_all(&html).await?;
+        stdin.shutdown().await
+    });
+    let output = child.wait_with_output().await?;
+    write.await.map_err(std::io::Error::other)??;
+    if !output.status.success() {
+        return Err(PdfError::Failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
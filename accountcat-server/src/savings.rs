@@ -0,0 +1,92 @@
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::Date;
+
+use crate::period::Period;
+
+/// Number of months left to contribute until the deadline, counting the current month. Months run
+/// from `start_day` like summaries, see [Period]. Zero when the deadline has passed
+pub fn months_left(today: Date, deadline: Date, start_day: u8) -> i32 {
+    if deadline < today {
+        return 0;
+    }
+    let (current, last) = (
+        Period::containing(today, start_day).month,
+        Period::containing(deadline, start_day).month,
+    );
+    (last.year() - current.year()) * 12 + i32::from(u8::from(last.month()))
+        - i32::from(u8::from(current.month()))
+        + 1
+}
+
+/// Contribution needed every month, including the current one, to reach the target by the
+/// deadline. Rounded up to the minor unit of a currency with `scale` decimal places. The whole
+/// remaining amount is due once the deadline has passed
+pub fn required_monthly(
+    remaining: &BigDecimal,
+    today: Date,
+    deadline: Date,
+    start_day: u8,
+    scale: i64,
+) -> BigDecimal {
+    if *remaining <= BigDecimal::zero() {
+        return BigDecimal::zero();
+    }
+    let months = months_left(today, deadline, start_day).max(1);
+    let monthly = remaining / BigDecimal::from(months);
+    let mut rounded = monthly.with_scale(scale);
+    if rounded < monthly {
+        rounded += BigDecimal::new(1.into(), scale);
+    }
+    rounded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dateutils::parse_date;
+
+    fn date(s: &str) -> Date {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn test_months_left() {
+        assert_eq!(3, months_left(date("2025-10-19"), date("2025-12-31"), 1));
+        assert_eq!(1, months_left(date("2025-10-19"), date("2025-10-25"), 1));
+        assert_eq!(14, months_left(date("2025-10-19"), date("2026-11-01"), 1));
+        assert_eq!(0, months_left(date("2025-10-19"), date("2025-10-18"), 1));
+        // from payday to payday, the 25th starts the period of the next month
+        assert_eq!(2, months_left(date("2025-10-19"), date("2025-10-25"), 25));
+        assert_eq!(1, months_left(date("2025-10-26"), date("2025-11-24"), 25));
+        assert_eq!(1, months_left(date("2025-10-19"), date("2025-11-04"), 5));
+        assert_eq!(2, months_left(date("2025-10-19"), date("2025-11-05"), 5));
+    }
+
+    #[test]
+    fn test_required_monthly() {
+        let today = date("2025-10-19");
+        assert_eq!(
+            "33.34".parse::<BigDecimal>().unwrap(),
+            required_monthly(&"100".parse().unwrap(), today, date("2025-12-01"), 1, 2)
+        );
+        // currencies without minor units
+        assert_eq!(
+            BigDecimal::from(34),
+            required_monthly(&"100".parse().unwrap(), today, date("2025-12-01"), 1, 0)
+        );
+        assert_eq!(
+            "33.334".parse::<BigDecimal>().unwrap(),
+            required_monthly(&"100".parse().unwrap(), today, date("2025-12-01"), 1, 3)
+        );
+        // overdue goals need the rest right away
+        assert_eq!(
+            "100".parse::<BigDecimal>().unwrap(),
+            required_monthly(&"100".parse().unwrap(), today, date("2025-01-01"), 1, 2)
+        );
+        assert_eq!(
+            BigDecimal::zero(),
+            required_monthly(&"-5".parse().unwrap(), today, date("2025-12-01"), 1, 2)
+        );
+    }
+}
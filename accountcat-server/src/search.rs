@@ -0,0 +1,134 @@
+use crate::idl::accounting::HighlightFragment;
+
+/// Split a search query into terms. Each term has to be found in the item for it to be a match
+pub fn split_terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(String::from).collect()
+}
+
+/// Build a `ilike` pattern that matches the term anywhere in the text
+pub fn contains_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    push_escaped(&mut pattern, term);
+    pattern.push('%');
+    pattern
+}
+
+/// Build a `like` pattern that matches text starting with the term
+pub fn prefix_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 1);
+    push_escaped(&mut pattern, term);
+    pattern.push('%');
+    pattern
+}
+
+fn push_escaped(pattern: &mut String, term: &str) {
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+}
+
+/// Split the text into fragments, marking the parts that match any of the terms case-insensitively.
+/// Works on characters rather than words, so CJK text without spaces is highlighted as well.
+pub fn highlight(text: &str, terms: &[String]) -> Vec<HighlightFragment> {
+    let chars: Vec<char> = text.chars().collect();
+    let folded: Vec<char> = chars.iter().map(|&c| fold(c)).collect();
+    let terms: Vec<Vec<char>> = terms
+        .iter()
+        .map(|t| t.chars().map(fold).collect::<Vec<char>>())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let mut matched = vec![false; chars.len()];
+    for term in terms.iter() {
+        if term.len() > folded.len() {
+            continue;
+        }
+        for start in 0..=(folded.len() - term.len()) {
+            if folded[start..start + term.len()] == term[..] {
+                matched[start..start + term.len()].fill(true);
+            }
+        }
+    }
+    let mut fragments: Vec<HighlightFragment> = Vec::new();
+    for (c, matched) in chars.into_iter().zip(matched) {
+        match fragments.last_mut() {
+            Some(last) if last.matched == matched => last.text.push(c),
+            _ => fragments.push(HighlightFragment {
+                text: String::from(c),
+                matched,
+            }),
+        }
+    }
+    fragments
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(fragments: Vec<HighlightFragment>) -> String {
+        fragments
+            .into_iter()
+            .map(|f| {
+                if f.matched {
+                    format!("[{}]", f.text)
+                } else {
+                    f.text
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_terms() {
+        assert_eq!(vec!["牙醫", "dentist"], split_terms("  牙醫\tdentist "));
+        assert!(split_terms("   ").is_empty());
+    }
+
+    #[test]
+    fn test_contains_pattern_escape() {
+        assert_eq!("%100\\%%", contains_pattern("100%"));
+        assert_eq!("%a\\_b\\\\c%", contains_pattern("a_b\\c"));
+    }
+
+    #[test]
+    fn test_prefix_pattern_escape() {
+        assert_eq!("50\\%%", prefix_pattern("50%"));
+        assert_eq!("牛肉麵%", prefix_pattern("牛肉麵"));
+    }
+
+    #[test]
+    fn test_highlight_case_insensitive() {
+        let terms = split_terms("costco");
+        assert_eq!(
+            "Big [Costco] run",
+            render(highlight("Big Costco run", &terms))
+        );
+    }
+
+    #[test]
+    fn test_highlight_cjk() {
+        let terms = split_terms("牙醫");
+        assert_eq!("春天看[牙醫]", render(highlight("春天看牙醫", &terms)));
+    }
+
+    #[test]
+    fn test_highlight_overlapping_terms() {
+        let terms = split_terms("ab bc");
+        assert_eq!("[abc]d", render(highlight("abcd", &terms)));
+    }
+
+    #[test]
+    fn test_highlight_no_match() {
+        let fragments = highlight("lunch", &split_terms("dinner"));
+        assert_eq!(1, fragments.len());
+        assert!(!fragments[0].matched);
+    }
+}
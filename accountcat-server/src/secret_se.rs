@@ -21,6 +21,19 @@ where
     })
 }
 
+pub fn serialize_optional_secrets<S>(
+    secrets: &Option<Vec<SecretString>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match secrets {
+        Some(secrets) => serializer.collect_seq(secrets.iter().map(|_| "<hidden>")),
+        None => serializer.serialize_str("None"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
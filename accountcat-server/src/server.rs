@@ -16,19 +16,29 @@ use tower_sessions_sqlx_store::PostgresStore;
 use tracing::Level;
 
 use crate::{
+    anomaly,
     config::Config,
     csp::{CspLayer, NonceLayer, build_csp},
+    idcodec::IdCodec,
     idl::{
-        accounting::accounting_server::AccountingServer,
+        accounting::accounting_server::AccountingServer, alert::alert_server::AlertServer,
+        data_import::data_import_server::DataImportServer,
+        forecast::forecast_server::ForecastServer,
+        installment::installment_server::InstallmentServer,
         instance_setting::instance_setting_server::InstanceSettingServer,
-        todolist::todolist_server::TodolistServer, user::user_server::UserServer,
+        investment::investment_server::InvestmentServer, ledger::ledger_server::LedgerServer,
+        net_worth::net_worth_server::NetWorthServer, savings::savings_server::SavingsServer,
+        split::split_server::SplitServer, todolist::todolist_server::TodolistServer,
+        user::user_server::UserServer,
     },
     jwtutils::{self, JwtVerifier},
-    middleware,
+    middleware, report,
     serve_dist::ServeDist,
     service::{
-        accounting::AccountingApi, instance_setting::InstanceSettingApi, todolist::TodolistApi,
-        user::UserApi,
+        accounting::AccountingApi, alert::AlertApi, data_import::DataImportApi,
+        forecast::ForecastApi, installment::InstallmentApi, instance_setting::InstanceSettingApi,
+        investment::InvestmentApi, ledger::LedgerApi, net_worth::NetWorthApi, savings::SavingsApi,
+        split::SplitApi, todolist::TodolistApi, user::UserApi,
     },
 };
 
@@ -37,11 +47,15 @@ pub const SESSION_KEY_CLAIMS: &str = "claims";
 pub struct ServerState {
     pub database: PgPool,
     pub jwt_verify: JwtVerifier,
+    pub ids: IdCodec,
 }
 
 pub async fn init_state(
     Config {
-        login, database, ..
+        login,
+        database,
+        hashids,
+        ..
     }: &Config,
 ) -> ServerState {
     let verifier = JwtVerifier::new(jwtutils::DEFAULT_JWK_URL, login.client_id.clone())
@@ -50,6 +64,7 @@ pub async fn init_state(
     ServerState {
         jwt_verify: verifier,
         database: database.clone().into(),
+        ids: IdCodec::from(hashids),
     }
 }
 
@@ -94,19 +109,34 @@ pub async fn main(arg: &ServerArg, config: &Config) {
         administrators.clone(),
     ));
     let todolist_api = TodolistServer::new(TodolistApi::new(server_state.clone()));
-    let accounting_api = AccountingServer::new(AccountingApi::new(
-        server_state.clone(),
-        config.hashids.salt.clone(),
-    ));
+    let accounting_api = AccountingServer::new(AccountingApi::new(server_state.clone()));
     let instance_setting_api = InstanceSettingServer::new(InstanceSettingApi::new(
         server_state.clone(),
         administrators.clone(),
     ));
+    let ledger_api = LedgerServer::new(LedgerApi::new(server_state.clone()));
+    let split_api = SplitServer::new(SplitApi::new(server_state.clone()));
+    let savings_api = SavingsServer::new(SavingsApi::new(server_state.clone()));
+    let net_worth_api = NetWorthServer::new(NetWorthApi::new(server_state.clone()));
+    let investment_api = InvestmentServer::new(InvestmentApi::new(server_state.clone()));
+    let forecast_api = ForecastServer::new(ForecastApi::new(server_state.clone()));
+    let alert_api = AlertServer::new(AlertApi::new(server_state.clone()));
+    let data_import_api = DataImportServer::new(DataImportApi::new(server_state.clone()));
+    let installment_api = InstallmentServer::new(InstallmentApi::new(server_state.clone()));
     let mut grpc_server_builder = tonic::service::Routes::builder();
     grpc_server_builder.add_service(user_api);
     grpc_server_builder.add_service(todolist_api);
     grpc_server_builder.add_service(accounting_api);
     grpc_server_builder.add_service(instance_setting_api);
+    grpc_server_builder.add_service(ledger_api);
+    grpc_server_builder.add_service(split_api);
+    grpc_server_builder.add_service(savings_api);
+    grpc_server_builder.add_service(net_worth_api);
+    grpc_server_builder.add_service(investment_api);
+    grpc_server_builder.add_service(forecast_api);
+    grpc_server_builder.add_service(alert_api);
+    grpc_server_builder.add_service(data_import_api);
+    grpc_server_builder.add_service(installment_api);
     let grpc_server = grpc_server_builder.routes();
 
     let identity_layer = axum_middleware::from_fn(middleware::identity::enforce_identity);
@@ -135,10 +165,15 @@ pub async fn main(arg: &ServerArg, config: &Config) {
                         .on_response(DefaultOnResponse::new().level(Level::INFO)),
                 )
                 .layer(identity_layer)
+                .layer(session_layer.clone()),
+        )
+        .merge(
+            report::http::router(server_state.clone(), config.server.pdf_command.clone())
                 .layer(session_layer),
         )
         .fallback_service(asset_service);
 
+    tokio::spawn(anomaly::detector::run(server_state.database.clone()));
     let addr = std::net::SocketAddr::from(([0, 0, 0, 0], config.server.port.unwrap_or(3000)));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app)
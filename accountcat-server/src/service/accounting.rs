@@ -1,195 +1,419 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use hash_ids::HashIds;
 use iso_currency::{Currency, IntoEnumIterator};
 use num_traits::ToPrimitive;
-use secrecy::{ExposeSecret, SecretString};
-use sqlx::types::BigDecimal;
+use sqlx::{PgConnection, types::BigDecimal};
+use time::{Date, Duration, Month, OffsetDateTime, Weekday};
 use tonic::{Request, Response, Status};
 use tracing::error;
 
 use crate::{
-    auth::claims_from_request,
+    amount::{self, bad_request, parse_currency, parse_money},
+    dateutils, duplicate, heatmap,
+    idcodec::Namespace,
     idl::accounting::{
-        Amount, AmountType, CurrencyList, DailySpending, DaySpending, DeleteItem, Item, ItemList,
-        Last7DayHistogram, MonthlySpending, NewItem, NewTag, Tag, TagList, TagSearch,
-        UpdateItemRequest, YearlySummary, accounting_server::Accounting,
+        Amount, AmountType, AwaitingRepayment, AwaitingRepaymentList, ConfirmItemRequest,
+        CurrencyList, DailySpending, DaySpending, DeleteItem, DismissDuplicateRequest,
+        DuplicateList, DuplicatePair, DuplicateSearch, Heatmap, HeatmapDay, HeatmapRequest, Item,
+        ItemList, ItemStatus, ItemSuggestion, ItemSuggestionList, ItemSuggestionRequest,
+        Last7DayHistogram, MergeDuplicateRequest, MonthlySpending, NewItem, NewPayee,
+        NewPayeeAlias, NewTag, Offset, Payee, PayeeCurrencyStatistics, PayeeList, PayeeSearch,
+        PayeeStatistics, PayeeStatisticsRequest, PriceHistory, PriceHistoryRequest, PricePoint,
+        QuickAddRequest, QuickAddResult, RemoveOffsetRequest, SearchRequest, SearchResult,
+        SearchResultList, Tag, TagList, TagSearch, UpdateItemRequest, YearlySummary,
+        accounting_server::Accounting, price_history_request::Subject,
     },
+    ledger::{self, LedgerAccess, Role},
+    period::{self, Period},
     protobufutils::{from_proto_timestamp, to_proto_timestamp},
+    quickadd,
+    search::{contains_pattern, highlight, prefix_pattern, split_terms},
     server::ServerState,
 };
 
 pub struct AccountingApi {
     state: Arc<ServerState>,
-    hashids: HashIds,
 }
 
 impl AccountingApi {
-    pub fn new(state: Arc<ServerState>, salt: SecretString) -> Self {
-        let hashids = HashIds::builder().with_salt(salt.expose_secret()).finish();
-        Self { state, hashids }
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
     }
 
-    fn encode_id(&self, id: i32) -> String {
-        self.hashids.encode(&[id as u64])
-    }
-
-    fn decode_id(&self, id: &str) -> Option<i32> {
-        let numbers = self.hashids.decode(id).ok()?;
-        numbers.first().and_then(|&n| i32::try_from(n).ok())
-    }
-}
-
-#[tonic::async_trait]
-impl Accounting for AccountingApi {
-    async fn list(&self, request: Request<()>) -> tonic::Result<Response<ItemList>> {
-        let claims = claims_from_request(&request)?;
-        let items = match sqlx::query!("select accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.created_at, accounting_items.occurred_at
-from accounting_items
-join users on users.id = accounting_items.user_id
-where users.google_sub = $1
-order by accounting_items.created_at desc", claims.sub)
-            .map(|x| Item {
-                id: self.encode_id(x.id),
-                amount: Some(Amount{
-                    amount: format_amount(&x.amount),
-                    currency: x.currency,
-                }),
-                r#type: if x.amount < BigDecimal::from(0) {
-                    AmountType::Expense
-                } else {
-                    AmountType::Income
-                }.into(),
-                name: x.name.unwrap_or_default(),
-                created_at: x.created_at.map(to_proto_timestamp),
-                occurred_at: Some(to_proto_timestamp(x.occurred_at)),
-            })
-            .fetch_all(&self.state.database)
-            .await {
-            Ok(x) => x,
-            Err(err) => {
-                error!(service = "accounting", type = "query_error", message = err.to_string());
-                return Err(Status::internal(String::new()));
-            }
+    /// Insert a new item into the ledger, shared by [Accounting::add] and
+    /// [Accounting::quick_add]
+    async fn insert_item(&self, access: &LedgerAccess, item: NewItem) -> tonic::Result<Item> {
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
         };
-        Ok(Response::new(ItemList { items }))
+        let item = self.insert_item_in(&mut tx, access, item).await?;
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(item)
     }
-    async fn add(&self, request: Request<NewItem>) -> tonic::Result<Response<Item>> {
-        let claims = claims_from_request(&request)?;
+
+    /// [Self::insert_item] within the transaction of `connection`, for importers adding many
+    /// items at once
+    pub(crate) async fn insert_item_in(
+        &self,
+        connection: &mut PgConnection,
+        access: &LedgerAccess,
+        item: NewItem,
+    ) -> tonic::Result<Item> {
         let NewItem {
             name,
             amount,
             tags,
             r#type,
-        } = request.into_inner();
+            payee_id,
+            note,
+            quantity,
+            unit,
+            account_id,
+            occurred_at,
+            reimbursable,
+            status,
+        } = item;
+        let status = status_name(status)?;
+        let payee_id = match payee_id.filter(|x| !x.is_empty()) {
+            Some(id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Payee, &id)
+                    .ok_or_else(|| Status::invalid_argument("bad payee id"))?,
+            ),
+            None => None,
+        };
+        let account_id = match account_id.filter(|x| !x.is_empty()) {
+            Some(id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Account, &id)
+                    .ok_or_else(|| Status::invalid_argument("bad account id"))?,
+            ),
+            None => None,
+        };
+        let quantity = match quantity.filter(|x| !x.is_empty()) {
+            Some(quantity) => Some(parse_quantity(&quantity)?),
+            None => None,
+        };
         let Some(Amount { amount, currency }) = amount else {
             return Err(Status::invalid_argument("missing amount"));
         };
-        let Ok(mut amount) = amount.parse::<BigDecimal>() else {
-            return Err(Status::invalid_argument("amount isn't numeric"));
-        };
-        let Ok(mut tx) = self.state.database.begin().await else {
-            return Err(Status::internal(String::new()));
+        let (mut amount, _) = parse_money("amount.amount", &amount, "amount.currency", &currency)?;
+        let occurred_at = match occurred_at {
+            Some(x) => Some(
+                from_proto_timestamp(x)
+                    .map_err(|_err| Status::invalid_argument("bad occurred_at"))?,
+            ),
+            None => None,
         };
         if r#type == (AmountType::Expense as i32) {
             amount = -amount;
         }
-        let item = match sqlx::query!(
-            "insert into accounting_items (user_id, name, amount, currency)
-select users.id, $1, $2, $3
-from users
-where users.google_sub = $4
+        let item = match sqlx::query_as!(
+            ItemRecord,
+            "insert into accounting_items (ledger_id, user_id, name, amount, currency, payee_id, note, quantity, unit, account_id, occurred_at, reimbursable, status)
+values ($1, $2, $3, $4, $5, (select payees.id from payees where payees.ledger_id = $1 and payees.id = $6), $7, $8, $9,
+        case when $10::integer is null
+             then (select payees.default_account_id from payees where payees.ledger_id = $1 and payees.id = $6)
+             else (select accounts.id from accounts where accounts.ledger_id = $1 and accounts.id = $10)
+        end,
+        coalesce($11, now()), $12, $13)
 returning accounting_items.id,
           accounting_items.name,
           accounting_items.amount,
           accounting_items.currency,
           accounting_items.created_at,
-          accounting_items.occurred_at",
+          accounting_items.occurred_at,
+          accounting_items.payee_id,
+          accounting_items.note,
+          accounting_items.quantity,
+          accounting_items.unit,
+          accounting_items.account_id,
+          accounting_items.reimbursable,
+          accounting_items.status",
+            access.ledger_id,
+            access.user_id,
             name,
             amount,
             currency,
-            claims.sub
+            payee_id,
+            note.filter(|x| !x.is_empty()),
+            quantity,
+            unit.filter(|x| !x.is_empty()),
+            account_id,
+            occurred_at,
+            reimbursable,
+            status,
         )
-        .fetch_one(&mut *tx)
+        .fetch_one(&mut *connection)
         .await
         {
             Ok(record) => Ok(record),
             Err(_err) => Err(Status::internal(String::new())),
         }?;
-        let tag_id: Vec<i32> = tags.iter().filter_map(|x| x.parse().ok()).collect();
-        sqlx::query!(
-            "insert into accounting_item_tags (tag_id, accounting_item_id)
+        if payee_id.is_some() && item.payee_id.is_none() {
+            return Err(Status::invalid_argument("payee not found"));
+        }
+        if account_id.is_some() && item.account_id.is_none() {
+            return Err(Status::invalid_argument("account not found"));
+        }
+        if let (true, Some(payee_id)) = (tags.is_empty(), item.payee_id) {
+            sqlx::query!(
+                "insert into accounting_item_tags (tag_id, accounting_item_id)
+select payee_default_tags.tag_id, $1
+from payee_default_tags
+where payee_default_tags.payee_id = $2",
+                item.id,
+                payee_id,
+            )
+            .execute(&mut *connection)
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        } else {
+            let tag_id: Vec<i32> = tags
+                .iter()
+                .filter_map(|x| self.state.ids.decode(Namespace::Tag, x))
+                .collect();
+            sqlx::query!(
+                "insert into accounting_item_tags (tag_id, accounting_item_id)
 select tags.id, $1
 from tags
-join users on users.id = tags.user_id
-where users.google_sub = $2 and tags.id = any($3)",
-            item.id,
-            claims.sub,
-            &tag_id[..],
-        )
-        .execute(&mut *tx)
-        .await
-        .map_err(|_err| Status::internal(String::new()))?;
-        tx.commit()
+where tags.ledger_id = $2 and tags.id = any($3)",
+                item.id,
+                access.ledger_id,
+                &tag_id[..],
+            )
+            .execute(&mut *connection)
             .await
             .map_err(|_err| Status::internal(String::new()))?;
-        Ok(Response::new(Item {
-            id: self.encode_id(item.id),
-            name: item.name.unwrap_or_default(),
+        }
+        let duplicate_of = match duplicate::duplicates_of(&mut *connection, item.id).await {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "find duplicates of new item", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Item {
+            duplicate_of: duplicate_of
+                .into_iter()
+                .map(|id| self.state.ids.encode(Namespace::Item, id))
+                .collect(),
+            ..self.to_item(item)
+        })
+    }
+
+    fn to_item(&self, record: ItemRecord) -> Item {
+        Item {
+            id: self.state.ids.encode(Namespace::Item, record.id),
             amount: Some(Amount {
-                currency: item.currency,
-                amount: format_amount(&item.amount),
+                amount: format_amount(&record.amount),
+                currency: record.currency,
             }),
-            r#type: if item.amount < BigDecimal::from(0) {
+            r#type: if record.amount < BigDecimal::from(0) {
                 AmountType::Expense
             } else {
                 AmountType::Income
             }
             .into(),
-            created_at: item.created_at.map(to_proto_timestamp),
-            occurred_at: Some(to_proto_timestamp(item.occurred_at)),
-        }))
+            name: record.name.unwrap_or_default(),
+            created_at: record.created_at.map(to_proto_timestamp),
+            occurred_at: Some(to_proto_timestamp(record.occurred_at)),
+            payee_id: record
+                .payee_id
+                .map(|id| self.state.ids.encode(Namespace::Payee, id)),
+            note: record.note,
+            unit_price: record
+                .quantity
+                .as_ref()
+                .map(|quantity| format_amount(&unit_price(&record.amount, quantity))),
+            quantity: record.quantity.as_ref().map(format_amount),
+            unit: record.unit,
+            account_id: record
+                .account_id
+                .map(|id| self.state.ids.encode(Namespace::Account, id)),
+            duplicate_of: Vec::new(),
+            reimbursable: record.reimbursable,
+            status: status_from_name(&record.status).into(),
+        }
+    }
+
+    /// Items of the ledger by their ids
+    pub(crate) async fn items_by_id(
+        &self,
+        ledger_id: i32,
+        ids: &[i32],
+    ) -> tonic::Result<HashMap<i32, Item>> {
+        match sqlx::query_as!(
+            ItemRecord,
+            "select accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.created_at, accounting_items.occurred_at, accounting_items.payee_id, accounting_items.note, accounting_items.quantity, accounting_items.unit, accounting_items.account_id, accounting_items.reimbursable, accounting_items.status
+from accounting_items
+where accounting_items.ledger_id = $1 and accounting_items.id = any($2)",
+            ledger_id,
+            ids,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(records) => Ok(records
+                .into_iter()
+                .map(|record| (record.id, self.to_item(record)))
+                .collect()),
+            Err(err) => {
+                error!(action = "get items by id", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn find_payee(&self, ledger_id: i32, id: i32) -> tonic::Result<Payee> {
+        match sqlx::query!(
+            r#"select payees.id,
+       payees.name,
+       array(select payee_aliases.alias from payee_aliases where payee_aliases.payee_id = payees.id order by payee_aliases.alias) "aliases!",
+       array(select payee_default_tags.tag_id from payee_default_tags where payee_default_tags.payee_id = payees.id order by payee_default_tags.tag_id) "default_tags!",
+       payees.default_account_id
+from payees
+where payees.ledger_id = $1 and payees.id = $2"#,
+            ledger_id,
+            id
+        )
+        .map(|r| Payee {
+            id: self.state.ids.encode(Namespace::Payee, r.id),
+            name: r.name,
+            aliases: r.aliases,
+            default_tags: r
+                .default_tags
+                .iter()
+                .map(|&id| self.state.ids.encode(Namespace::Tag, id))
+                .collect(),
+            default_account_id: r
+                .default_account_id
+                .map(|id| self.state.ids.encode(Namespace::Account, id)),
+        })
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(payee) => Ok(payee),
+            Err(sqlx::Error::RowNotFound) => Err(Status::not_found("payee not found")),
+            Err(err) => {
+                error!(action = "find payee", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+}
+
+struct ItemRecord {
+    id: i32,
+    name: Option<String>,
+    amount: BigDecimal,
+    currency: String,
+    created_at: Option<OffsetDateTime>,
+    occurred_at: OffsetDateTime,
+    payee_id: Option<i32>,
+    note: Option<String>,
+    quantity: Option<BigDecimal>,
+    unit: Option<String>,
+    account_id: Option<i32>,
+    reimbursable: bool,
+    status: String,
+}
+
+#[tonic::async_trait]
+impl Accounting for AccountingApi {
+    async fn list(&self, request: Request<()>) -> tonic::Result<Response<ItemList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let items = match sqlx::query_as!(ItemRecord, "select accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.created_at, accounting_items.occurred_at, accounting_items.payee_id, accounting_items.note, accounting_items.quantity, accounting_items.unit, accounting_items.account_id, accounting_items.reimbursable, accounting_items.status
+from accounting_items
+where accounting_items.ledger_id = $1
+order by accounting_items.created_at desc", ledger.ledger_id)
+            .map(|x| self.to_item(x))
+            .fetch_all(&self.state.database)
+            .await {
+            Ok(x) => x,
+            Err(err) => {
+                error!(service = "accounting", type = "query_error", message = err.to_string());
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(ItemList { items }))
+    }
+    async fn add(&self, request: Request<NewItem>) -> tonic::Result<Response<Item>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        Ok(Response::new(
+            self.insert_item(&access, request.into_inner()).await?,
+        ))
     }
     async fn complete_tag(&self, request: Request<TagSearch>) -> tonic::Result<Response<TagList>> {
-        let claims = claims_from_request(&request)?;
-        let TagSearch { keyword } = request.into_inner();
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let TagSearch {
+            keyword,
+            limit,
+            item_name,
+        } = request.into_inner();
+        let limit = match limit {
+            0 => DEFAULT_TAG_LIMIT,
+            limit => limit.min(MAX_TAG_LIMIT),
+        };
+        let item_name = item_name
+            .map(|x| x.trim().to_lowercase())
+            .filter(|x| !x.is_empty());
+        // every use scores by its age like the names of SuggestItems, dated by when the tag was put on
+        // the item
         match sqlx::query!(
             r#"select tags.id, tags.name
 from tags
-join users on users.id = tags.user_id
-where users.google_sub = $1 and tags.name like $2 escape '\'"#,
-            claims.sub,
-            format!("%{}%", keyword.replace("%", "\\%"))
+cross join lateral (
+    select count(*) filter (where lower(accounting_items.name) = $5) as with_item_name,
+           coalesce(sum(power(0.5, greatest(extract(epoch from now() - accounting_item_tags.created_at), 0) / (86400 * 30))), 0) as score
+    from accounting_item_tags
+    join accounting_items on accounting_items.id = accounting_item_tags.accounting_item_id
+    where accounting_item_tags.tag_id = tags.id
+) uses
+where tags.ledger_id = $1 and tags.name ilike $2 escape '\'
+order by uses.with_item_name desc, tags.name ilike $3 escape '\' desc, uses.score desc, tags.name
+limit $4"#,
+            ledger.ledger_id,
+            contains_pattern(&keyword),
+            prefix_pattern(&keyword),
+            i64::from(limit),
+            item_name,
         )
         .map(|r| Tag {
-            id: r.id.to_string(),
+            id: self.state.ids.encode(Namespace::Tag, r.id),
             name: r.name,
         })
         .fetch_all(&self.state.database)
         .await
         {
             Ok(tags) => Ok(Response::new(TagList { tags })),
-            Err(_err) => Err(Status::internal(String::new())),
+            Err(err) => {
+                error!(action = "complete tag", error = ?err);
+                Err(Status::internal(String::new()))
+            }
         }
     }
 
     async fn create_tag(&self, request: Request<NewTag>) -> tonic::Result<Response<Tag>> {
-        let claims = claims_from_request(&request)?;
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
         let NewTag { name } = request.into_inner();
         match sqlx::query!(
-            "insert into tags (user_id, name)
-select users.id, $1
-from users
-where users.google_sub = $2
+            "insert into tags (ledger_id, user_id, name)
+values ($1, $2, $3)
 returning tags.id, tags.name",
+            ledger.ledger_id,
+            ledger.user_id,
             name,
-            claims.sub
         )
         .fetch_one(&self.state.database)
         .await
         {
             Ok(record) => Ok(Response::new(Tag {
-                id: record.id.to_string(),
+                id: self.state.ids.encode(Namespace::Tag, record.id),
                 name: record.name,
             })),
             Err(_err) => Err(Status::internal(String::new())),
@@ -198,23 +422,22 @@ returning tags.id, tags.name",
 
     async fn list_currency(&self, _request: Request<()>) -> tonic::Result<Response<CurrencyList>> {
         let code = Currency::iter()
-            .filter(|x| x.flags().is_empty())
+            .filter(|x| parse_currency(x.code()).is_ok())
             .map(|x| String::from(x.code()))
             .collect();
         Ok(Response::new(CurrencyList { code }))
     }
 
     async fn delete(&self, request: Request<DeleteItem>) -> tonic::Result<Response<()>> {
-        let claims = claims_from_request(&request)?;
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
         let DeleteItem { id } = request.into_inner();
-        let Some(id) = self.decode_id(&id) else {
+        let Some(id) = self.state.ids.decode(Namespace::Item, &id) else {
             return Ok(Response::new(()));
         };
         if let Err(err) = sqlx::query!(
             r#"delete from accounting_items
-using users
-where users.google_sub = $1 and accounting_items.id = $2 and accounting_items.user_id = users.id"#,
-            claims.sub,
+where accounting_items.ledger_id = $1 and accounting_items.id = $2"#,
+            ledger.ledger_id,
             id,
         )
         .execute(&self.state.database)
@@ -230,62 +453,138 @@ where users.google_sub = $1 and accounting_items.id = $2 and accounting_items.us
         &self,
         request: Request<UpdateItemRequest>,
     ) -> tonic::Result<Response<()>> {
-        let claims = claims_from_request(&request)?;
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
         let UpdateItemRequest {
             id,
             name,
             amount,
             occurred_at,
+            payee_id,
+            note,
+            quantity,
+            unit,
+            account_id,
+            reimbursable,
+            status,
         } = request.into_inner();
-        let Some(id) = self.decode_id(&id) else {
+        let Some(id) = self.state.ids.decode(Namespace::Item, &id) else {
             return Err(Status::invalid_argument("bad id"));
         };
+        let status = match status {
+            Some(status) => Some(status_name(status)?),
+            None => None,
+        };
+        let update_payee = payee_id.is_some();
+        let payee_id = match payee_id.filter(|x| !x.is_empty()) {
+            Some(payee_id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Payee, &payee_id)
+                    .ok_or_else(|| Status::invalid_argument("bad payee id"))?,
+            ),
+            None => None,
+        };
+        let update_account = account_id.is_some();
+        let account_id = match account_id.filter(|x| !x.is_empty()) {
+            Some(account_id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Account, &account_id)
+                    .ok_or_else(|| Status::invalid_argument("bad account id"))?,
+            ),
+            None => None,
+        };
+        let quantity = match quantity {
+            Some(quantity) if quantity.is_empty() => Some(None),
+            Some(quantity) => Some(Some(parse_quantity(&quantity)?)),
+            None => None,
+        };
         let (amount, currency) = match amount {
-            Some(Amount { currency, amount }) => (Some(amount), Some(currency)),
+            Some(Amount { currency, amount }) => {
+                let (amount, _) =
+                    parse_money("amount.amount", &amount, "amount.currency", &currency)?;
+                (Some(amount), Some(currency))
+            }
             None => (None, None),
         };
-        let amount = amount.and_then(|x| x.parse::<BigDecimal>().ok());
-        if let Err(err) = sqlx::query!(
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let updated = match sqlx::query!(
             "update accounting_items
 set name = coalesce($1, name),
     occurred_at = coalesce($2, occurred_at),
     amount = coalesce((case when amount = 0 then 1 else sign(amount) end)*$3, amount),
-    currency = coalesce($4, currency)
-from users
-where accounting_items.id = $5 and accounting_items.user_id = users.id and users.google_sub = $6",
+    currency = coalesce($4, currency),
+    payee_id = case when $7 then (select payees.id from payees where payees.id = $8 and payees.ledger_id = $6) else payee_id end,
+    note = case when $9::text is null then note else nullif($9, '') end,
+    quantity = case when $10 then $11 else quantity end,
+    unit = case when $12::text is null then unit else nullif($12, '') end,
+    account_id = case when $13 then (select accounts.id from accounts where accounts.id = $14 and accounts.ledger_id = $6) else account_id end,
+    reimbursable = coalesce($15, reimbursable),
+    status = coalesce($16, status)
+where accounting_items.id = $5 and accounting_items.ledger_id = $6
+returning accounting_items.payee_id, accounting_items.account_id",
             name,
             occurred_at.and_then(|x| from_proto_timestamp(x).ok()),
             amount,
             currency,
             id,
-            claims.sub
+            ledger.ledger_id,
+            update_payee,
+            payee_id,
+            note,
+            quantity.is_some(),
+            quantity.flatten(),
+            unit,
+            update_account,
+            account_id,
+            reimbursable,
+            status,
         )
-        .execute(&self.state.database)
+        .fetch_optional(&mut *tx)
         .await
         {
-            error!(action = "update accounting item", error = ?err);
-            return Err(Status::internal(String::new()));
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "update accounting item", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        if let Some(updated) = updated {
+            if payee_id.is_some() && updated.payee_id.is_none() {
+                return Err(Status::invalid_argument("payee not found"));
+            }
+            if account_id.is_some() && updated.account_id.is_none() {
+                return Err(Status::invalid_argument("account not found"));
+            }
         }
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
         Ok(Response::new(()))
     }
     async fn get_daily_spending(
         &self,
         request: Request<()>,
     ) -> tonic::Result<Response<DailySpending>> {
-        let claims = claims_from_request(&request)?;
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
         let state = match sqlx::query!(
-            "select count(*) filter (where accounting_items.currency = 'TWD') count,
-                    count(*) filter (where accounting_items.currency != 'TWD') unsupported_count,
-    -sum(accounting_items.amount) filter (where accounting_items.amount < 0 and accounting_items.currency = 'TWD') expense,
-    sum(accounting_items.amount) filter (where accounting_items.amount >= 0 and accounting_items.currency = 'TWD') income,
-    date_trunc('day', now(), 'Asia/Taipei') at time zone 'Asia/Taipei' today
-from accounting_items
-join users on users.id = accounting_items.user_id
-where users.google_sub = $1
-      and accounting_items.occurred_at >= date_trunc('day', now(), 'Asia/Taipei')
-      and accounting_items.occurred_at < date_trunc('day', now(), 'Asia/Taipei') + interval '1 day' 
+            "select count(accounting_items.id) filter (where accounting_items.currency = 'TWD') count,
+       count(accounting_items.id) filter (where accounting_items.currency != 'TWD') unsupported_count,
+       -sum(net_amount(accounting_items)) filter (where accounting_items.amount < 0 and accounting_items.currency = 'TWD') expense,
+       sum(net_amount(accounting_items)) filter (where accounting_items.amount >= 0 and accounting_items.currency = 'TWD') income,
+       date_trunc('day', now(), users.time_zone) at time zone users.time_zone today
+from users
+left join accounting_items on accounting_items.ledger_id = $1
+      and accounting_items.occurred_at >= date_trunc('day', now(), users.time_zone)
+      and accounting_items.occurred_at < date_trunc('day', now(), users.time_zone) + interval '1 day'
+      and counted(accounting_items, $2)
+where users.id = $2
+group by users.time_zone
 ",
-            claims.sub
+            ledger.ledger_id,
+            ledger.user_id
         )
         .fetch_one(&self.state.database)
         .await
@@ -320,22 +619,25 @@ where users.google_sub = $1
         &self,
         request: Request<()>,
     ) -> tonic::Result<Response<Last7DayHistogram>> {
-        let claims = claims_from_request(&request)?;
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
         let data = match sqlx::query!(
             "select
-to_char(histogram.date at time zone 'Asia/Taipei', 'YYYY/MM/DD') date,
-sum(accounting_items.amount) filter (where accounting_items.amount >= 0) income,
--sum(accounting_items.amount) filter (where accounting_items.amount < 0) expense
-from generate_series(date_trunc('day', now(), 'Asia/Taipei') - interval '6 days', date_trunc('day', now(), 'Asia/Taipei'), interval '1 day') as histogram(date)
-join users on users.google_sub = $1
-left join accounting_items on accounting_items.user_id = users.id
-and accounting_items.occurred_at >= histogram.date
-and accounting_items.occurred_at < histogram.date + interval '1 day'
+to_char(histogram.day, 'YYYY/MM/DD') date,
+sum(net_amount(accounting_items)) filter (where accounting_items.amount >= 0) income,
+-sum(net_amount(accounting_items)) filter (where accounting_items.amount < 0) expense
+from users
+cross join generate_series(((now() at time zone users.time_zone)::date - 6)::timestamp, (now() at time zone users.time_zone)::date::timestamp, interval '1 day') as histogram(day)
+left join accounting_items on accounting_items.ledger_id = $1
+and accounting_items.occurred_at >= histogram.day at time zone users.time_zone
+and accounting_items.occurred_at < (histogram.day + interval '1 day') at time zone users.time_zone
 and accounting_items.currency = 'TWD'
-group by histogram.date
-order by histogram.date
+and counted(accounting_items, $2)
+where users.id = $2
+group by histogram.day
+order by histogram.day
 ",
-            claims.sub
+            ledger.ledger_id,
+            ledger.user_id
         )
         .map(|r| DaySpending {
             date: r.date.unwrap_or_default(),
@@ -358,36 +660,1353 @@ order by histogram.date
         &self,
         request: Request<()>,
     ) -> tonic::Result<Response<YearlySummary>> {
-        let claims = claims_from_request(&request)?;
-        let months = match sqlx::query!("select
-to_char(histogram.date at time zone 'Asia/Taipei', 'MM') date,
-sum(accounting_items.amount) filter (where accounting_items.amount >= 0) income,
-sum(accounting_items.amount) filter (where accounting_items.amount < 0) expense
-from (select date_trunc('year', now(), 'Asia/Taipei') + interval '1' month * i date from generate_series(0,11) as s(i)) as histogram
-join users on users.google_sub = $1
-left join accounting_items on accounting_items.user_id = users.id
-and accounting_items.occurred_at >= histogram.date
-and accounting_items.occurred_at < histogram.date + interval '1 month'
-and accounting_items.currency = 'TWD'
-group by histogram.date
-order by histogram.date", claims.sub)
-            .map(|r| MonthlySpending {
-                date: r.date.unwrap_or_default(),
-                income: r.income.and_then(|d| d.to_f64()).unwrap_or_default(),
-                expense: r.expense.and_then(|d| d.to_f64()).unwrap_or_default()
-            })
-            .fetch_all(&self.state.database)
-            .await {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let user = match sqlx::query!(
+            r#"select users.month_start_day,
+       users.time_zone,
+       (now() at time zone users.time_zone)::date as "today!"
+from users
+where users.id = $1"#,
+            ledger.user_id
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get yearly summary preference", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let start_day = user.month_start_day as u8;
+        let current = Period::containing(user.today, start_day);
+        let periods = period::year(current.month.year(), start_day);
+        let starts: Vec<Date> = periods.iter().map(|x| x.start).collect();
+        let ends: Vec<Date> = periods.iter().map(|x| x.end).collect();
+        let rows = match sqlx::query!(
+            r#"select periods.start_date as "start_date!",
+       sum(net_amount(accounting_items)) filter (where accounting_items.amount >= 0) income,
+       sum(net_amount(accounting_items)) filter (where accounting_items.amount < 0) expense
+from unnest($3::date[], $4::date[]) as periods(start_date, end_date)
+left join accounting_items on accounting_items.ledger_id = $1
+      and accounting_items.occurred_at >= periods.start_date::timestamp at time zone $5
+      and accounting_items.occurred_at < periods.end_date::timestamp at time zone $5
+      and accounting_items.currency = 'TWD'
+      and counted(accounting_items, $2)
+group by periods.start_date
+order by periods.start_date"#,
+            ledger.ledger_id,
+            ledger.user_id,
+            &starts,
+            &ends,
+            user.time_zone,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
             Ok(x) => x,
             Err(err) => {
                 error!(action = "get yearly summary", error = ?err);
                 return Err(Status::internal(String::new()));
             }
         };
+        let months = periods
+            .iter()
+            .zip(rows)
+            .map(|(period, r)| MonthlySpending {
+                date: format!("{:02}", u8::from(period.month.month())),
+                income: r.income.and_then(|d| d.to_f64()).unwrap_or_default(),
+                expense: r.expense.and_then(|d| d.to_f64()).unwrap_or_default(),
+                start_date: dateutils::format_date(period.start),
+                end_date: dateutils::format_date(period.last_day()),
+                label: period.label(),
+            })
+            .collect();
         Ok(Response::new(YearlySummary { months }))
     }
-}
 
-fn format_amount(a: &BigDecimal) -> String {
-    a.normalized().to_plain_string()
+    async fn get_heatmap(
+        &self,
+        request: Request<HeatmapRequest>,
+    ) -> tonic::Result<Response<Heatmap>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let HeatmapRequest {
+            year,
+            start_date,
+            end_date,
+        } = request.into_inner();
+        let user = match sqlx::query!(
+            r#"select users.base_currency,
+       users.time_zone,
+       users.week_start,
+       (now() at time zone users.time_zone)::date as "today!"
+from users
+where users.id = $1"#,
+            ledger.user_id
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get heatmap preference", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let (start, end) = match (year, start_date, end_date) {
+            (Some(year), None, None) => {
+                let (Ok(start), Ok(end)) = (
+                    Date::from_calendar_date(year, Month::January, 1),
+                    Date::from_calendar_date(year, Month::December, 31),
+                ) else {
+                    return Err(Status::invalid_argument("bad year"));
+                };
+                (start, end)
+            }
+            (None, Some(start), Some(end)) => {
+                let Some(start) = dateutils::parse_date(&start) else {
+                    return Err(Status::invalid_argument("bad start_date"));
+                };
+                let Some(end) = dateutils::parse_date(&end) else {
+                    return Err(Status::invalid_argument("bad end_date"));
+                };
+                (start, end)
+            }
+            (None, None, None) => {
+                let week_start = Weekday::Monday.nth_next(user.week_start as u8);
+                (
+                    period::week_start(user.today - Duration::weeks(51), week_start),
+                    user.today,
+                )
+            }
+            _ => {
+                return Err(Status::invalid_argument(
+                    "either year or both start_date and end_date are required",
+                ));
+            }
+        };
+        if end < start || (end - start).whole_days() >= heatmap::MAX_DAYS {
+            return Err(Status::invalid_argument(format!(
+                "the range has to be from 1 to {} days",
+                heatmap::MAX_DAYS
+            )));
+        }
+        let days = match sqlx::query!(
+            r#"select days.day::date as "date!",
+       coalesce(sum(net_amount(accounting_items)) filter (where accounting_items.amount > 0), 0) as "income!",
+       coalesce(-sum(net_amount(accounting_items)) filter (where accounting_items.amount < 0), 0) as "expense!",
+       count(accounting_items.id) as "count!"
+from generate_series($2::date::timestamp, $3::date::timestamp, interval '1 day') days(day)
+left join accounting_items
+       on accounting_items.ledger_id = $1
+      and accounting_items.currency = $4
+      and accounting_items.occurred_at >= days.day at time zone $5
+      and accounting_items.occurred_at < (days.day + interval '1 day') at time zone $5
+      and counted(accounting_items, $6)
+group by days.day
+order by days.day"#,
+            ledger.ledger_id,
+            start,
+            end,
+            user.base_currency,
+            user.time_zone,
+            ledger.user_id,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get heatmap", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let expenses: Vec<BigDecimal> = days.iter().map(|x| x.expense.clone()).collect();
+        let thresholds = heatmap::thresholds(&expenses);
+        Ok(Response::new(Heatmap {
+            currency: user.base_currency,
+            time_zone: user.time_zone,
+            days: days
+                .into_iter()
+                .map(|x| HeatmapDay {
+                    date: dateutils::format_date(x.date),
+                    income: format_amount(&x.income),
+                    level: heatmap::level(&x.expense, &thresholds),
+                    expense: format_amount(&x.expense),
+                    count: x.count,
+                })
+                .collect(),
+            thresholds: thresholds.iter().map(format_amount).collect(),
+        }))
+    }
+
+    async fn search(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> tonic::Result<Response<SearchResultList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let SearchRequest { query, limit } = request.into_inner();
+        let terms = split_terms(&query);
+        if terms.is_empty() {
+            return Ok(Response::new(SearchResultList::default()));
+        }
+        let query = terms.join(" ");
+        let patterns: Vec<String> = terms.iter().map(|t| contains_pattern(t)).collect();
+        let longest = terms
+            .iter()
+            .max_by_key(|t| t.chars().count())
+            .map(String::as_str)
+            .unwrap_or_default();
+        let limit = match limit {
+            0 => DEFAULT_SEARCH_LIMIT,
+            limit => limit.min(MAX_SEARCH_LIMIT),
+        };
+        // Candidates are collected with the trigram and full-text indexes on item and tag names and
+        // notes, by the longest term as every term has to match anyway. The indexes can't serve
+        // `ilike any` over an array. Every term then has to be found in the name, the note or one
+        // of the tags. A term that isn't a substring still matches a similar word in the name,
+        // which tolerates typos. Substring matching is what makes CJK text work, as neither the
+        // `simple` text search configuration nor trigrams split CJK text into words.
+        let results = match sqlx::query!(
+            r#"with candidates as (
+    select accounting_items.id
+    from accounting_items
+    where accounting_items.ledger_id = $1
+          and (accounting_items.name ilike $7
+               or accounting_items.name %> $8
+               or accounting_items.note ilike $7
+               or to_tsvector('simple', coalesce(accounting_items.name, '')) @@ plainto_tsquery('simple', $2))
+    union
+    select accounting_item_tags.accounting_item_id
+    from tags
+    join accounting_item_tags on accounting_item_tags.tag_id = tags.id
+    where tags.ledger_id = $1 and (tags.name ilike $7 or tags.name % $2)
+),
+documents as (
+    select candidates.id, array_remove(array_agg(tags.name order by tags.name), null) tag_names
+    from candidates
+    left join accounting_item_tags on accounting_item_tags.accounting_item_id = candidates.id
+    left join tags on tags.id = accounting_item_tags.tag_id
+    group by candidates.id
+)
+select accounting_items.id,
+       accounting_items.name,
+       accounting_items.amount,
+       accounting_items.currency,
+       accounting_items.created_at,
+       accounting_items.occurred_at,
+       accounting_items.payee_id,
+       accounting_items.note,
+       accounting_items.quantity,
+       accounting_items.unit,
+       accounting_items.account_id,
+       accounting_items.reimbursable,
+       accounting_items.status,
+       documents.tag_names as "tag_names!",
+       (case when coalesce(accounting_items.name, '') ilike $4 then 1 else 0 end
+        + similarity(coalesce(accounting_items.name, ''), $2)
+        + ts_rank(to_tsvector('simple', coalesce(accounting_items.name, '')), plainto_tsquery('simple', $2))
+        + coalesce((select max(similarity(tag_name, $2)) from unnest(documents.tag_names) tag_name), 0))::float8 as "rank!"
+from documents
+join accounting_items on accounting_items.id = documents.id
+where (select bool_and(coalesce(accounting_items.name, '') ilike term.pattern
+                       or coalesce(accounting_items.name, '') %> term.word
+                       or coalesce(accounting_items.note, '') ilike term.pattern
+                       or exists(select 1 from unnest(documents.tag_names) tag_name where tag_name ilike term.pattern))
+       from unnest($3::text[], $6::text[]) term(pattern, word))
+order by "rank!" desc, accounting_items.occurred_at desc
+limit $5"#,
+            ledger.ledger_id,
+            query,
+            &patterns[..],
+            contains_pattern(&query),
+            i64::from(limit),
+            &terms[..],
+            contains_pattern(longest),
+            longest,
+        )
+        .map(|r| SearchResult {
+            highlighted_name: highlight(r.name.as_deref().unwrap_or_default(), &terms),
+            item: Some(self.to_item(ItemRecord {
+                id: r.id,
+                name: r.name,
+                amount: r.amount,
+                currency: r.currency,
+                created_at: r.created_at,
+                occurred_at: r.occurred_at,
+                payee_id: r.payee_id,
+                note: r.note,
+                quantity: r.quantity,
+                unit: r.unit,
+                account_id: r.account_id,
+                reimbursable: r.reimbursable,
+                status: r.status,
+            })),
+            tags: r.tag_names,
+            rank: r.rank,
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "search accounting items", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(SearchResultList { results }))
+    }
+
+    async fn create_payee(&self, request: Request<NewPayee>) -> tonic::Result<Response<Payee>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewPayee {
+            name,
+            aliases,
+            default_tags,
+            default_account_id,
+        } = request.into_inner();
+        let default_account_id = match default_account_id.filter(|x| !x.is_empty()) {
+            Some(id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Account, &id)
+                    .ok_or_else(|| Status::invalid_argument("bad default_account_id"))?,
+            ),
+            None => None,
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let payee = match sqlx::query!(
+            "insert into payees (ledger_id, user_id, name, default_account_id)
+values ($1, $2, $3, (select accounts.id from accounts where accounts.ledger_id = $1 and accounts.id = $4))
+on conflict (ledger_id, name) do nothing
+returning payees.id, payees.default_account_id",
+            ledger.ledger_id,
+            ledger.user_id,
+            name,
+            default_account_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        {
+            Ok(Some(record)) => record,
+            Ok(None) => return Err(Status::already_exists("payee already exists")),
+            Err(err) => {
+                error!(action = "create payee", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        if default_account_id.is_some() && payee.default_account_id.is_none() {
+            return Err(Status::invalid_argument("account not found"));
+        }
+        sqlx::query!(
+            "insert into payee_aliases (payee_id, alias)
+select $1, alias from unnest($2::text[]) alias
+on conflict (payee_id, alias) do nothing",
+            payee.id,
+            &aliases[..],
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_err| Status::internal(String::new()))?;
+        let tag_id: Vec<i32> = default_tags
+            .iter()
+            .filter_map(|x| self.state.ids.decode(Namespace::Tag, x))
+            .collect();
+        sqlx::query!(
+            "insert into payee_default_tags (payee_id, tag_id)
+select $1, tags.id
+from tags
+where tags.ledger_id = $2 and tags.id = any($3)",
+            payee.id,
+            ledger.ledger_id,
+            &tag_id[..],
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_err| Status::internal(String::new()))?;
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(
+            self.find_payee(ledger.ledger_id, payee.id).await?,
+        ))
+    }
+
+    async fn complete_payee(
+        &self,
+        request: Request<PayeeSearch>,
+    ) -> tonic::Result<Response<PayeeList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let PayeeSearch { keyword } = request.into_inner();
+        match sqlx::query!(
+            r#"select payees.id,
+       payees.name,
+       array(select payee_aliases.alias from payee_aliases where payee_aliases.payee_id = payees.id order by payee_aliases.alias) "aliases!",
+       array(select payee_default_tags.tag_id from payee_default_tags where payee_default_tags.payee_id = payees.id order by payee_default_tags.tag_id) "default_tags!",
+       payees.default_account_id
+from payees
+where payees.ledger_id = $1
+      and (payees.name ilike $2
+           or exists(select 1 from payee_aliases where payee_aliases.payee_id = payees.id and payee_aliases.alias ilike $2))
+order by payees.name"#,
+            ledger.ledger_id,
+            contains_pattern(&keyword),
+        )
+        .map(|r| Payee {
+            id: self.state.ids.encode(Namespace::Payee, r.id),
+            name: r.name,
+            aliases: r.aliases,
+            default_tags: r
+                .default_tags
+                .iter()
+                .map(|&id| self.state.ids.encode(Namespace::Tag, id))
+                .collect(),
+            default_account_id: r
+                .default_account_id
+                .map(|id| self.state.ids.encode(Namespace::Account, id)),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(payees) => Ok(Response::new(PayeeList { payees })),
+            Err(err) => {
+                error!(action = "complete payee", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn add_payee_alias(
+        &self,
+        request: Request<NewPayeeAlias>,
+    ) -> tonic::Result<Response<Payee>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewPayeeAlias { payee_id, alias } = request.into_inner();
+        let Some(payee_id) = self.state.ids.decode(Namespace::Payee, &payee_id) else {
+            return Err(Status::invalid_argument("bad payee id"));
+        };
+        if let Err(err) = sqlx::query!(
+            "insert into payee_aliases (payee_id, alias)
+select payees.id, $3
+from payees
+where payees.ledger_id = $1 and payees.id = $2
+on conflict (payee_id, alias) do nothing",
+            ledger.ledger_id,
+            payee_id,
+            alias,
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            error!(action = "add payee alias", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        Ok(Response::new(
+            self.find_payee(ledger.ledger_id, payee_id).await?,
+        ))
+    }
+
+    async fn get_payee_statistics(
+        &self,
+        request: Request<PayeeStatisticsRequest>,
+    ) -> tonic::Result<Response<PayeeStatistics>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let PayeeStatisticsRequest {
+            payee_id,
+            since,
+            until,
+        } = request.into_inner();
+        let Some(payee_id) = self.state.ids.decode(Namespace::Payee, &payee_id) else {
+            return Err(Status::invalid_argument("bad payee id"));
+        };
+        let since = since.and_then(|x| from_proto_timestamp(x).ok());
+        let until = until.and_then(|x| from_proto_timestamp(x).ok());
+        let currencies = match sqlx::query!(
+            r#"select accounting_items.currency,
+       count(*) as "count!",
+       sum(accounting_items.amount) as "total!",
+       avg(accounting_items.amount) as "average!"
+from accounting_items
+where accounting_items.ledger_id = $1
+      and accounting_items.payee_id = $2
+      and ($3::timestamptz is null or accounting_items.occurred_at >= $3)
+      and ($4::timestamptz is null or accounting_items.occurred_at < $4)
+group by accounting_items.currency
+order by count(*) desc, accounting_items.currency"#,
+            ledger.ledger_id,
+            payee_id,
+            since,
+            until,
+        )
+        .map(|r| {
+            let scale = parse_currency(&r.currency)
+                .map(amount::exponent)
+                .unwrap_or(2);
+            PayeeCurrencyStatistics {
+                total: format_amount(&r.total),
+                count: r.count,
+                average: format_amount(&r.average.round(scale.into())),
+                currency: r.currency,
+            }
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get payee statistics", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let last_item = match sqlx::query_as!(
+            ItemRecord,
+            "select accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.created_at, accounting_items.occurred_at, accounting_items.payee_id, accounting_items.note, accounting_items.quantity, accounting_items.unit, accounting_items.account_id, accounting_items.reimbursable, accounting_items.status
+from accounting_items
+where accounting_items.ledger_id = $1
+      and accounting_items.payee_id = $2
+      and ($3::timestamptz is null or accounting_items.occurred_at >= $3)
+      and ($4::timestamptz is null or accounting_items.occurred_at < $4)
+order by accounting_items.occurred_at desc
+limit 1",
+            ledger.ledger_id,
+            payee_id,
+            since,
+            until,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(x) => x.map(|x| self.to_item(x)),
+            Err(err) => {
+                error!(action = "get payee last item", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(PayeeStatistics {
+            currencies,
+            last_item,
+        }))
+    }
+
+    async fn get_price_history(
+        &self,
+        request: Request<PriceHistoryRequest>,
+    ) -> tonic::Result<Response<PriceHistory>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let PriceHistoryRequest { subject } = request.into_inner();
+        let (name, payee_id) = match subject {
+            Some(Subject::Name(name)) => (Some(name), None),
+            Some(Subject::PayeeId(payee_id)) => {
+                let Some(payee_id) = self.state.ids.decode(Namespace::Payee, &payee_id) else {
+                    return Err(Status::invalid_argument("bad payee id"));
+                };
+                (None, Some(payee_id))
+            }
+            None => return Err(Status::invalid_argument("missing name or payee")),
+        };
+        let points = match sqlx::query!(
+            r#"select accounting_items.id,
+       accounting_items.occurred_at,
+       accounting_items.currency,
+       accounting_items.amount,
+       accounting_items.quantity as "quantity!",
+       accounting_items.unit
+from accounting_items
+where accounting_items.ledger_id = $1
+      and accounting_items.quantity is not null
+      and ($2::text is null or lower(accounting_items.name) = lower($2))
+      and ($3::integer is null or accounting_items.payee_id = $3)
+order by accounting_items.occurred_at"#,
+            ledger.ledger_id,
+            name,
+            payee_id,
+        )
+        .map(|r| PricePoint {
+            item_id: self.state.ids.encode(Namespace::Item, r.id),
+            occurred_at: Some(to_proto_timestamp(r.occurred_at)),
+            currency: r.currency,
+            unit_price: format_amount(&unit_price(&r.amount, &r.quantity)),
+            unit: r.unit.unwrap_or_default(),
+            quantity: format_amount(&r.quantity),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get price history", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(PriceHistory { points }))
+    }
+
+    async fn quick_add(
+        &self,
+        request: Request<QuickAddRequest>,
+    ) -> tonic::Result<Response<QuickAddResult>> {
+        let required = if request.get_ref().parse_only {
+            Role::Viewer
+        } else {
+            Role::Editor
+        };
+        let access = ledger::authorize_request(&self.state, &request, required).await?;
+        let QuickAddRequest { text, parse_only } = request.into_inner();
+        let now = match dateutils::user_now(&self.state.database, access.user_id).await {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get user time", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let entry = quickadd::parse(&text, now.date());
+        let Some(amount) = entry.amount else {
+            return Err(Status::invalid_argument("missing amount"));
+        };
+        let currency = match entry.currency {
+            Some(currency) => currency,
+            None => match sqlx::query_scalar!(
+                "select users.base_currency from users where users.id = $1",
+                access.user_id
+            )
+            .fetch_one(&self.state.database)
+            .await
+            {
+                Ok(x) => x,
+                Err(err) => {
+                    error!(action = "get base currency", error = ?err);
+                    return Err(Status::internal(String::new()));
+                }
+            },
+        };
+        let known = match sqlx::query!(
+            "select tags.id, tags.name from tags where tags.ledger_id = $1 and tags.name = any($2)",
+            access.ledger_id,
+            &entry.tags[..],
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "resolve quick add tags", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut tags = Vec::new();
+        let mut unknown_tags = Vec::new();
+        for name in entry.tags {
+            match known.iter().find(|x| x.name == name) {
+                Some(tag) => {
+                    let id = self.state.ids.encode(Namespace::Tag, tag.id);
+                    if !tags.contains(&id) {
+                        tags.push(id);
+                    }
+                }
+                None if !unknown_tags.contains(&name) => unknown_tags.push(name),
+                None => {}
+            }
+        }
+        // a date without a time keeps the current time of day
+        let occurred_at = match (entry.date, entry.time) {
+            (None, None) => None,
+            (date, time) => Some(to_proto_timestamp(
+                date.unwrap_or(now.date())
+                    .with_time(time.unwrap_or(now.time()))
+                    .assume_offset(now.offset()),
+            )),
+        };
+        let item = NewItem {
+            name: entry.name,
+            amount: Some(Amount {
+                amount: format_amount(&amount),
+                currency,
+            }),
+            r#type: if entry.income {
+                AmountType::Income
+            } else {
+                AmountType::Expense
+            }
+            .into(),
+            tags,
+            occurred_at,
+            ..Default::default()
+        };
+        let added = if parse_only {
+            None
+        } else {
+            Some(self.insert_item(&access, item.clone()).await?)
+        };
+        Ok(Response::new(QuickAddResult {
+            item: Some(item),
+            unknown_tags,
+            added,
+        }))
+    }
+
+    async fn find_duplicates(
+        &self,
+        request: Request<DuplicateSearch>,
+    ) -> tonic::Result<Response<DuplicateList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let DuplicateSearch { limit } = request.into_inner();
+        let limit = limit
+            .filter(|x| *x > 0)
+            .unwrap_or(DEFAULT_DUPLICATE_LIMIT)
+            .min(MAX_SEARCH_LIMIT);
+        let pairs =
+            match duplicate::find(&self.state.database, ledger.ledger_id, limit as usize).await {
+                Ok(x) => x,
+                Err(err) => {
+                    error!(action = "find duplicates", error = ?err);
+                    return Err(Status::internal(String::new()));
+                }
+            };
+        let ids: Vec<i32> = pairs
+            .iter()
+            .flat_map(|x| [x.item_id, x.other_item_id])
+            .collect();
+        let items = self.items_by_id(ledger.ledger_id, &ids).await?;
+        Ok(Response::new(DuplicateList {
+            pairs: pairs
+                .into_iter()
+                .map(|x| DuplicatePair {
+                    item: items.get(&x.item_id).cloned(),
+                    other: items.get(&x.other_item_id).cloned(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn merge_duplicates(
+        &self,
+        request: Request<MergeDuplicateRequest>,
+    ) -> tonic::Result<Response<Item>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let MergeDuplicateRequest { keep_id, remove_id } = request.into_inner();
+        let (Some(keep_id), Some(remove_id)) = (
+            self.state.ids.decode(Namespace::Item, &keep_id),
+            self.state.ids.decode(Namespace::Item, &remove_id),
+        ) else {
+            return Err(Status::invalid_argument("bad item id"));
+        };
+        if keep_id == remove_id {
+            return Err(Status::invalid_argument("can't merge an item into itself"));
+        }
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        // details missing from the kept item are taken from the removed one
+        let merged = match sqlx::query!(
+            "update accounting_items
+set payee_id = coalesce(accounting_items.payee_id, removed.payee_id),
+    note = coalesce(accounting_items.note, removed.note),
+    quantity = coalesce(accounting_items.quantity, removed.quantity),
+    unit = case when accounting_items.quantity is null then removed.unit else accounting_items.unit end,
+    account_id = coalesce(accounting_items.account_id, removed.account_id),
+    card_statement_id = coalesce(accounting_items.card_statement_id, removed.card_statement_id)
+from accounting_items removed
+where accounting_items.ledger_id = $1 and accounting_items.id = $2
+      and removed.ledger_id = $1 and removed.id = $3",
+            ledger.ledger_id,
+            keep_id,
+            remove_id,
+        )
+        .execute(&mut *tx)
+        .await
+        {
+            Ok(x) => x.rows_affected(),
+            Err(err) => {
+                error!(action = "merge duplicate details", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        if merged == 0 {
+            return Err(Status::not_found("item not found"));
+        }
+        // merged, an income offsetting the expense would offset itself
+        match sqlx::query_scalar!(
+            r#"select exists (select 1 from item_offsets
+              where (item_offsets.income_item_id = $1 and item_offsets.expense_item_id = $2)
+                    or (item_offsets.income_item_id = $2 and item_offsets.expense_item_id = $1)) as "exists!""#,
+            keep_id,
+            remove_id,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(false) => {}
+            Ok(true) => {
+                return Err(Status::failed_precondition("the items offset each other"));
+            }
+            Err(err) => {
+                error!(action = "check offsets between duplicates", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        }
+        // a plan moves with its purchase, while installments belong to their plan
+        match sqlx::query!(
+            r#"select exists (select 1 from accounting_items where accounting_items.id in ($1, $2) and accounting_items.installment_plan_id is not null) as "installment!",
+       (select count(*) from installment_plans where installment_plans.purchase_item_id in ($1, $2)) as "purchases!""#,
+            keep_id,
+            remove_id,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(x) if x.installment => {
+                return Err(Status::failed_precondition("installments can't be merged"));
+            }
+            Ok(x) if x.purchases > 1 => {
+                return Err(Status::failed_precondition(
+                    "both items are paid in installments",
+                ));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                error!(action = "check installments of duplicates", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        }
+        let moved = async {
+            sqlx::query!(
+                "insert into accounting_item_tags (tag_id, accounting_item_id)
+select accounting_item_tags.tag_id, $1
+from accounting_item_tags
+where accounting_item_tags.accounting_item_id = $2
+      and accounting_item_tags.tag_id not in (select kept.tag_id from accounting_item_tags kept where kept.accounting_item_id = $1)",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update shared_expenses set accounting_item_id = $1 where accounting_item_id = $2",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update settlements set accounting_item_id = $1 where accounting_item_id = $2",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update investment_transactions set accounting_item_id = $1 where accounting_item_id = $2",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update einvoices set accounting_item_id = $1 where accounting_item_id = $2",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update installment_plans set purchase_item_id = $1 where purchase_item_id = $2",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            // offsets of both items between the same income and expense add up
+            sqlx::query!(
+                "insert into item_offsets (income_item_id, expense_item_id, amount)
+select case when item_offsets.income_item_id = $2 then $1 else item_offsets.income_item_id end,
+       case when item_offsets.expense_item_id = $2 then $1 else item_offsets.expense_item_id end,
+       item_offsets.amount
+from item_offsets
+where item_offsets.income_item_id = $2 or item_offsets.expense_item_id = $2
+on conflict (income_item_id, expense_item_id) do update set amount = item_offsets.amount + excluded.amount",
+                keep_id,
+                remove_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!("delete from accounting_items where id = $1", remove_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(err) = moved {
+            error!(action = "merge duplicates", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        match self
+            .items_by_id(ledger.ledger_id, &[keep_id])
+            .await?
+            .remove(&keep_id)
+        {
+            Some(item) => Ok(Response::new(item)),
+            None => Err(Status::not_found("item not found")),
+        }
+    }
+
+    async fn dismiss_duplicate(
+        &self,
+        request: Request<DismissDuplicateRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let DismissDuplicateRequest {
+            item_id,
+            other_item_id,
+        } = request.into_inner();
+        let (Some(item_id), Some(other_item_id)) = (
+            self.state.ids.decode(Namespace::Item, &item_id),
+            self.state.ids.decode(Namespace::Item, &other_item_id),
+        ) else {
+            return Err(Status::invalid_argument("bad item id"));
+        };
+        if item_id == other_item_id {
+            return Err(Status::invalid_argument(
+                "an item isn't a duplicate of itself",
+            ));
+        }
+        let (item_id, other_item_id) = (item_id.min(other_item_id), item_id.max(other_item_id));
+        match sqlx::query!(
+            "insert into duplicate_dismissals (item_id, other_item_id)
+select items.id, others.id
+from accounting_items items, accounting_items others
+where items.ledger_id = $1 and items.id = $2
+      and others.ledger_id = $1 and others.id = $3
+on conflict do nothing",
+            ledger.ledger_id,
+            item_id,
+            other_item_id,
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            Ok(_) => Ok(Response::new(())),
+            Err(err) => {
+                error!(action = "dismiss duplicate", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn link_offset(&self, request: Request<Offset>) -> tonic::Result<Response<Offset>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let Offset {
+            income_id,
+            expense_id,
+            amount,
+        } = request.into_inner();
+        let (Some(income_item_id), Some(expense_item_id)) = (
+            self.state.ids.decode(Namespace::Item, &income_id),
+            self.state.ids.decode(Namespace::Item, &expense_id),
+        ) else {
+            return Err(Status::invalid_argument("bad item id"));
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        // what is left of each item, apart from the offset being replaced
+        let items = match sqlx::query!(
+            r#"select accounting_items.id,
+       accounting_items.amount,
+       accounting_items.currency,
+       abs(accounting_items.amount) - coalesce((
+           select sum(item_offsets.amount)
+           from item_offsets
+           where (item_offsets.income_item_id = accounting_items.id or item_offsets.expense_item_id = accounting_items.id)
+                 and not (item_offsets.income_item_id = $2 and item_offsets.expense_item_id = $3)
+       ), 0) as "remaining!"
+from accounting_items
+where accounting_items.ledger_id = $1 and accounting_items.id in ($2, $3)
+for update"#,
+            ledger.ledger_id,
+            income_item_id,
+            expense_item_id,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get items to offset", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let (Some(income), Some(expense)) = (
+            items.iter().find(|x| x.id == income_item_id),
+            items.iter().find(|x| x.id == expense_item_id),
+        ) else {
+            return Err(Status::not_found("item not found"));
+        };
+        if income.amount <= BigDecimal::from(0) {
+            return Err(Status::invalid_argument("income_id isn't an income"));
+        }
+        if expense.amount >= BigDecimal::from(0) {
+            return Err(Status::invalid_argument("expense_id isn't an expense"));
+        }
+        if income.currency != expense.currency {
+            return Err(Status::invalid_argument(
+                "the income and the expense are in different currencies",
+            ));
+        }
+        let remaining = (&income.remaining).min(&expense.remaining).clone();
+        let amount = match amount {
+            Some(amount) => {
+                let (amount, _) = parse_money("amount", &amount, "currency", &income.currency)?;
+                if amount > remaining {
+                    return Err(Status::invalid_argument(
+                        "amount is more than what is left of the income or the expense",
+                    ));
+                }
+                amount
+            }
+            None => remaining,
+        };
+        if amount <= BigDecimal::from(0) {
+            return Err(Status::failed_precondition("nothing is left to offset"));
+        }
+        if let Err(err) = sqlx::query!(
+            "insert into item_offsets (income_item_id, expense_item_id, amount)
+values ($1, $2, $3)
+on conflict (income_item_id, expense_item_id) do update set amount = excluded.amount",
+            income_item_id,
+            expense_item_id,
+            amount,
+        )
+        .execute(&mut *tx)
+        .await
+        {
+            error!(action = "link offset", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(Offset {
+            income_id,
+            expense_id,
+            amount: Some(format_amount(&amount)),
+        }))
+    }
+
+    async fn remove_offset(
+        &self,
+        request: Request<RemoveOffsetRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let RemoveOffsetRequest {
+            income_id,
+            expense_id,
+        } = request.into_inner();
+        let (Some(income_item_id), Some(expense_item_id)) = (
+            self.state.ids.decode(Namespace::Item, &income_id),
+            self.state.ids.decode(Namespace::Item, &expense_id),
+        ) else {
+            return Err(Status::invalid_argument("bad item id"));
+        };
+        if let Err(err) = sqlx::query!(
+            "delete from item_offsets
+using accounting_items
+where item_offsets.income_item_id = $2 and item_offsets.expense_item_id = $3
+      and accounting_items.id = item_offsets.income_item_id and accounting_items.ledger_id = $1",
+            ledger.ledger_id,
+            income_item_id,
+            expense_item_id,
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            error!(action = "remove offset", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn get_awaiting_repayment(
+        &self,
+        request: Request<()>,
+    ) -> tonic::Result<Response<AwaitingRepaymentList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        match sqlx::query!(
+            r#"select accounting_items.id,
+       accounting_items.name,
+       accounting_items.amount,
+       accounting_items.currency,
+       accounting_items.created_at,
+       accounting_items.occurred_at,
+       accounting_items.payee_id,
+       accounting_items.note,
+       accounting_items.quantity,
+       accounting_items.unit,
+       accounting_items.account_id,
+       accounting_items.reimbursable,
+       accounting_items.status,
+       outstanding.amount as "outstanding!"
+from accounting_items
+cross join lateral (
+    select -accounting_items.amount - coalesce(sum(item_offsets.amount), 0) as amount
+    from item_offsets
+    where item_offsets.expense_item_id = accounting_items.id
+) outstanding
+where accounting_items.ledger_id = $1
+      and accounting_items.reimbursable
+      and accounting_items.amount < 0
+      and outstanding.amount > 0
+order by accounting_items.occurred_at, accounting_items.id"#,
+            ledger.ledger_id,
+        )
+        .map(|r| AwaitingRepayment {
+            outstanding: format_amount(&r.outstanding),
+            item: Some(self.to_item(ItemRecord {
+                id: r.id,
+                name: r.name,
+                amount: r.amount,
+                currency: r.currency,
+                created_at: r.created_at,
+                occurred_at: r.occurred_at,
+                payee_id: r.payee_id,
+                note: r.note,
+                quantity: r.quantity,
+                unit: r.unit,
+                account_id: r.account_id,
+                reimbursable: r.reimbursable,
+                status: r.status,
+            })),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(items) => Ok(Response::new(AwaitingRepaymentList { items })),
+            Err(err) => {
+                error!(action = "get awaiting repayment", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn suggest_items(
+        &self,
+        request: Request<ItemSuggestionRequest>,
+    ) -> tonic::Result<Response<ItemSuggestionList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let ItemSuggestionRequest { prefix, limit } = request.into_inner();
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Ok(Response::new(ItemSuggestionList::default()));
+        }
+        let limit = match limit {
+            0 => DEFAULT_SUGGESTION_LIMIT,
+            limit => limit.min(MAX_SUGGESTION_LIMIT),
+        };
+        // Every use of a name scores by its age, halving every 30 days, so names used often and
+        // lately come first. Generated installments are named after their purchases and left out
+        match sqlx::query!(
+            r#"with names as (
+    select lower(accounting_items.name) as key,
+           count(*) as uses,
+           sum(power(0.5, greatest(extract(epoch from now() - accounting_items.occurred_at), 0) / (86400 * 30))) as score
+    from accounting_items
+    where accounting_items.ledger_id = $1
+          and lower(accounting_items.name) like $2 escape '\'
+          and accounting_items.installment_plan_id is null
+    group by lower(accounting_items.name)
+    order by score desc, max(accounting_items.occurred_at) desc
+    limit $3
+)
+select latest.name as "name!",
+       latest.amount,
+       latest.currency,
+       latest.payee_id,
+       latest.occurred_at,
+       names.uses as "uses!",
+       array(select tags.id from accounting_item_tags join tags on tags.id = accounting_item_tags.tag_id where accounting_item_tags.accounting_item_id = latest.id order by tags.name) as "tag_ids!",
+       array(select tags.name from accounting_item_tags join tags on tags.id = accounting_item_tags.tag_id where accounting_item_tags.accounting_item_id = latest.id order by tags.name) as "tag_names!"
+from names
+cross join lateral (
+    select accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.payee_id, accounting_items.occurred_at
+    from accounting_items
+    where accounting_items.ledger_id = $1
+          and lower(accounting_items.name) = names.key
+          and accounting_items.installment_plan_id is null
+    order by accounting_items.occurred_at desc, accounting_items.id desc
+    limit 1
+) latest
+order by names.score desc, latest.occurred_at desc"#,
+            ledger.ledger_id,
+            prefix_pattern(&prefix.to_lowercase()),
+            i64::from(limit),
+        )
+        .map(|r| ItemSuggestion {
+            name: r.name,
+            r#type: if r.amount < BigDecimal::from(0) {
+                AmountType::Expense
+            } else {
+                AmountType::Income
+            }
+            .into(),
+            amount: Some(Amount {
+                amount: format_amount(&r.amount.abs()),
+                currency: r.currency,
+            }),
+            tags: r
+                .tag_ids
+                .into_iter()
+                .zip(r.tag_names)
+                .map(|(id, name)| Tag {
+                    id: self.state.ids.encode(Namespace::Tag, id),
+                    name,
+                })
+                .collect(),
+            payee_id: r
+                .payee_id
+                .map(|id| self.state.ids.encode(Namespace::Payee, id)),
+            count: r.uses,
+            last_used_at: Some(to_proto_timestamp(r.occurred_at)),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(suggestions) => Ok(Response::new(ItemSuggestionList { suggestions })),
+            Err(err) => {
+                error!(action = "suggest items", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn confirm_item(
+        &self,
+        request: Request<ConfirmItemRequest>,
+    ) -> tonic::Result<Response<Item>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let ConfirmItemRequest {
+            id,
+            amount,
+            occurred_at,
+        } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Item, &id) else {
+            return Err(Status::invalid_argument("bad id"));
+        };
+        let occurred_at = match occurred_at {
+            Some(x) => Some(
+                from_proto_timestamp(x)
+                    .map_err(|_err| Status::invalid_argument("bad occurred_at"))?,
+            ),
+            None => None,
+        };
+        let item = match sqlx::query!(
+            "select accounting_items.currency, accounting_items.status
+from accounting_items
+where accounting_items.ledger_id = $1 and accounting_items.id = $2",
+            ledger.ledger_id,
+            id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(Status::not_found("item not found")),
+            Err(err) => {
+                error!(action = "get item to confirm", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        if item.status != "pending" {
+            return Err(Status::failed_precondition("the item isn't pending"));
+        }
+        let amount = match amount.filter(|x| !x.is_empty()) {
+            Some(amount) => Some(parse_money("amount", &amount, "currency", &item.currency)?.0),
+            None => None,
+        };
+        match sqlx::query_as!(
+            ItemRecord,
+            "update accounting_items
+set status = 'cleared',
+    amount = coalesce((case when amount = 0 then 1 else sign(amount) end)*$3, amount),
+    occurred_at = coalesce($4, occurred_at)
+where accounting_items.ledger_id = $1 and accounting_items.id = $2 and accounting_items.status = 'pending'
+returning accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.created_at, accounting_items.occurred_at, accounting_items.payee_id, accounting_items.note, accounting_items.quantity, accounting_items.unit, accounting_items.account_id, accounting_items.reimbursable, accounting_items.status",
+            ledger.ledger_id,
+            id,
+            amount,
+            occurred_at,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(record)) => Ok(Response::new(self.to_item(record))),
+            Ok(None) => Err(Status::failed_precondition("the item isn't pending")),
+            Err(err) => {
+                error!(action = "confirm item", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn list_upcoming(&self, request: Request<()>) -> tonic::Result<Response<ItemList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        match sqlx::query_as!(
+            ItemRecord,
+            "select accounting_items.id, accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.created_at, accounting_items.occurred_at, accounting_items.payee_id, accounting_items.note, accounting_items.quantity, accounting_items.unit, accounting_items.account_id, accounting_items.reimbursable, accounting_items.status
+from accounting_items
+join users on users.id = $2
+where accounting_items.ledger_id = $1
+      and accounting_items.occurred_at >= date_trunc('day', now(), users.time_zone) + interval '1 day'
+order by accounting_items.occurred_at, accounting_items.id",
+            ledger.ledger_id,
+            ledger.user_id,
+        )
+        .map(|x| self.to_item(x))
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(items) => Ok(Response::new(ItemList { items })),
+            Err(err) => {
+                error!(action = "list upcoming items", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+}
+
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+const DEFAULT_DUPLICATE_LIMIT: u32 = 100;
+const MAX_SEARCH_LIMIT: u32 = 200;
+const DEFAULT_SUGGESTION_LIMIT: u32 = 10;
+const DEFAULT_TAG_LIMIT: u32 = 20;
+const MAX_TAG_LIMIT: u32 = 100;
+const MAX_SUGGESTION_LIMIT: u32 = 50;
+
+pub(crate) fn format_amount(a: &BigDecimal) -> String {
+    a.normalized().to_plain_string()
+}
+
+fn parse_quantity(quantity: &str) -> tonic::Result<BigDecimal> {
+    amount::parse_quantity(quantity).map_err(|err| bad_request(vec![("quantity", err)]))
+}
+
+fn unit_price(amount: &BigDecimal, quantity: &BigDecimal) -> BigDecimal {
+    (amount.abs() / quantity).round(4)
+}
+
+fn status_name(status: i32) -> tonic::Result<&'static str> {
+    match ItemStatus::try_from(status) {
+        Ok(ItemStatus::Cleared) => Ok("cleared"),
+        Ok(ItemStatus::Pending) => Ok("pending"),
+        Ok(ItemStatus::Reconciled) => Ok("reconciled"),
+        Err(_) => Err(Status::invalid_argument("bad status")),
+    }
+}
+
+fn status_from_name(name: &str) -> ItemStatus {
+    match name {
+        "pending" => ItemStatus::Pending,
+        "reconciled" => ItemStatus::Reconciled,
+        _ => ItemStatus::Cleared,
+    }
 }
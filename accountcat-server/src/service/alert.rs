@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    anomaly::rules::Thresholds,
+    auth::claims_from_request,
+    idcodec::Namespace,
+    idl::alert::{
+        AlertIdRequest, AlertKind, AlertList, AlertSettings, SpendingAlert, UnreadCount,
+        alert_server,
+    },
+    protobufutils::to_proto_timestamp,
+    server::ServerState,
+    service::accounting::format_amount,
+};
+
+/// Largest ratio `numeric(6,2)` holds
+const MAX_RATIO: i32 = 10000;
+
+pub struct AlertApi {
+    state: Arc<ServerState>,
+}
+
+impl AlertApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+}
+
+fn to_settings(thresholds: Thresholds) -> AlertSettings {
+    AlertSettings {
+        enabled: thresholds.enabled,
+        tag_ratio: format_amount(&thresholds.tag_ratio),
+        item_ratio: format_amount(&thresholds.item_ratio),
+        min_amount: format_amount(&thresholds.min_amount),
+    }
+}
+
+fn parse_ratio(ratio: &str, field: &str) -> tonic::Result<BigDecimal> {
+    match ratio.parse::<BigDecimal>() {
+        Ok(x) if x > BigDecimal::from(1) && x < BigDecimal::from(MAX_RATIO) => Ok(x.round(2)),
+        _ => Err(Status::invalid_argument(format!(
+            "{field} must be a number between 1 and {MAX_RATIO}"
+        ))),
+    }
+}
+
+#[tonic::async_trait]
+impl alert_server::Alert for AlertApi {
+    async fn list_alerts(&self, request: Request<()>) -> tonic::Result<Response<AlertList>> {
+        let claims = claims_from_request(&request)?;
+        match sqlx::query!(
+            r#"select alerts.id,
+       alerts.ledger_id,
+       alerts.kind,
+       alerts.explanation,
+       alerts.currency,
+       alerts.baseline,
+       alerts.actual,
+       alerts.deviation,
+       alerts.tag_id,
+       alerts.payee_id,
+       alerts.accounting_item_id,
+       to_char(alerts.month, 'YYYY-MM') as month,
+       alerts.read_at is not null as "read!",
+       alerts.created_at
+from alerts
+join users on users.id = alerts.user_id
+where users.google_sub = $1
+      and alerts.dismissed_at is null
+      and exists (select 1 from ledger_members where ledger_members.ledger_id = alerts.ledger_id and ledger_members.user_id = alerts.user_id)
+order by alerts.created_at desc, alerts.id desc"#,
+            claims.sub
+        )
+        .map(|r| SpendingAlert {
+            id: self.state.ids.encode(Namespace::Alert, r.id),
+            ledger_id: self.state.ids.encode(Namespace::Ledger, r.ledger_id),
+            kind: match r.kind.as_str() {
+                "large_item" => AlertKind::LargeItem,
+                _ => AlertKind::TagMonth,
+            } as i32,
+            explanation: r.explanation,
+            currency: r.currency,
+            baseline: format_amount(&r.baseline),
+            actual: format_amount(&r.actual),
+            deviation: format_amount(&r.deviation),
+            tag_id: r.tag_id.map(|id| self.state.ids.encode(Namespace::Tag, id)),
+            payee_id: r
+                .payee_id
+                .map(|id| self.state.ids.encode(Namespace::Payee, id)),
+            item_id: r
+                .accounting_item_id
+                .map(|id| self.state.ids.encode(Namespace::Item, id)),
+            month: r.month,
+            read: r.read,
+            created_at: Some(to_proto_timestamp(r.created_at)),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(alerts) => Ok(Response::new(AlertList { alerts })),
+            Err(err) => {
+                error!(action = "list alerts", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn get_unread_count(&self, request: Request<()>) -> tonic::Result<Response<UnreadCount>> {
+        let claims = claims_from_request(&request)?;
+        match sqlx::query_scalar!(
+            r#"select count(*) as "count!"
+from alerts
+join users on users.id = alerts.user_id
+where users.google_sub = $1
+      and alerts.read_at is null
+      and alerts.dismissed_at is null
+      and exists (select 1 from ledger_members where ledger_members.ledger_id = alerts.ledger_id and ledger_members.user_id = alerts.user_id)"#,
+            claims.sub
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(count) => Ok(Response::new(UnreadCount { count })),
+            Err(err) => {
+                error!(action = "count unread alerts", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn mark_read(&self, request: Request<AlertIdRequest>) -> tonic::Result<Response<()>> {
+        let claims = claims_from_request(&request)?;
+        let AlertIdRequest { id } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Alert, &id) else {
+            return Err(Status::invalid_argument("bad id"));
+        };
+        if let Err(err) = sqlx::query!(
+            "update alerts
+set read_at = coalesce(alerts.read_at, now())
+from users
+where users.id = alerts.user_id
+      and users.google_sub = $1
+      and alerts.id = $2
+      and exists (select 1 from ledger_members where ledger_members.ledger_id = alerts.ledger_id and ledger_members.user_id = alerts.user_id)",
+            claims.sub,
+            id
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            error!(action = "mark alert read", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn dismiss(&self, request: Request<AlertIdRequest>) -> tonic::Result<Response<()>> {
+        let claims = claims_from_request(&request)?;
+        let AlertIdRequest { id } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Alert, &id) else {
+            return Err(Status::invalid_argument("bad id"));
+        };
+        if let Err(err) = sqlx::query!(
+            "update alerts
+set dismissed_at = coalesce(alerts.dismissed_at, now())
+from users
+where users.id = alerts.user_id
+      and users.google_sub = $1
+      and alerts.id = $2
+      and exists (select 1 from ledger_members where ledger_members.ledger_id = alerts.ledger_id and ledger_members.user_id = alerts.user_id)",
+            claims.sub,
+            id
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            error!(action = "dismiss alert", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn get_settings(&self, request: Request<()>) -> tonic::Result<Response<AlertSettings>> {
+        let claims = claims_from_request(&request)?;
+        match sqlx::query_as!(
+            Thresholds,
+            "select alert_settings.enabled,
+       alert_settings.tag_ratio,
+       alert_settings.item_ratio,
+       alert_settings.min_amount
+from alert_settings
+join users on users.id = alert_settings.user_id
+where users.google_sub = $1",
+            claims.sub
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(thresholds) => Ok(Response::new(to_settings(thresholds.unwrap_or_default()))),
+            Err(err) => {
+                error!(action = "get alert settings", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn update_settings(
+        &self,
+        request: Request<AlertSettings>,
+    ) -> tonic::Result<Response<AlertSettings>> {
+        let claims = claims_from_request(&request)?;
+        let AlertSettings {
+            enabled,
+            tag_ratio,
+            item_ratio,
+            min_amount,
+        } = request.into_inner();
+        let tag_ratio = parse_ratio(&tag_ratio, "tag ratio")?;
+        let item_ratio = parse_ratio(&item_ratio, "item ratio")?;
+        let min_amount = match min_amount.parse::<BigDecimal>() {
+            Ok(x) if x >= BigDecimal::zero() && x < BigDecimal::from(10i64.pow(16)) => x.round(3),
+            _ => {
+                return Err(Status::invalid_argument(
+                    "min amount must be a non-negative number",
+                ));
+            }
+        };
+        match sqlx::query_as!(
+            Thresholds,
+            "insert into alert_settings (user_id, enabled, tag_ratio, item_ratio, min_amount)
+select users.id, $2, $3, $4, $5
+from users
+where users.google_sub = $1
+on conflict (user_id) do update
+set enabled = excluded.enabled,
+    tag_ratio = excluded.tag_ratio,
+    item_ratio = excluded.item_ratio,
+    min_amount = excluded.min_amount
+returning alert_settings.enabled,
+          alert_settings.tag_ratio,
+          alert_settings.item_ratio,
+          alert_settings.min_amount",
+            claims.sub,
+            enabled,
+            tag_ratio,
+            item_ratio,
+            min_amount,
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(thresholds) => Ok(Response::new(to_settings(thresholds))),
+            Err(err) => {
+                error!(action = "update alert settings", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use std::{collections::HashMap, sync::Arc};
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    idcodec::Namespace,
+    idl::{
+        accounting::{Amount, AmountType, NewItem},
+        data_import::{
+            self as proto, AppExport, AppExportPreview, AppImportRequest, CategoryTag, EInvoice,
+            EInvoiceFile, EInvoiceLine, EInvoiceRequest, ImportSummary, data_import_server,
+        },
+    },
+    import::{
+        einvoice,
+        profile::{App, Export, Record},
+    },
+    ledger::{self, Role},
+    protobufutils::to_proto_timestamp,
+    server::ServerState,
+    service::accounting::{AccountingApi, format_amount},
+};
+
+pub struct DataImportApi {
+    state: Arc<ServerState>,
+    /// Items of app exports are added the same way as [AccountingApi] adds them
+    accounting: AccountingApi,
+}
+
+impl DataImportApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self {
+            accounting: AccountingApi::new(state.clone()),
+            state,
+        }
+    }
+
+    /// Ids of the tags named after the categories
+    async fn tags_by_name(
+        &self,
+        ledger_id: i32,
+        categories: &[String],
+    ) -> tonic::Result<HashMap<String, i32>> {
+        match sqlx::query!(
+            "select tags.id, tags.name from tags where tags.ledger_id = $1 and tags.name = any($2)",
+            ledger_id,
+            categories,
+        )
+        .map(|r| (r.name, r.id))
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => Ok(x.into_iter().collect()),
+            Err(err) => {
+                error!(action = "find tags of categories", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    /// Times of the records, local times of exports without time zones in the time zone of the
+    /// user
+    async fn occurred_at(
+        &self,
+        user_id: i32,
+        records: &[Record],
+    ) -> tonic::Result<Vec<OffsetDateTime>> {
+        let local: Vec<PrimitiveDateTime> = records.iter().map(|x| x.occurred_at).collect();
+        let zoned = match sqlx::query_scalar!(
+            r#"select times.local at time zone users.time_zone as "occurred_at!"
+from unnest($1::timestamp[]) with ordinality as times(local, position)
+join users on users.id = $2
+order by times.position"#,
+            &local,
+            user_id,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "resolve times of records", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(records
+            .iter()
+            .zip(zoned)
+            .map(|(record, zoned)| match record.offset {
+                Some(offset) => record.occurred_at.assume_offset(offset),
+                None => zoned,
+            })
+            .collect())
+    }
+}
+
+fn parse_export(app: i32, content: &str) -> tonic::Result<Export> {
+    let app = match proto::App::try_from(app) {
+        Ok(proto::App::Moze) => App::Moze,
+        Ok(proto::App::MoneyLover) => App::MoneyLover,
+        Ok(proto::App::Spendee) => App::Spendee,
+        Err(_) => return Err(Status::invalid_argument("unknown app")),
+    };
+    app.profile()
+        .parse(content)
+        .map_err(|err| Status::invalid_argument(err.to_string()))
+}
+
+/// Category names in the order they first appear, with the numbers of their items. Items without
+/// categories are left untagged
+fn categories(export: &Export) -> Vec<(String, i32)> {
+    let mut categories: Vec<(String, i32)> = Vec::new();
+    for record in export
+        .records
+        .iter()
+        .filter(|x| !x.category.trim().is_empty())
+    {
+        match categories.iter_mut().find(|(x, _)| *x == record.category) {
+            Some((_, count)) => *count += 1,
+            None => categories.push((record.category.clone(), 1)),
+        }
+    }
+    categories
+}
+
+#[tonic::async_trait]
+impl data_import_server::DataImport for DataImportApi {
+    async fn import_e_invoices(
+        &self,
+        request: Request<EInvoiceFile>,
+    ) -> tonic::Result<Response<ImportSummary>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let EInvoiceFile { content } = request.into_inner();
+        let invoices = einvoice::parse_csv(&content)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let summary =
+            match einvoice::import(&mut tx, access.ledger_id, access.user_id, &invoices).await {
+                Ok(x) => x,
+                Err(err) => {
+                    error!(action = "import e-invoices", error = ?err);
+                    return Err(Status::internal(String::new()));
+                }
+            };
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(ImportSummary {
+            created: summary.created as i32,
+            duplicated: summary.duplicated as i32,
+            skipped: 0,
+        }))
+    }
+
+    async fn get_e_invoice(
+        &self,
+        request: Request<EInvoiceRequest>,
+    ) -> tonic::Result<Response<EInvoice>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let EInvoiceRequest { item_id } = request.into_inner();
+        let Some(item_id) = self.state.ids.decode(Namespace::Item, &item_id) else {
+            return Err(Status::invalid_argument("bad item id"));
+        };
+        let invoice = match sqlx::query!(
+            "select einvoices.id, einvoices.number, einvoices.seller_tax_id
+from einvoices
+where einvoices.ledger_id = $1 and einvoices.accounting_item_id = $2",
+            access.ledger_id,
+            item_id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(Status::not_found("the item isn't imported from an invoice")),
+            Err(err) => {
+                error!(action = "get e-invoice", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let lines = match sqlx::query!(
+            "select einvoice_lines.name, einvoice_lines.amount
+from einvoice_lines
+where einvoice_lines.einvoice_id = $1
+order by einvoice_lines.position",
+            invoice.id,
+        )
+        .map(|r| EInvoiceLine {
+            name: r.name,
+            amount: format_amount(&r.amount),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get e-invoice lines", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(EInvoice {
+            number: invoice.number,
+            seller_tax_id: invoice.seller_tax_id,
+            lines,
+        }))
+    }
+
+    async fn preview_app_export(
+        &self,
+        request: Request<AppExport>,
+    ) -> tonic::Result<Response<AppExportPreview>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let AppExport { app, content } = request.into_inner();
+        let export = parse_export(app, &content)?;
+        let categories = categories(&export);
+        let names: Vec<String> = categories.iter().map(|(x, _)| x.clone()).collect();
+        let tags = self.tags_by_name(access.ledger_id, &names).await?;
+        Ok(Response::new(AppExportPreview {
+            categories: categories
+                .into_iter()
+                .map(|(category, count)| CategoryTag {
+                    tag_id: tags
+                        .get(&category)
+                        .map(|id| self.state.ids.encode(Namespace::Tag, *id)),
+                    category,
+                    count,
+                })
+                .collect(),
+            items: export.records.len() as i32,
+            skipped: export.skipped as i32,
+        }))
+    }
+
+    async fn import_app_export(
+        &self,
+        request: Request<AppImportRequest>,
+    ) -> tonic::Result<Response<ImportSummary>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let AppImportRequest {
+            app,
+            content,
+            category_tags,
+            create_missing_tags,
+        } = request.into_inner();
+        let export = parse_export(app, &content)?;
+        let names: Vec<String> = categories(&export).into_iter().map(|(x, _)| x).collect();
+        let mut tags = self.tags_by_name(access.ledger_id, &names).await?;
+        for (category, tag_id) in category_tags {
+            let Some(tag_id) = self.state.ids.decode(Namespace::Tag, &tag_id) else {
+                return Err(Status::invalid_argument("bad tag id"));
+            };
+            tags.insert(category, tag_id);
+        }
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        if create_missing_tags {
+            let missing: Vec<String> = names
+                .into_iter()
+                .filter(|x| !tags.contains_key(x))
+                .collect();
+            for category in missing {
+                match sqlx::query_scalar!(
+                    "insert into tags (ledger_id, user_id, name)
+values ($1, $2, $3)
+on conflict do nothing
+returning tags.id",
+                    access.ledger_id,
+                    access.user_id,
+                    category,
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                {
+                    Ok(Some(id)) => {
+                        tags.insert(category, id);
+                    }
+                    // a tag of the name was added since the tags were looked up
+                    Ok(None) => {}
+                    Err(err) => {
+                        error!(action = "create tag of category", error = ?err);
+                        return Err(Status::internal(String::new()));
+                    }
+                }
+            }
+        }
+        let times = self.occurred_at(access.user_id, &export.records).await?;
+        let mut duplicated = 0;
+        for (record, occurred_at) in export.records.iter().zip(times) {
+            let item = NewItem {
+                name: record.name.clone(),
+                amount: Some(Amount {
+                    amount: format_amount(&record.amount.abs()),
+                    currency: record.currency.clone(),
+                }),
+                r#type: if record.amount < BigDecimal::zero() {
+                    AmountType::Expense
+                } else {
+                    AmountType::Income
+                }
+                .into(),
+                tags: tags
+                    .get(&record.category)
+                    .map(|id| self.state.ids.encode(Namespace::Tag, *id))
+                    .into_iter()
+                    .collect(),
+                note: record.note.clone(),
+                occurred_at: Some(to_proto_timestamp(occurred_at)),
+                ..Default::default()
+            };
+            let item = self
+                .accounting
+                .insert_item_in(&mut tx, &access, item)
+                .await?;
+            if !item.duplicate_of.is_empty() {
+                duplicated += 1;
+            }
+        }
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(ImportSummary {
+            created: export.records.len() as i32,
+            duplicated,
+            skipped: export.skipped as i32,
+        }))
+    }
+}
@@ -0,0 +1,448 @@
+use std::sync::Arc;
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::{Date, Duration};
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    amount::{exponent, parse_currency, parse_money},
+    dateutils::{format_date, parse_date},
+    forecast::{self, Recurrence, occurrences, project},
+    idcodec::Namespace,
+    idl::forecast::{
+        self as proto, CashFlowForecast, DeleteScheduledItemRequest, ForecastDay, ForecastRequest,
+        NewScheduledItem, ScheduledItem, ScheduledItemList, ScheduledItemType, TagEstimate,
+        forecast_server,
+    },
+    ledger::{self, Role},
+    server::ServerState,
+    service::accounting::format_amount,
+};
+
+/// Longest period that can be projected
+const MAX_DAYS: u32 = 366;
+const DEFAULT_TRAILING_DAYS: u32 = 90;
+
+pub struct ForecastApi {
+    state: Arc<ServerState>,
+}
+
+impl ForecastApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    fn to_scheduled_item(&self, record: ScheduledItemRecord) -> ScheduledItem {
+        let recurrence = match Recurrence::from_record(&record.recurrence) {
+            Some(Recurrence::Weekly) => proto::Recurrence::Weekly,
+            Some(Recurrence::Monthly) => proto::Recurrence::Monthly,
+            _ => proto::Recurrence::Once,
+        };
+        ScheduledItem {
+            id: self.state.ids.encode(Namespace::ScheduledItem, record.id),
+            name: record.name,
+            amount: format_amount(&record.amount.abs()),
+            currency: record.currency,
+            r#type: if record.amount < BigDecimal::zero() {
+                ScheduledItemType::Expense
+            } else {
+                ScheduledItemType::Income
+            } as i32,
+            recurrence: recurrence as i32,
+            start_date: format_date(record.start_date),
+            end_date: record.end_date.map(format_date),
+            tag_id: record
+                .tag_id
+                .map(|id| self.state.ids.encode(Namespace::Tag, id)),
+        }
+    }
+}
+
+struct ScheduledItemRecord {
+    id: i32,
+    name: String,
+    amount: BigDecimal,
+    currency: String,
+    recurrence: String,
+    start_date: Date,
+    end_date: Option<Date>,
+    tag_id: Option<i32>,
+}
+
+fn recurrence_from_proto(recurrence: i32) -> tonic::Result<Recurrence> {
+    match proto::Recurrence::try_from(recurrence) {
+        Ok(proto::Recurrence::Once) => Ok(Recurrence::Once),
+        Ok(proto::Recurrence::Weekly) => Ok(Recurrence::Weekly),
+        Ok(proto::Recurrence::Monthly) => Ok(Recurrence::Monthly),
+        Err(_) => Err(Status::invalid_argument("bad recurrence")),
+    }
+}
+
+#[tonic::async_trait]
+impl forecast_server::Forecast for ForecastApi {
+    async fn create_scheduled_item(
+        &self,
+        request: Request<NewScheduledItem>,
+    ) -> tonic::Result<Response<ScheduledItem>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewScheduledItem {
+            name,
+            amount,
+            currency,
+            r#type,
+            recurrence,
+            start_date,
+            end_date,
+            tag_id,
+        } = request.into_inner();
+        if name.trim().is_empty() {
+            return Err(Status::invalid_argument("missing name"));
+        }
+        let (mut amount, _) = parse_money("amount", &amount, "currency", &currency)?;
+        if r#type == ScheduledItemType::Expense as i32 {
+            amount = -amount;
+        }
+        let recurrence = recurrence_from_proto(recurrence)?;
+        let Some(start_date) = parse_date(&start_date) else {
+            return Err(Status::invalid_argument("bad start date"));
+        };
+        let end_date = match end_date.filter(|x| !x.is_empty()) {
+            Some(end_date) => match parse_date(&end_date) {
+                Some(x) if x >= start_date => Some(x),
+                _ => return Err(Status::invalid_argument("bad end date")),
+            },
+            None => None,
+        };
+        let tag_id = match tag_id.filter(|x| !x.is_empty()) {
+            Some(tag_id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Tag, &tag_id)
+                    .ok_or_else(|| Status::invalid_argument("bad tag id"))?,
+            ),
+            None => None,
+        };
+        match sqlx::query_as!(
+            ScheduledItemRecord,
+            "insert into scheduled_items (ledger_id, name, amount, currency, recurrence, start_date, end_date, tag_id)
+select $1, $2, $3, $4, $5, $6, $7, $8
+where $8::integer is null or exists (select 1 from tags where tags.ledger_id = $1 and tags.id = $8)
+returning scheduled_items.id,
+          scheduled_items.name,
+          scheduled_items.amount,
+          scheduled_items.currency,
+          scheduled_items.recurrence,
+          scheduled_items.start_date,
+          scheduled_items.end_date,
+          scheduled_items.tag_id",
+            ledger.ledger_id,
+            name,
+            amount,
+            currency,
+            recurrence.as_str(),
+            start_date,
+            end_date,
+            tag_id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(record)) => Ok(Response::new(self.to_scheduled_item(record))),
+            Ok(None) => Err(Status::invalid_argument("tag not found")),
+            Err(err) => {
+                error!(action = "create scheduled item", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn list_scheduled_items(
+        &self,
+        request: Request<()>,
+    ) -> tonic::Result<Response<ScheduledItemList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        match sqlx::query_as!(
+            ScheduledItemRecord,
+            "select scheduled_items.id,
+       scheduled_items.name,
+       scheduled_items.amount,
+       scheduled_items.currency,
+       scheduled_items.recurrence,
+       scheduled_items.start_date,
+       scheduled_items.end_date,
+       scheduled_items.tag_id
+from scheduled_items
+where scheduled_items.ledger_id = $1
+order by scheduled_items.start_date, scheduled_items.id",
+            ledger.ledger_id
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(records) => Ok(Response::new(ScheduledItemList {
+                items: records
+                    .into_iter()
+                    .map(|x| self.to_scheduled_item(x))
+                    .collect(),
+            })),
+            Err(err) => {
+                error!(action = "list scheduled items", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn delete_scheduled_item(
+        &self,
+        request: Request<DeleteScheduledItemRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let DeleteScheduledItemRequest { id } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::ScheduledItem, &id) else {
+            return Err(Status::invalid_argument("bad id"));
+        };
+        if let Err(err) = sqlx::query!(
+            "delete from scheduled_items where scheduled_items.ledger_id = $1 and scheduled_items.id = $2",
+            ledger.ledger_id,
+            id
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            error!(action = "delete scheduled item", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn get_forecast(
+        &self,
+        request: Request<ForecastRequest>,
+    ) -> tonic::Result<Response<CashFlowForecast>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let ForecastRequest {
+            days,
+            trailing_days,
+        } = request.into_inner();
+        if days == 0 || days > MAX_DAYS {
+            return Err(Status::invalid_argument(format!(
+                "days must be between 1 and {MAX_DAYS}"
+            )));
+        }
+        let trailing_days = match trailing_days {
+            None => DEFAULT_TRAILING_DAYS,
+            Some(x) if (1..=MAX_DAYS).contains(&x) => x,
+            Some(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "trailing days must be between 1 and {MAX_DAYS}"
+                )));
+            }
+        };
+        // asset accounts start from their latest snapshot, or the opening balance when there is
+        // none, plus the items after it
+        let opening = match sqlx::query!(
+            r#"select users.base_currency,
+       users.time_zone,
+       (now() at time zone users.time_zone)::date as "today!",
+       coalesce((
+           select sum(coalesce(latest.value, accounts.opening_balance) + coalesce((
+               select sum(accounting_items.amount)
+               from accounting_items
+               where accounting_items.account_id = accounts.id
+                     and accounting_items.currency = accounts.currency
+                     and (latest.date is null or (accounting_items.occurred_at at time zone users.time_zone)::date > latest.date)
+                     and (accounting_items.occurred_at at time zone users.time_zone)::date <= (now() at time zone users.time_zone)::date
+           ), 0))
+           from accounts
+           left join lateral (
+               select account_snapshots.date, account_snapshots.value
+               from account_snapshots
+               where account_snapshots.account_id = accounts.id
+                     and account_snapshots.date <= (now() at time zone users.time_zone)::date
+               order by account_snapshots.date desc
+               limit 1
+           ) latest on true
+           where accounts.ledger_id = $1 and accounts.kind = 'asset' and accounts.currency = users.base_currency
+       ), 0) as "balance!"
+from users
+where users.id = $2"#,
+            ledger.ledger_id,
+            ledger.user_id,
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get forecast opening balance", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let currency = opening.base_currency;
+        let today = opening.today;
+        let until = today + Duration::days(i64::from(days));
+        // like the opening balance, only items of asset accounts move it
+        let future_items = match sqlx::query!(
+            r#"select (accounting_items.occurred_at at time zone $4)::date as "date!",
+       accounting_items.amount,
+       accounting_items.currency
+from accounting_items
+join accounts on accounts.id = accounting_items.account_id
+where accounting_items.ledger_id = $1
+      and accounts.ledger_id = $1
+      and accounts.kind = 'asset'
+      and (accounting_items.occurred_at at time zone $4)::date > $2
+      and (accounting_items.occurred_at at time zone $4)::date <= $3"#,
+            ledger.ledger_id,
+            today,
+            until,
+            &opening.time_zone,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get future-dated items", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let scheduled_items = match sqlx::query_as!(
+            ScheduledItemRecord,
+            "select scheduled_items.id,
+       scheduled_items.name,
+       scheduled_items.amount,
+       scheduled_items.currency,
+       scheduled_items.recurrence,
+       scheduled_items.start_date,
+       scheduled_items.end_date,
+       scheduled_items.tag_id
+from scheduled_items
+where scheduled_items.ledger_id = $1",
+            ledger.ledger_id
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get scheduled items", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut unsupported_count = 0;
+        let mut committed = Vec::new();
+        for item in future_items {
+            if item.currency == currency {
+                committed.push((item.date, item.amount));
+            } else {
+                unsupported_count += 1;
+            }
+        }
+        for item in scheduled_items {
+            let Some(recurrence) = Recurrence::from_record(&item.recurrence) else {
+                continue;
+            };
+            let dates = occurrences(
+                item.start_date,
+                item.end_date,
+                recurrence,
+                today + Duration::days(1),
+                until,
+            );
+            if item.currency != currency {
+                unsupported_count += dates.len() as i64;
+                continue;
+            }
+            committed.extend(dates.into_iter().map(|date| (date, item.amount.clone())));
+        }
+        // items are split evenly among their tags, so spending with several tags counts once.
+        // Tags covered by scheduled items are left out
+        let estimates = match sqlx::query!(
+            r#"with items as (
+    select accounting_items.id, accounting_items.amount
+    from accounting_items
+    where accounting_items.ledger_id = $1
+          and accounting_items.currency = $2
+          and accounting_items.amount < 0
+          and (accounting_items.occurred_at at time zone $5)::date > $3::date - $4::integer
+          and (accounting_items.occurred_at at time zone $5)::date <= $3
+),
+item_tags as (
+    select items.amount,
+           accounting_item_tags.tag_id,
+           count(*) over (partition by items.id) as tag_count
+    from items
+    left join accounting_item_tags on accounting_item_tags.accounting_item_id = items.id
+)
+select item_tags.tag_id as "tag_id?",
+       tags.name as "tag_name?",
+       sum(item_tags.amount / item_tags.tag_count) / $4 as "daily_amount!"
+from item_tags
+left join tags on tags.id = item_tags.tag_id
+where item_tags.tag_id is null
+      or item_tags.tag_id not in (
+          select scheduled_items.tag_id
+          from scheduled_items
+          where scheduled_items.ledger_id = $1 and scheduled_items.tag_id is not null
+      )
+group by item_tags.tag_id, tags.name
+order by 3, tags.name"#,
+            ledger.ledger_id,
+            currency,
+            today,
+            trailing_days as i32,
+            &opening.time_zone,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "estimate variable spending", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let scale = parse_currency(&currency).map(exponent).unwrap_or(2).into();
+        let daily_estimate: BigDecimal = estimates.iter().map(|x| &x.daily_amount).sum();
+        let daily_estimate = daily_estimate.round(scale);
+        let days = project(
+            today,
+            days as u16,
+            &opening.balance,
+            &committed,
+            &daily_estimate,
+        );
+        let shortfall_date = days
+            .iter()
+            .find(|x| x.balance < BigDecimal::zero())
+            .map(|x| format_date(x.date));
+        Ok(Response::new(CashFlowForecast {
+            currency,
+            opening_balance: format_amount(&opening.balance),
+            days: days.into_iter().map(to_forecast_day).collect(),
+            estimates: estimates
+                .into_iter()
+                .map(|x| TagEstimate {
+                    tag_id: x.tag_id.map(|id| self.state.ids.encode(Namespace::Tag, id)),
+                    tag_name: x.tag_name,
+                    daily_amount: format_amount(&x.daily_amount.round(scale)),
+                })
+                .collect(),
+            shortfall_date,
+            unsupported_count,
+        }))
+    }
+}
+
+fn to_forecast_day(day: forecast::Day) -> ForecastDay {
+    ForecastDay {
+        date: format_date(day.date),
+        committed: format_amount(&day.committed),
+        estimated: format_amount(&day.estimated),
+        balance: format_amount(&day.balance),
+        committed_balance: format_amount(&day.committed_balance),
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::Date;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    amount::{exponent, parse_currency, parse_money},
+    dateutils::{format_date, parse_date, user_now},
+    idcodec::Namespace,
+    idl::installment::{
+        DeleteInstallmentPlanRequest, InstallmentPayment, InstallmentPlan, InstallmentPlanList,
+        NewInstallmentPlan, installment_server,
+    },
+    installment::{MAX_INSTALLMENTS, MIN_INSTALLMENTS, schedule},
+    ledger::{self, Role},
+    server::ServerState,
+    service::accounting::format_amount,
+};
+
+pub struct InstallmentApi {
+    state: Arc<ServerState>,
+}
+
+impl InstallmentApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    /// Plans of the ledger, or only the plan `plan_id`, with the latest purchases first. Installments
+    /// are due on dates in the time zone of the user
+    async fn load_plans(
+        &self,
+        ledger_id: i32,
+        user_id: i32,
+        plan_id: Option<i32>,
+    ) -> tonic::Result<Vec<InstallmentPlan>> {
+        let plans = match sqlx::query!(
+            r#"select installment_plans.id,
+       installment_plans.purchase_item_id,
+       coalesce(accounting_items.name, '') as "name!",
+       accounting_items.currency,
+       -accounting_items.amount as "principal!",
+       installment_plans.fee
+from installment_plans
+join accounting_items on accounting_items.id = installment_plans.purchase_item_id
+where installment_plans.ledger_id = $1 and ($2::integer is null or installment_plans.id = $2)
+order by accounting_items.occurred_at desc, installment_plans.id desc"#,
+            ledger_id,
+            plan_id,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "list installment plans", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let ids: Vec<i32> = plans.iter().map(|x| x.id).collect();
+        let installments = match sqlx::query!(
+            r#"select accounting_items.installment_plan_id as "plan_id!",
+       accounting_items.id,
+       (accounting_items.occurred_at at time zone users.time_zone)::date as "due!",
+       -accounting_items.amount as "amount!"
+from accounting_items
+join users on users.id = $2
+where accounting_items.installment_plan_id = any($1)
+order by accounting_items.occurred_at, accounting_items.id"#,
+            &ids,
+            user_id,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "list installments", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let today = match user_now(&self.state.database, user_id).await {
+            Ok(x) => x.date(),
+            Err(err) => {
+                error!(action = "get user time", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut plans: Vec<InstallmentPlan> = plans
+            .into_iter()
+            .map(|plan| {
+                let mut remaining = BigDecimal::zero();
+                let mut remaining_count = 0;
+                let mut next_due: Option<Date> = None;
+                let installments = installments
+                    .iter()
+                    .filter(|x| x.plan_id == plan.id)
+                    .enumerate()
+                    .map(|(i, x)| {
+                        let paid = x.due <= today;
+                        if !paid {
+                            remaining += &x.amount;
+                            remaining_count += 1;
+                            next_due.get_or_insert(x.due);
+                        }
+                        InstallmentPayment {
+                            item_id: self.state.ids.encode(Namespace::Item, x.id),
+                            number: i as u32 + 1,
+                            due: format_date(x.due),
+                            amount: format_amount(&x.amount),
+                            paid,
+                        }
+                    })
+                    .collect();
+                InstallmentPlan {
+                    id: self.state.ids.encode(Namespace::InstallmentPlan, plan.id),
+                    item_id: self
+                        .state
+                        .ids
+                        .encode(Namespace::Item, plan.purchase_item_id),
+                    name: plan.name,
+                    currency: plan.currency,
+                    principal: format_amount(&plan.principal),
+                    fee: format_amount(&plan.fee),
+                    installments,
+                    remaining: format_amount(&remaining),
+                    remaining_count,
+                    next_due: next_due.map(format_date),
+                }
+            })
+            .collect();
+        // plans still being paid first
+        plans.sort_by_key(|x| x.remaining_count == 0);
+        Ok(plans)
+    }
+}
+
+#[tonic::async_trait]
+impl installment_server::Installment for InstallmentApi {
+    async fn create_installment_plan(
+        &self,
+        request: Request<NewInstallmentPlan>,
+    ) -> tonic::Result<Response<InstallmentPlan>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewInstallmentPlan {
+            item_id,
+            installments,
+            first_due,
+            fee,
+        } = request.into_inner();
+        let Some(item_id) = self.state.ids.decode(Namespace::Item, &item_id) else {
+            return Err(Status::invalid_argument("bad item id"));
+        };
+        if !(MIN_INSTALLMENTS..=MAX_INSTALLMENTS).contains(&installments) {
+            return Err(Status::invalid_argument(format!(
+                "installments must be from {MIN_INSTALLMENTS} to {MAX_INSTALLMENTS}"
+            )));
+        }
+        let Some(first_due) = parse_date(&first_due) else {
+            return Err(Status::invalid_argument("bad first_due"));
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let purchase = match sqlx::query!(
+            r#"select accounting_items.name, accounting_items.amount, accounting_items.currency, accounting_items.payee_id
+from accounting_items
+where accounting_items.ledger_id = $1
+      and accounting_items.id = $2
+      and accounting_items.installment_plan_id is null
+for update"#,
+            access.ledger_id,
+            item_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(Status::not_found("item not found")),
+            Err(err) => {
+                error!(action = "get installment purchase", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        if purchase.amount >= BigDecimal::zero() {
+            return Err(Status::invalid_argument("the item isn't an expense"));
+        }
+        let fee = match fee.filter(|x| !x.is_empty()) {
+            Some(fee) => parse_money("fee", &fee, "currency", &purchase.currency)?.0,
+            None => BigDecimal::zero(),
+        };
+        let scale = match parse_currency(&purchase.currency) {
+            Ok(currency) => i64::from(exponent(currency)),
+            Err(_) => return Err(Status::failed_precondition("unknown currency of the item")),
+        };
+        let plan_id = match sqlx::query_scalar!(
+            "insert into installment_plans (ledger_id, purchase_item_id, installments, first_due, fee)
+values ($1, $2, $3, $4, $5)
+on conflict (purchase_item_id) do nothing
+returning installment_plans.id",
+            access.ledger_id,
+            item_id,
+            installments as i16,
+            first_due,
+            fee,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                return Err(Status::already_exists(
+                    "the item already has an installment plan",
+                ));
+            }
+            Err(err) => {
+                error!(action = "create installment plan", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let total = -&purchase.amount + &fee;
+        let schedule = schedule(&total, scale, installments, first_due);
+        let name = purchase.name.unwrap_or_default();
+        let names: Vec<String> = schedule
+            .iter()
+            .map(|x| {
+                format!("{name} ({}/{installments})", x.number)
+                    .trim_start()
+                    .to_string()
+            })
+            .collect();
+        let amounts: Vec<BigDecimal> = schedule.iter().map(|x| -&x.amount).collect();
+        let dues: Vec<Date> = schedule.iter().map(|x| x.due).collect();
+        let generated = async {
+            sqlx::query!(
+                "insert into accounting_items (ledger_id, user_id, name, amount, currency, payee_id, occurred_at, installment_plan_id)
+select $1, $2, installments.name, installments.amount, $3, $4, installments.due::timestamp at time zone users.time_zone, $5
+from unnest($6::text[], $7::numeric[], $8::date[]) as installments(name, amount, due)
+join users on users.id = $2",
+                access.ledger_id,
+                access.user_id,
+                purchase.currency,
+                purchase.payee_id,
+                plan_id,
+                &names,
+                &amounts,
+                &dues,
+            )
+            .execute(&mut *tx)
+            .await?;
+            // installments are tagged like the purchase, so tag summaries hold either way
+            sqlx::query!(
+                "insert into accounting_item_tags (accounting_item_id, tag_id)
+select accounting_items.id, accounting_item_tags.tag_id
+from accounting_items, accounting_item_tags
+where accounting_items.installment_plan_id = $1 and accounting_item_tags.accounting_item_id = $2",
+                plan_id,
+                item_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(err) = generated {
+            error!(action = "generate installments", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        let mut plans = self
+            .load_plans(access.ledger_id, access.user_id, Some(plan_id))
+            .await?;
+        match plans.pop() {
+            Some(plan) => Ok(Response::new(plan)),
+            None => Err(Status::internal(String::new())),
+        }
+    }
+
+    async fn list_installment_plans(
+        &self,
+        request: Request<()>,
+    ) -> tonic::Result<Response<InstallmentPlanList>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let plans = self
+            .load_plans(access.ledger_id, access.user_id, None)
+            .await?;
+        Ok(Response::new(InstallmentPlanList { plans }))
+    }
+
+    async fn delete_installment_plan(
+        &self,
+        request: Request<DeleteInstallmentPlanRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let access = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let DeleteInstallmentPlanRequest { id } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::InstallmentPlan, &id) else {
+            return Err(Status::invalid_argument("bad installment plan id"));
+        };
+        match sqlx::query!(
+            "delete from installment_plans where installment_plans.ledger_id = $1 and installment_plans.id = $2",
+            access.ledger_id,
+            id,
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            Ok(x) if x.rows_affected() == 0 => Err(Status::not_found("installment plan not found")),
+            Ok(_) => Ok(Response::new(())),
+            Err(err) => {
+                error!(action = "delete installment plan", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+}
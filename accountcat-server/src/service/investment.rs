@@ -0,0 +1,380 @@
+use std::{collections::HashMap, sync::Arc};
+
+use iso_currency::Currency;
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::OffsetDateTime;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    amount::{bad_request, parse_amount, parse_currency},
+    dateutils::format_date,
+    idcodec::Namespace,
+    idl::investment::{
+        DividendIncome, Holding, HoldingList, NewTransaction, Transaction, TransactionKind,
+        YearlyDividend, investment_server,
+    },
+    investment::holding::{self, Kind, replay},
+    ledger::{self, Role},
+    protobufutils::{from_proto_timestamp, to_proto_timestamp},
+    server::ServerState,
+    service::accounting::format_amount,
+};
+
+pub struct InvestmentApi {
+    state: Arc<ServerState>,
+}
+
+impl InvestmentApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+}
+
+struct TransactionRecord {
+    symbol: String,
+    currency: String,
+    kind: String,
+    quantity: Option<BigDecimal>,
+    amount: BigDecimal,
+    fee: BigDecimal,
+    year: i32,
+    occurred_at: OffsetDateTime,
+}
+
+impl TransactionRecord {
+    fn to_transaction(&self) -> tonic::Result<holding::Transaction> {
+        let Ok(kind) = self.kind.parse() else {
+            error!(
+                action = "parse investment transaction kind",
+                kind = self.kind
+            );
+            return Err(Status::internal(String::new()));
+        };
+        Ok(holding::Transaction {
+            kind,
+            quantity: self.quantity.clone().unwrap_or_default(),
+            amount: self.amount.clone(),
+            fee: self.fee.clone(),
+            year: self.year,
+        })
+    }
+}
+
+async fn load_transactions(
+    database: &sqlx::PgPool,
+    ledger_id: i32,
+    user_id: i32,
+    symbol: Option<&str>,
+) -> tonic::Result<Vec<TransactionRecord>> {
+    match sqlx::query_as!(
+        TransactionRecord,
+        r#"select investment_transactions.symbol,
+       investment_transactions.currency,
+       investment_transactions.kind,
+       investment_transactions.quantity,
+       investment_transactions.amount,
+       investment_transactions.fee,
+       extract(year from investment_transactions.occurred_at at time zone users.time_zone)::integer as "year!",
+       investment_transactions.occurred_at
+from investment_transactions
+join users on users.id = $3
+where investment_transactions.ledger_id = $1
+      and ($2::text is null or investment_transactions.symbol = $2)
+order by investment_transactions.symbol,
+         investment_transactions.currency,
+         investment_transactions.occurred_at,
+         investment_transactions.id"#,
+        ledger_id,
+        symbol,
+        user_id,
+    )
+    .fetch_all(database)
+    .await
+    {
+        Ok(x) => Ok(x),
+        Err(err) => {
+            error!(action = "load investment transactions", error = ?err);
+            Err(Status::internal(String::new()))
+        }
+    }
+}
+
+fn kind_from_proto(kind: TransactionKind) -> Kind {
+    match kind {
+        TransactionKind::Buy => Kind::Buy,
+        TransactionKind::Sell => Kind::Sell,
+        TransactionKind::Dividend => Kind::Dividend,
+    }
+}
+
+fn parse_non_negative(value: &str, field: &str, currency: Currency) -> tonic::Result<BigDecimal> {
+    if value.is_empty() {
+        return Ok(BigDecimal::zero());
+    }
+    parse_amount(value, currency).map_err(|err| bad_request(vec![(field, err)]))
+}
+
+#[tonic::async_trait]
+impl investment_server::Investment for InvestmentApi {
+    async fn record_transaction(
+        &self,
+        request: Request<NewTransaction>,
+    ) -> tonic::Result<Response<Transaction>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewTransaction {
+            symbol,
+            kind,
+            quantity,
+            amount,
+            fee,
+            currency,
+            occurred_at,
+            account_id,
+        } = request.into_inner();
+        let symbol = symbol.trim().to_uppercase();
+        if symbol.is_empty() {
+            return Err(Status::invalid_argument("missing symbol"));
+        }
+        let Ok(proto_kind) = TransactionKind::try_from(kind) else {
+            return Err(Status::invalid_argument("bad kind"));
+        };
+        let kind = kind_from_proto(proto_kind);
+        let quantity = match kind {
+            Kind::Dividend => None,
+            _ => match quantity.parse::<BigDecimal>() {
+                Ok(x) if x > BigDecimal::zero() => Some(x),
+                _ => {
+                    return Err(Status::invalid_argument(
+                        "quantity must be a positive number",
+                    ));
+                }
+            },
+        };
+        let parsed_currency =
+            parse_currency(&currency).map_err(|err| bad_request(vec![("currency", err)]))?;
+        let amount = parse_non_negative(&amount, "amount", parsed_currency)?;
+        let fee = parse_non_negative(&fee, "fee", parsed_currency)?;
+        let occurred_at = match occurred_at {
+            Some(x) => {
+                from_proto_timestamp(x).map_err(|_| Status::invalid_argument("bad occurred_at"))?
+            }
+            None => OffsetDateTime::now_utc(),
+        };
+        let account_id = match account_id.filter(|x| !x.is_empty()) {
+            Some(id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Account, &id)
+                    .ok_or_else(|| Status::invalid_argument("bad account id"))?,
+            ),
+            None => None,
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        if kind == Kind::Sell {
+            // a sale can't be backdated to before the units were bought
+            let records = load_transactions(
+                &self.state.database,
+                ledger.ledger_id,
+                ledger.user_id,
+                Some(&symbol),
+            )
+            .await?
+            .into_iter()
+            .filter(|x| x.currency == currency)
+            .collect::<Vec<_>>();
+            let earlier = records
+                .iter()
+                .take_while(|x| x.occurred_at <= occurred_at)
+                .map(|x| x.to_transaction())
+                .collect::<tonic::Result<Vec<_>>>()?;
+            let later = records
+                .iter()
+                .skip(earlier.len())
+                .map(|x| x.to_transaction())
+                .collect::<tonic::Result<Vec<_>>>()?;
+            let sale = holding::Transaction {
+                kind,
+                quantity: quantity.clone().unwrap_or_default(),
+                amount: amount.clone(),
+                fee: fee.clone(),
+                year: occurred_at.year(),
+            };
+            if let Err(err) = replay(earlier.iter().chain([&sale]).chain(later.iter())) {
+                return Err(Status::failed_precondition(err.to_string()));
+            }
+        }
+        let (item_name, item_amount) = match kind {
+            Kind::Buy => (format!("Buy {symbol}"), -(&amount + &fee)),
+            Kind::Sell => (format!("Sell {symbol}"), &amount - &fee),
+            Kind::Dividend => (format!("Dividend {symbol}"), &amount - &fee),
+        };
+        let item = match sqlx::query!(
+            "insert into accounting_items (ledger_id, user_id, name, amount, currency, occurred_at, account_id)
+values ($1, $2, $3, $4, $5, $6, (select accounts.id from accounts where accounts.ledger_id = $1 and accounts.id = $7))
+returning accounting_items.id, accounting_items.account_id",
+            ledger.ledger_id,
+            ledger.user_id,
+            item_name,
+            item_amount,
+            currency,
+            occurred_at,
+            account_id,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "insert investment item", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        if account_id.is_some() && item.account_id.is_none() {
+            return Err(Status::invalid_argument("account not found"));
+        }
+        let id = match sqlx::query_scalar!(
+            "insert into investment_transactions (ledger_id, symbol, kind, quantity, amount, fee, currency, accounting_item_id, occurred_at)
+values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+returning investment_transactions.id",
+            ledger.ledger_id,
+            symbol,
+            kind.as_str(),
+            quantity,
+            amount,
+            fee,
+            currency,
+            item.id,
+            occurred_at,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(id) => id,
+            Err(err) => {
+                error!(action = "insert investment transaction", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(Transaction {
+            id: self.state.ids.encode(Namespace::InvestmentTransaction, id),
+            symbol,
+            kind: proto_kind as i32,
+            quantity: quantity.as_ref().map(format_amount),
+            amount: format_amount(&amount),
+            fee: format_amount(&fee),
+            currency,
+            occurred_at: Some(to_proto_timestamp(occurred_at)),
+            item_id: self.state.ids.encode(Namespace::Item, item.id),
+        }))
+    }
+
+    async fn list_holdings(&self, request: Request<()>) -> tonic::Result<Response<HoldingList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let records =
+            load_transactions(&self.state.database, ledger.ledger_id, ledger.user_id, None).await?;
+        // records are ordered by symbol and currency, so each holding is a contiguous run
+        let mut positions: Vec<(String, String, Vec<holding::Transaction>)> = Vec::new();
+        for record in records.iter() {
+            let transaction = record.to_transaction()?;
+            match positions.last_mut() {
+                Some((symbol, currency, transactions))
+                    if *symbol == record.symbol && *currency == record.currency =>
+                {
+                    transactions.push(transaction)
+                }
+                _ => positions.push((
+                    record.symbol.clone(),
+                    record.currency.clone(),
+                    vec![transaction],
+                )),
+            }
+        }
+        let symbols: Vec<String> = positions.iter().map(|(s, _, _)| s.clone()).collect();
+        let prices: HashMap<String, (time::Date, BigDecimal)> = match sqlx::query!(
+            "select distinct on (security_prices.symbol) security_prices.symbol, security_prices.date, security_prices.close
+from security_prices
+where security_prices.symbol = any($1)
+order by security_prices.symbol, security_prices.date desc",
+            &symbols[..],
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x
+                .into_iter()
+                .map(|r| (r.symbol, (r.date, r.close)))
+                .collect(),
+            Err(err) => {
+                error!(action = "load security prices", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut holdings = Vec::with_capacity(positions.len());
+        for (symbol, currency, transactions) in positions {
+            let position = match replay(transactions.iter()) {
+                Ok(x) => x,
+                Err(err) => {
+                    error!(action = "replay investment transactions", symbol = symbol, error = ?err);
+                    return Err(Status::internal(String::new()));
+                }
+            };
+            let price = prices.get(&symbol);
+            let market_value = price.map(|(_, close)| (&position.quantity * close).round(2));
+            holdings.push(Holding {
+                quantity: format_amount(&position.quantity),
+                cost_basis: format_amount(&position.cost_basis),
+                average_cost: position.average_cost().as_ref().map(format_amount),
+                price: price.map(|(_, close)| format_amount(close)),
+                price_date: price.map(|(date, _)| format_date(*date)),
+                unrealized_gain: market_value
+                    .as_ref()
+                    .map(|x| format_amount(&(x - &position.cost_basis))),
+                market_value: market_value.as_ref().map(format_amount),
+                realized_gain: format_amount(&position.realized_gain),
+                symbol,
+                currency,
+            });
+        }
+        Ok(Response::new(HoldingList { holdings }))
+    }
+
+    async fn get_dividend_income(
+        &self,
+        request: Request<()>,
+    ) -> tonic::Result<Response<DividendIncome>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        match sqlx::query!(
+            r#"select extract(year from investment_transactions.occurred_at at time zone users.time_zone)::integer as "year!",
+       investment_transactions.currency,
+       sum(investment_transactions.amount - investment_transactions.fee) as "amount!"
+from investment_transactions
+join users on users.id = $2
+where investment_transactions.ledger_id = $1 and investment_transactions.kind = 'dividend'
+group by 1, 2
+order by 1, 2"#,
+            ledger.ledger_id,
+            ledger.user_id,
+        )
+        .map(|r| YearlyDividend {
+            year: r.year,
+            currency: r.currency,
+            amount: format_amount(&r.amount),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(years) => Ok(Response::new(DividendIncome { years })),
+            Err(err) => {
+                error!(action = "get dividend income", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+}
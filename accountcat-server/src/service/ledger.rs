@@ -0,0 +1,390 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    auth::claims_from_request,
+    idcodec::Namespace,
+    idl::ledger::{
+        self, AcceptInvitationRequest, Invitation, LedgerList, Member, MemberList,
+        MemberListRequest, NewInvitation, NewLedger, RemoveMemberRequest, UpdateMemberRequest,
+        ledger_server,
+    },
+    ledger::{LedgerAccess, Role, authorize, ensure_personal_ledger},
+    protobufutils::to_proto_timestamp,
+    server::ServerState,
+};
+
+pub struct LedgerApi {
+    state: Arc<ServerState>,
+}
+
+impl LedgerApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    async fn authorize(
+        &self,
+        sub: &str,
+        ledger_id: &str,
+        required: Role,
+    ) -> tonic::Result<LedgerAccess> {
+        let Some(ledger_id) = self.state.ids.decode(Namespace::Ledger, ledger_id) else {
+            return Err(Status::invalid_argument("bad ledger id"));
+        };
+        authorize(&self.state.database, sub, Some(ledger_id), required).await
+    }
+}
+
+fn parse_role(role: i32) -> tonic::Result<Role> {
+    ledger::Role::try_from(role)
+        .map(Role::from)
+        .map_err(|_| Status::invalid_argument("bad role"))
+}
+
+fn role_from_record(role: &str) -> tonic::Result<Role> {
+    role.parse().map_err(|_| {
+        error!(action = "parse ledger role", role = role);
+        Status::internal(String::new())
+    })
+}
+
+const INVITATION_VALID_DAYS: i32 = 7;
+
+#[tonic::async_trait]
+impl ledger_server::Ledger for LedgerApi {
+    async fn list(&self, request: Request<()>) -> tonic::Result<Response<LedgerList>> {
+        let claims = claims_from_request(&request)?;
+        if let Err(err) = ensure_personal_ledger(&self.state.database, &claims.sub).await {
+            error!(action = "create personal ledger", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        let records = match sqlx::query!(
+            r#"select ledgers.id,
+       ledgers.name,
+       ledger_members.role,
+       coalesce(ledgers.personal_user_id = users.id, false) as "personal!"
+from ledger_members
+join ledgers on ledgers.id = ledger_members.ledger_id
+join users on users.id = ledger_members.user_id
+where users.google_sub = $1
+order by "personal!" desc, ledgers.name"#,
+            claims.sub
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "list ledgers", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut ledgers = Vec::with_capacity(records.len());
+        for r in records {
+            ledgers.push(ledger::LedgerInfo {
+                id: self.state.ids.encode(Namespace::Ledger, r.id),
+                name: r.name,
+                role: ledger::Role::from(role_from_record(&r.role)?).into(),
+                personal: r.personal,
+            });
+        }
+        Ok(Response::new(LedgerList { ledgers }))
+    }
+
+    async fn create(
+        &self,
+        request: Request<NewLedger>,
+    ) -> tonic::Result<Response<ledger::LedgerInfo>> {
+        let claims = claims_from_request(&request)?;
+        let NewLedger { name } = request.into_inner();
+        if name.trim().is_empty() {
+            return Err(Status::invalid_argument("missing name"));
+        }
+        let record = match sqlx::query!(
+            "with ledger as (
+    insert into ledgers (name) values ($1) returning ledgers.id
+)
+insert into ledger_members (ledger_id, user_id, role)
+select ledger.id, users.id, 'owner'
+from ledger, users
+where users.google_sub = $2
+returning ledger_members.ledger_id",
+            name,
+            claims.sub
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "create ledger", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(ledger::LedgerInfo {
+            id: self.state.ids.encode(Namespace::Ledger, record.ledger_id),
+            name,
+            role: ledger::Role::Owner.into(),
+            personal: false,
+        }))
+    }
+
+    async fn create_invitation(
+        &self,
+        request: Request<NewInvitation>,
+    ) -> tonic::Result<Response<Invitation>> {
+        let claims = claims_from_request(&request)?;
+        let NewInvitation { ledger_id, role } = request.into_inner();
+        let access = self.authorize(&claims.sub, &ledger_id, Role::Owner).await?;
+        let role = parse_role(role)?;
+        let token = Uuid::new_v4();
+        match sqlx::query!(
+            "insert into ledger_invitations (ledger_id, token, role, created_by, expires_at)
+values ($1, $2, $3, $4, now() + make_interval(days => $5))
+returning ledger_invitations.expires_at",
+            access.ledger_id,
+            token,
+            role.as_str(),
+            access.user_id,
+            INVITATION_VALID_DAYS,
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(record) => Ok(Response::new(Invitation {
+                token: token.to_string(),
+                expires_at: Some(to_proto_timestamp(record.expires_at)),
+            })),
+            Err(err) => {
+                error!(action = "create ledger invitation", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn accept_invitation(
+        &self,
+        request: Request<AcceptInvitationRequest>,
+    ) -> tonic::Result<Response<ledger::LedgerInfo>> {
+        let claims = claims_from_request(&request)?;
+        let AcceptInvitationRequest { token } = request.into_inner();
+        let Ok(token) = token.parse::<Uuid>() else {
+            return Err(Status::invalid_argument("bad token"));
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let invitation = match sqlx::query!(
+            "update ledger_invitations
+set accepted_by = users.id, accepted_at = now()
+from users
+where users.google_sub = $2
+      and ledger_invitations.token = $1
+      and ledger_invitations.accepted_at is null
+      and ledger_invitations.expires_at > now()
+returning ledger_invitations.ledger_id, ledger_invitations.role, users.id as user_id",
+            token,
+            claims.sub
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(Status::not_found("invitation not found or expired")),
+            Err(err) => {
+                error!(action = "accept ledger invitation", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        // joining again doesn't change the role of an existing member
+        let ledger = match sqlx::query!(
+            "with member as (
+    insert into ledger_members (ledger_id, user_id, role)
+    values ($1, $2, $3)
+    on conflict (ledger_id, user_id) do nothing
+)
+select ledgers.id, ledgers.name, coalesce(ledgers.personal_user_id = $2, false) as \"personal!\"
+from ledgers
+where ledgers.id = $1",
+            invitation.ledger_id,
+            invitation.user_id,
+            invitation.role,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "join ledger", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        let access = authorize(
+            &self.state.database,
+            &claims.sub,
+            Some(ledger.id),
+            Role::Viewer,
+        )
+        .await?;
+        Ok(Response::new(ledger::LedgerInfo {
+            id: self.state.ids.encode(Namespace::Ledger, ledger.id),
+            name: ledger.name,
+            role: ledger::Role::from(access.role).into(),
+            personal: ledger.personal,
+        }))
+    }
+
+    async fn list_members(
+        &self,
+        request: Request<MemberListRequest>,
+    ) -> tonic::Result<Response<MemberList>> {
+        let claims = claims_from_request(&request)?;
+        let MemberListRequest { ledger_id } = request.into_inner();
+        let access = self
+            .authorize(&claims.sub, &ledger_id, Role::Viewer)
+            .await?;
+        let records = match sqlx::query!(
+            "select ledger_members.user_id, ledger_members.role, users.name
+from ledger_members
+join users on users.id = ledger_members.user_id
+where ledger_members.ledger_id = $1
+order by ledger_members.created_at",
+            access.ledger_id
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "list ledger members", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut members = Vec::with_capacity(records.len());
+        for r in records {
+            members.push(Member {
+                user_id: self.state.ids.encode(Namespace::User, r.user_id),
+                name: r.name.unwrap_or_default(),
+                role: ledger::Role::from(role_from_record(&r.role)?).into(),
+            });
+        }
+        Ok(Response::new(MemberList { members }))
+    }
+
+    async fn update_member(
+        &self,
+        request: Request<UpdateMemberRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let claims = claims_from_request(&request)?;
+        let UpdateMemberRequest {
+            ledger_id,
+            user_id,
+            role,
+        } = request.into_inner();
+        let access = self.authorize(&claims.sub, &ledger_id, Role::Owner).await?;
+        let role = parse_role(role)?;
+        let Some(user_id) = self.state.ids.decode(Namespace::User, &user_id) else {
+            return Err(Status::invalid_argument("bad user id"));
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        if let Err(err) = sqlx::query!(
+            "update ledger_members
+set role = $3
+where ledger_members.ledger_id = $1 and ledger_members.user_id = $2",
+            access.ledger_id,
+            user_id,
+            role.as_str(),
+        )
+        .execute(&mut *tx)
+        .await
+        {
+            error!(action = "update ledger member", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        check_ownership(&mut tx, access.ledger_id).await?;
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(()))
+    }
+
+    async fn remove_member(
+        &self,
+        request: Request<RemoveMemberRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let claims = claims_from_request(&request)?;
+        let RemoveMemberRequest { ledger_id, user_id } = request.into_inner();
+        let Some(user_id) = self.state.ids.decode(Namespace::User, &user_id) else {
+            return Err(Status::invalid_argument("bad user id"));
+        };
+        let access = self
+            .authorize(&claims.sub, &ledger_id, Role::Viewer)
+            .await?;
+        // anyone can leave a ledger, but only owners can remove others
+        if access.user_id != user_id && access.role < Role::Owner {
+            return Err(Status::permission_denied(format!(
+                "requires {} role of the ledger",
+                Role::Owner.as_str()
+            )));
+        }
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        if let Err(err) = sqlx::query!(
+            "delete from ledger_members
+where ledger_members.ledger_id = $1 and ledger_members.user_id = $2",
+            access.ledger_id,
+            user_id,
+        )
+        .execute(&mut *tx)
+        .await
+        {
+            error!(action = "remove ledger member", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        check_ownership(&mut tx, access.ledger_id).await?;
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(()))
+    }
+}
+
+/// Ensure the ledger still has an owner, and that the owner of a personal ledger stays its owner
+async fn check_ownership(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ledger_id: i32,
+) -> tonic::Result<()> {
+    match sqlx::query!(
+        r#"select count(*) filter (where ledger_members.role = 'owner') as "owners!",
+       count(*) filter (where ledger_members.user_id = ledgers.personal_user_id and ledger_members.role = 'owner') as "personal_owner!",
+       ledgers.personal_user_id is not null as "personal!"
+from ledgers
+left join ledger_members on ledger_members.ledger_id = ledgers.id
+where ledgers.id = $1
+group by ledgers.id"#,
+        ledger_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    {
+        Ok(r) if r.owners == 0 => Err(Status::failed_precondition("a ledger needs an owner")),
+        Ok(r) if r.personal && r.personal_owner == 0 => Err(Status::failed_precondition(
+            "the owner of a personal ledger can't be changed",
+        )),
+        Ok(_) => Ok(()),
+        Err(err) => {
+            error!(action = "check ledger ownership", error = ?err);
+            Err(Status::internal(String::new()))
+        }
+    }
+}
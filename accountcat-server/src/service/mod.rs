@@ -1,4 +1,13 @@
 pub mod accounting;
+pub mod alert;
+pub mod data_import;
+pub mod forecast;
+pub mod installment;
 pub mod instance_setting;
+pub mod investment;
+pub mod ledger;
+pub mod net_worth;
+pub mod savings;
+pub mod split;
 pub mod todolist;
 pub mod user;
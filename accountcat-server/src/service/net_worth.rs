@@ -0,0 +1,602 @@
+use std::sync::Arc;
+
+use num_traits::Zero;
+use sqlx::types::BigDecimal;
+use time::{Date, Duration};
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    amount::{bad_request, parse_currency},
+    billing::{Cycle, cycle_containing, valid_day},
+    dateutils::{format_date, parse_date},
+    idcodec::Namespace,
+    idl::net_worth::{
+        Account, AccountKind, AccountList, NetWorthHistory, NetWorthHistoryRequest, NetWorthPoint,
+        NewAccount, NewSnapshot, ReconcileRequest, Snapshot, Statement, StatementItem,
+        StatementRequest, net_worth_server,
+    },
+    ledger::{self, Role},
+    protobufutils::to_proto_timestamp,
+    server::ServerState,
+    service::accounting::format_amount,
+};
+
+struct CardAccount {
+    currency: String,
+    opening_balance: BigDecimal,
+    closing_day: Option<i16>,
+    due_day: Option<i16>,
+    /// of the user viewing the account, which dates of the items are in
+    time_zone: String,
+    today: Date,
+}
+
+pub struct NetWorthApi {
+    state: Arc<ServerState>,
+}
+
+impl NetWorthApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    /// Find a card account and the billing cycle containing `date`, or today of the user when not
+    /// given
+    async fn find_cycle(
+        &self,
+        ledger_id: i32,
+        user_id: i32,
+        account_id: i32,
+        date: Option<Date>,
+    ) -> tonic::Result<(CardAccount, Cycle)> {
+        let account = match sqlx::query_as!(
+            CardAccount,
+            r#"select accounts.currency,
+       accounts.opening_balance,
+       accounts.closing_day,
+       accounts.due_day,
+       users.time_zone,
+       (now() at time zone users.time_zone)::date as "today!"
+from accounts
+join users on users.id = $3
+where accounts.ledger_id = $1 and accounts.id = $2"#,
+            ledger_id,
+            account_id,
+            user_id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(Status::not_found("account not found")),
+            Err(err) => {
+                error!(action = "find card account", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let (Some(closing_day), Some(due_day)) = (account.closing_day, account.due_day) else {
+            return Err(Status::failed_precondition(
+                "the account doesn't have a billing cycle",
+            ));
+        };
+        let date = date.unwrap_or(account.today);
+        let cycle = cycle_containing(date, closing_day as u8, due_day as u8);
+        Ok((account, cycle))
+    }
+
+    async fn load_statement(
+        &self,
+        account_id: i32,
+        account: CardAccount,
+        cycle: Cycle,
+    ) -> sqlx::Result<Statement> {
+        let previous_closing = cycle.start - Duration::days(1);
+        // the previous cycle as the bank reported it, otherwise derived from the items
+        let previous_balance = sqlx::query_scalar!(
+            r#"select coalesce(
+    (select card_statements.statement_balance
+     from card_statements
+     where card_statements.account_id = $1 and card_statements.closing_date = $3),
+    $4 - coalesce((
+        select sum(accounting_items.amount)
+        from accounting_items
+        where accounting_items.account_id = $1
+              and accounting_items.currency = $2
+              and (accounting_items.occurred_at at time zone $5)::date <= $3
+    ), 0)
+) as "balance!""#,
+            account_id,
+            account.currency,
+            previous_closing,
+            account.opening_balance,
+            account.time_zone,
+        )
+        .fetch_one(&self.state.database)
+        .await?;
+        let reconciled = sqlx::query!(
+            "select card_statements.id, card_statements.statement_balance
+from card_statements
+where card_statements.account_id = $1 and card_statements.closing_date = $2",
+            account_id,
+            cycle.closing,
+        )
+        .fetch_optional(&self.state.database)
+        .await?;
+        let records = sqlx::query!(
+            "select accounting_items.id,
+       accounting_items.name,
+       accounting_items.occurred_at,
+       accounting_items.amount,
+       accounting_items.card_statement_id
+from accounting_items
+where accounting_items.account_id = $1
+      and accounting_items.currency = $2
+      and (accounting_items.occurred_at at time zone $5)::date between $3 and $4
+order by accounting_items.occurred_at, accounting_items.id",
+            account_id,
+            account.currency,
+            cycle.start,
+            cycle.closing,
+            account.time_zone,
+        )
+        .fetch_all(&self.state.database)
+        .await?;
+        let mut charges = BigDecimal::zero();
+        let mut credits = BigDecimal::zero();
+        let mut cleared_total = BigDecimal::zero();
+        let mut items = Vec::with_capacity(records.len());
+        for r in records {
+            let cleared = reconciled
+                .as_ref()
+                .is_some_and(|statement| r.card_statement_id == Some(statement.id));
+            if r.amount < BigDecimal::zero() {
+                charges -= &r.amount;
+            } else {
+                credits += &r.amount;
+            }
+            if cleared {
+                cleared_total -= &r.amount;
+            }
+            items.push(StatementItem {
+                item_id: self.state.ids.encode(Namespace::Item, r.id),
+                name: r.name.unwrap_or_default(),
+                occurred_at: Some(to_proto_timestamp(r.occurred_at)),
+                amount: format_amount(&r.amount),
+                cleared,
+            });
+        }
+        let balance = &previous_balance + &charges - &credits;
+        let payment_due = if balance > BigDecimal::zero() {
+            balance.clone()
+        } else {
+            BigDecimal::zero()
+        };
+        let discrepancy = reconciled.as_ref().map(|statement| {
+            format_amount(&(&statement.statement_balance - &previous_balance - &cleared_total))
+        });
+        Ok(Statement {
+            account_id: self.state.ids.encode(Namespace::Account, account_id),
+            currency: account.currency,
+            start_date: format_date(cycle.start),
+            closing_date: format_date(cycle.closing),
+            due_date: format_date(cycle.due),
+            previous_balance: format_amount(&previous_balance),
+            charges: format_amount(&charges),
+            credits: format_amount(&credits),
+            balance: format_amount(&balance),
+            payment_due: format_amount(&payment_due),
+            items,
+            reconciled: reconciled.is_some(),
+            statement_balance: reconciled
+                .as_ref()
+                .map(|statement| format_amount(&statement.statement_balance)),
+            discrepancy,
+        })
+    }
+}
+
+fn kind_from_record(kind: &str) -> AccountKind {
+    match kind {
+        "liability" => AccountKind::Liability,
+        _ => AccountKind::Asset,
+    }
+}
+
+fn kind_to_record(kind: AccountKind) -> &'static str {
+    match kind {
+        AccountKind::Asset => "asset",
+        AccountKind::Liability => "liability",
+    }
+}
+
+fn parse_optional_date(date: Option<String>, field: &str) -> tonic::Result<Option<Date>> {
+    match date.filter(|x| !x.is_empty()) {
+        Some(date) => parse_date(&date)
+            .map(Some)
+            .ok_or_else(|| Status::invalid_argument(format!("bad {field}"))),
+        None => Ok(None),
+    }
+}
+
+#[tonic::async_trait]
+impl net_worth_server::NetWorth for NetWorthApi {
+    async fn create_account(
+        &self,
+        request: Request<NewAccount>,
+    ) -> tonic::Result<Response<Account>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewAccount {
+            name,
+            kind,
+            currency,
+            opening_balance,
+            closing_day,
+            due_day,
+        } = request.into_inner();
+        if name.trim().is_empty() {
+            return Err(Status::invalid_argument("missing name"));
+        }
+        let Ok(kind) = AccountKind::try_from(kind) else {
+            return Err(Status::invalid_argument("bad kind"));
+        };
+        if let Err(err) = parse_currency(&currency) {
+            return Err(bad_request(vec![("currency", err)]));
+        }
+        let opening_balance = if opening_balance.is_empty() {
+            BigDecimal::from(0)
+        } else {
+            opening_balance
+                .parse::<BigDecimal>()
+                .map_err(|_| Status::invalid_argument("opening balance isn't numeric"))?
+        };
+        match (closing_day, due_day) {
+            (None, None) => {}
+            (Some(closing_day), Some(due_day))
+                if kind == AccountKind::Liability
+                    && valid_day(closing_day)
+                    && valid_day(due_day) => {}
+            _ => {
+                return Err(Status::invalid_argument(
+                    "closing day and due day must be days of month of a liability",
+                ));
+            }
+        }
+        match sqlx::query!(
+            "insert into accounts (ledger_id, name, kind, currency, opening_balance, closing_day, due_day)
+values ($1, $2, $3, $4, $5, $6, $7)
+on conflict (ledger_id, name) do nothing
+returning accounts.id, accounts.opening_balance",
+            ledger.ledger_id,
+            name,
+            kind_to_record(kind),
+            currency,
+            opening_balance,
+            closing_day.map(|x| x as i16),
+            due_day.map(|x| x as i16),
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(r)) => Ok(Response::new(Account {
+                id: self.state.ids.encode(Namespace::Account, r.id),
+                name,
+                kind: kind as i32,
+                currency,
+                opening_balance: format_amount(&r.opening_balance),
+                latest: None,
+                closing_day,
+                due_day,
+            })),
+            Ok(None) => Err(Status::already_exists("account already exists")),
+            Err(err) => {
+                error!(action = "create account", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn list_accounts(&self, request: Request<()>) -> tonic::Result<Response<AccountList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        match sqlx::query!(
+            r#"select accounts.id,
+       accounts.name,
+       accounts.kind,
+       accounts.currency,
+       accounts.opening_balance,
+       accounts.closing_day,
+       accounts.due_day,
+       latest.date as "date?",
+       latest.value as "value?",
+       latest.derived as "derived?"
+from accounts
+left join lateral (
+    select account_snapshots.date, account_snapshots.value, account_snapshots.derived
+    from account_snapshots
+    where account_snapshots.account_id = accounts.id
+    order by account_snapshots.date desc
+    limit 1
+) latest on true
+where accounts.ledger_id = $1
+order by accounts.kind, accounts.name"#,
+            ledger.ledger_id
+        )
+        .map(|r| Account {
+            id: self.state.ids.encode(Namespace::Account, r.id),
+            name: r.name,
+            kind: kind_from_record(&r.kind) as i32,
+            currency: r.currency,
+            opening_balance: format_amount(&r.opening_balance),
+            latest: match (r.date, r.value, r.derived) {
+                (Some(date), Some(value), Some(derived)) => Some(Snapshot {
+                    account_id: self.state.ids.encode(Namespace::Account, r.id),
+                    date: format_date(date),
+                    value: format_amount(&value),
+                    derived,
+                }),
+                _ => None,
+            },
+            closing_day: r.closing_day.map(i32::from),
+            due_day: r.due_day.map(i32::from),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(accounts) => Ok(Response::new(AccountList { accounts })),
+            Err(err) => {
+                error!(action = "list accounts", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn record_snapshot(
+        &self,
+        request: Request<NewSnapshot>,
+    ) -> tonic::Result<Response<Snapshot>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewSnapshot {
+            account_id,
+            date,
+            value,
+        } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Account, &account_id) else {
+            return Err(Status::invalid_argument("bad account id"));
+        };
+        let Some(date) = parse_date(&date) else {
+            return Err(Status::invalid_argument("bad date"));
+        };
+        let value = match value.filter(|x| !x.is_empty()) {
+            Some(value) => Some(
+                value
+                    .parse::<BigDecimal>()
+                    .map_err(|_| Status::invalid_argument("value isn't numeric"))?,
+            ),
+            None => None,
+        };
+        // liabilities grow with expenses paid by them, so their items count negatively
+        match sqlx::query!(
+            r#"insert into account_snapshots (account_id, date, value, derived)
+select accounts.id,
+       $3,
+       coalesce($4, accounts.opening_balance + (case when accounts.kind = 'liability' then -1 else 1 end) * coalesce((
+           select sum(accounting_items.amount)
+           from accounting_items
+           where accounting_items.account_id = accounts.id
+                 and accounting_items.currency = accounts.currency
+                 and (accounting_items.occurred_at at time zone users.time_zone)::date <= $3
+       ), 0)),
+       $4::numeric is null
+from accounts
+join users on users.id = $5
+where accounts.ledger_id = $1 and accounts.id = $2
+on conflict (account_id, date) do update set value = excluded.value, derived = excluded.derived
+returning account_snapshots.value, account_snapshots.derived"#,
+            ledger.ledger_id,
+            id,
+            date,
+            value,
+            ledger.user_id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(r)) => Ok(Response::new(Snapshot {
+                account_id,
+                date: format_date(date),
+                value: format_amount(&r.value),
+                derived: r.derived,
+            })),
+            Ok(None) => Err(Status::not_found("account not found")),
+            Err(err) => {
+                error!(action = "record account snapshot", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn get_net_worth_history(
+        &self,
+        request: Request<NetWorthHistoryRequest>,
+    ) -> tonic::Result<Response<NetWorthHistory>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let NetWorthHistoryRequest { since, until } = request.into_inner();
+        let since = parse_optional_date(since, "since")?;
+        let until = parse_optional_date(until, "until")?;
+        let currency = match sqlx::query_scalar!(
+            "select users.base_currency from users where users.id = $1",
+            ledger.user_id
+        )
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get base currency", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let points = match sqlx::query!(
+            r#"with dates as (
+    select distinct account_snapshots.date
+    from account_snapshots
+    join accounts on accounts.id = account_snapshots.account_id
+    where accounts.ledger_id = $1
+          and ($2::date is null or account_snapshots.date >= $2)
+          and ($3::date is null or account_snapshots.date <= $3)
+),
+latest as (
+    select dates.date, accounts.kind, accounts.currency, snapshot.value
+    from dates
+    cross join accounts
+    join lateral (
+        select account_snapshots.value
+        from account_snapshots
+        where account_snapshots.account_id = accounts.id and account_snapshots.date <= dates.date
+        order by account_snapshots.date desc
+        limit 1
+    ) snapshot on true
+    where accounts.ledger_id = $1
+)
+select latest.date as "date!",
+       coalesce(sum(latest.value) filter (where latest.kind = 'asset' and latest.currency = $4), 0) as "assets!",
+       coalesce(sum(latest.value) filter (where latest.kind = 'liability' and latest.currency = $4), 0) as "liabilities!",
+       count(*) filter (where latest.currency <> $4) as "unsupported_count!"
+from latest
+group by latest.date
+order by latest.date"#,
+            ledger.ledger_id,
+            since,
+            until,
+            currency,
+        )
+        .map(|r| NetWorthPoint {
+            date: format_date(r.date),
+            net_worth: format_amount(&(&r.assets - &r.liabilities)),
+            assets: format_amount(&r.assets),
+            liabilities: format_amount(&r.liabilities),
+            unsupported_count: r.unsupported_count,
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "get net worth history", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(NetWorthHistory { currency, points }))
+    }
+
+    async fn get_statement(
+        &self,
+        request: Request<StatementRequest>,
+    ) -> tonic::Result<Response<Statement>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let StatementRequest { account_id, date } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Account, &account_id) else {
+            return Err(Status::invalid_argument("bad account id"));
+        };
+        let date = parse_optional_date(date, "date")?;
+        let (account, cycle) = self
+            .find_cycle(ledger.ledger_id, ledger.user_id, id, date)
+            .await?;
+        match self.load_statement(id, account, cycle).await {
+            Ok(statement) => Ok(Response::new(statement)),
+            Err(err) => {
+                error!(action = "get card statement", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn reconcile_statement(
+        &self,
+        request: Request<ReconcileRequest>,
+    ) -> tonic::Result<Response<Statement>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let ReconcileRequest {
+            account_id,
+            date,
+            statement_balance,
+            item_ids,
+        } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Account, &account_id) else {
+            return Err(Status::invalid_argument("bad account id"));
+        };
+        let Some(date) = parse_date(&date) else {
+            return Err(Status::invalid_argument("bad date"));
+        };
+        let Ok(statement_balance) = statement_balance.parse::<BigDecimal>() else {
+            return Err(Status::invalid_argument("statement balance isn't numeric"));
+        };
+        let item_ids = if item_ids.is_empty() {
+            None
+        } else {
+            Some(
+                item_ids
+                    .iter()
+                    .map(|x| self.state.ids.decode(Namespace::Item, x))
+                    .collect::<Option<Vec<i32>>>()
+                    .ok_or_else(|| Status::invalid_argument("bad item id"))?,
+            )
+        };
+        let (account, cycle) = self
+            .find_cycle(ledger.ledger_id, ledger.user_id, id, Some(date))
+            .await?;
+        let reconcile = async || -> sqlx::Result<()> {
+            let mut tx = self.state.database.begin().await?;
+            let statement_id = sqlx::query_scalar!(
+                "insert into card_statements (account_id, closing_date, statement_balance)
+values ($1, $2, $3)
+on conflict (account_id, closing_date) do update
+set statement_balance = excluded.statement_balance, reconciled_at = now()
+returning card_statements.id",
+                id,
+                cycle.closing,
+                statement_balance.round(3),
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update accounting_items
+set card_statement_id = null,
+    status = case when status = 'reconciled' then 'cleared' else status end
+where card_statement_id = $1",
+                statement_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "update accounting_items
+set card_statement_id = $1, status = 'reconciled'
+where accounting_items.account_id = $2
+      and accounting_items.currency = $3
+      and (accounting_items.occurred_at at time zone $7)::date between $4 and $5
+      and ($6::integer[] is null or accounting_items.id = any($6))",
+                statement_id,
+                id,
+                account.currency,
+                cycle.start,
+                cycle.closing,
+                item_ids.as_deref(),
+                account.time_zone,
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        };
+        let statement = match reconcile().await {
+            Ok(()) => self.load_statement(id, account, cycle).await,
+            Err(err) => Err(err),
+        };
+        match statement {
+            Ok(statement) => Ok(Response::new(statement)),
+            Err(err) => {
+                error!(action = "reconcile card statement", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+}
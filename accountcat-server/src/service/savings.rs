@@ -0,0 +1,347 @@
+use std::{collections::HashMap, sync::Arc};
+
+use num_traits::{ToPrimitive, Zero};
+use sqlx::types::BigDecimal;
+use time::Date;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    amount::{exponent, parse_currency, parse_money},
+    dateutils::{format_date, parse_date},
+    idcodec::Namespace,
+    idl::savings::{
+        Contribution, DeleteGoalRequest, Goal, GoalList, MonthlyContribution, NewContribution,
+        NewGoal, savings_server,
+    },
+    ledger::{self, Role},
+    protobufutils::{from_proto_timestamp, to_proto_timestamp},
+    savings::required_monthly,
+    server::ServerState,
+    service::accounting::format_amount,
+};
+
+pub struct SavingsApi {
+    state: Arc<ServerState>,
+}
+
+impl SavingsApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    fn to_goal(&self, record: GoalRecord, history: Vec<MonthlyContribution>) -> Goal {
+        let remaining = &record.target_amount - &record.saved;
+        let scale = parse_currency(&record.currency).map(exponent).unwrap_or(2);
+        Goal {
+            id: self.state.ids.encode(Namespace::Goal, record.id),
+            name: record.name,
+            progress: (&record.saved / &record.target_amount)
+                .to_f64()
+                .unwrap_or_default(),
+            target_amount: format_amount(&record.target_amount),
+            currency: record.currency,
+            deadline: record.deadline.map(format_date),
+            tag_id: record
+                .tag_id
+                .map(|id| self.state.ids.encode(Namespace::Tag, id)),
+            saved: format_amount(&record.saved),
+            required_monthly: record.deadline.map(|deadline| {
+                format_amount(&required_monthly(
+                    &remaining,
+                    record.today,
+                    deadline,
+                    record.month_start_day as u8,
+                    scale.into(),
+                ))
+            }),
+            history,
+        }
+    }
+
+    async fn find_goals(
+        &self,
+        ledger_id: i32,
+        user_id: i32,
+        id: Option<i32>,
+    ) -> sqlx::Result<Vec<GoalRecord>> {
+        sqlx::query_as!(
+            GoalRecord,
+            r#"select savings_goals.id,
+       savings_goals.name,
+       savings_goals.target_amount,
+       savings_goals.currency,
+       savings_goals.deadline,
+       savings_goals.tag_id,
+       coalesce((select sum(savings_contributions.amount) from savings_contributions where savings_contributions.goal_id = savings_goals.id), 0)
+       + coalesce((
+           select -sum(accounting_items.amount)
+           from accounting_items
+           join accounting_item_tags on accounting_item_tags.accounting_item_id = accounting_items.id
+           where accounting_item_tags.tag_id = savings_goals.tag_id
+                 and accounting_items.ledger_id = savings_goals.ledger_id
+                 and accounting_items.currency = savings_goals.currency
+       ), 0) as "saved!",
+       (now() at time zone users.time_zone)::date as "today!",
+       users.month_start_day
+from savings_goals
+join users on users.id = $3
+where savings_goals.ledger_id = $1 and ($2::integer is null or savings_goals.id = $2)
+order by savings_goals.deadline nulls last, savings_goals.name"#,
+            ledger_id,
+            id,
+            user_id,
+        )
+        .fetch_all(&self.state.database)
+        .await
+    }
+}
+
+struct GoalRecord {
+    id: i32,
+    name: String,
+    target_amount: BigDecimal,
+    currency: String,
+    deadline: Option<Date>,
+    tag_id: Option<i32>,
+    saved: BigDecimal,
+    today: Date,
+    month_start_day: i16,
+}
+
+#[tonic::async_trait]
+impl savings_server::Savings for SavingsApi {
+    async fn create_goal(&self, request: Request<NewGoal>) -> tonic::Result<Response<Goal>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewGoal {
+            name,
+            target_amount,
+            currency,
+            deadline,
+            tag_id,
+        } = request.into_inner();
+        if name.trim().is_empty() {
+            return Err(Status::invalid_argument("missing name"));
+        }
+        let (target_amount, _) =
+            parse_money("target_amount", &target_amount, "currency", &currency)?;
+        if target_amount.is_zero() {
+            return Err(Status::invalid_argument(
+                "target amount must be a positive number",
+            ));
+        }
+        let deadline = match deadline.filter(|x| !x.is_empty()) {
+            Some(deadline) => Some(
+                parse_date(&deadline).ok_or_else(|| Status::invalid_argument("bad deadline"))?,
+            ),
+            None => None,
+        };
+        let tag_id = match tag_id.filter(|x| !x.is_empty()) {
+            Some(tag_id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::Tag, &tag_id)
+                    .ok_or_else(|| Status::invalid_argument("bad tag id"))?,
+            ),
+            None => None,
+        };
+        let id = match sqlx::query_scalar!(
+            "insert into savings_goals (ledger_id, name, target_amount, currency, deadline, tag_id)
+select $1, $2, $3, $4, $5, $6
+where $6::integer is null or exists (select 1 from tags where tags.ledger_id = $1 and tags.id = $6)
+returning savings_goals.id",
+            ledger.ledger_id,
+            name,
+            target_amount,
+            currency,
+            deadline,
+            tag_id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(id)) => id,
+            Ok(None) => return Err(Status::invalid_argument("tag not found")),
+            Err(err) => {
+                error!(action = "create savings goal", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        // items already tagged count towards the new goal
+        let record = match self
+            .find_goals(ledger.ledger_id, ledger.user_id, Some(id))
+            .await
+        {
+            Ok(mut x) if !x.is_empty() => x.remove(0),
+            Ok(_) => return Err(Status::internal(String::new())),
+            Err(err) => {
+                error!(action = "find savings goal", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(self.to_goal(record, Vec::new())))
+    }
+
+    async fn list_goals(&self, request: Request<()>) -> tonic::Result<Response<GoalList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let records = match self
+            .find_goals(ledger.ledger_id, ledger.user_id, None)
+            .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "list savings goals", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let history = match sqlx::query!(
+            r#"select funding.goal_id as "goal_id!",
+       to_char(period_month((funding.occurred_at at time zone users.time_zone)::date, users.month_start_day), 'YYYY-MM') as "month!",
+       sum(funding.amount) as "amount!"
+from (
+    select savings_contributions.goal_id, savings_contributions.amount, savings_contributions.occurred_at
+    from savings_contributions
+    join savings_goals on savings_goals.id = savings_contributions.goal_id
+    where savings_goals.ledger_id = $1
+    union all
+    select savings_goals.id, -accounting_items.amount, accounting_items.occurred_at
+    from savings_goals
+    join accounting_item_tags on accounting_item_tags.tag_id = savings_goals.tag_id
+    join accounting_items on accounting_items.id = accounting_item_tags.accounting_item_id
+    where savings_goals.ledger_id = $1
+          and accounting_items.ledger_id = savings_goals.ledger_id
+          and accounting_items.currency = savings_goals.currency
+) funding
+join users on users.id = $2
+group by 1, 2
+order by 1, 2"#,
+            ledger.ledger_id,
+            ledger.user_id,
+        )
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "list savings history", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let mut history_by_goal: HashMap<i32, Vec<MonthlyContribution>> = HashMap::new();
+        for r in history {
+            history_by_goal
+                .entry(r.goal_id)
+                .or_default()
+                .push(MonthlyContribution {
+                    month: r.month,
+                    amount: format_amount(&r.amount),
+                });
+        }
+        let goals = records
+            .into_iter()
+            .map(|record| {
+                let history = history_by_goal.remove(&record.id).unwrap_or_default();
+                self.to_goal(record, history)
+            })
+            .collect();
+        Ok(Response::new(GoalList { goals }))
+    }
+
+    async fn add_contribution(
+        &self,
+        request: Request<NewContribution>,
+    ) -> tonic::Result<Response<Contribution>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewContribution {
+            goal_id,
+            amount,
+            note,
+            occurred_at,
+        } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Goal, &goal_id) else {
+            return Err(Status::invalid_argument("bad goal id"));
+        };
+        let currency = match sqlx::query_scalar!(
+            "select savings_goals.currency from savings_goals where savings_goals.ledger_id = $1 and savings_goals.id = $2",
+            ledger.ledger_id,
+            id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(Status::not_found("goal not found")),
+            Err(err) => {
+                error!(action = "get savings goal currency", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        // withdrawals are negative
+        let (magnitude, withdrawal) = match amount.trim().strip_prefix('-') {
+            Some(x) => (x, true),
+            None => (amount.trim(), false),
+        };
+        let (amount, _) = parse_money("amount", magnitude, "currency", &currency)?;
+        if amount.is_zero() {
+            return Err(Status::invalid_argument("amount must be a non-zero number"));
+        }
+        let amount = if withdrawal { -amount } else { amount };
+        let occurred_at = match occurred_at {
+            Some(x) => Some(
+                from_proto_timestamp(x).map_err(|_| Status::invalid_argument("bad occurred_at"))?,
+            ),
+            None => None,
+        };
+        match sqlx::query!(
+            "insert into savings_contributions (goal_id, amount, note, occurred_at)
+select savings_goals.id, $3, $4, coalesce($5, now())
+from savings_goals
+where savings_goals.ledger_id = $1 and savings_goals.id = $2
+returning savings_contributions.id, savings_contributions.amount, savings_contributions.note, savings_contributions.occurred_at",
+            ledger.ledger_id,
+            id,
+            amount,
+            note.filter(|x| !x.is_empty()),
+            occurred_at,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(r)) => Ok(Response::new(Contribution {
+                id: self.state.ids.encode(Namespace::Contribution, r.id),
+                goal_id,
+                amount: format_amount(&r.amount),
+                note: r.note,
+                occurred_at: Some(to_proto_timestamp(r.occurred_at)),
+            })),
+            Ok(None) => Err(Status::not_found("goal not found")),
+            Err(err) => {
+                error!(action = "add savings contribution", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn delete_goal(
+        &self,
+        request: Request<DeleteGoalRequest>,
+    ) -> tonic::Result<Response<()>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let DeleteGoalRequest { id } = request.into_inner();
+        let Some(id) = self.state.ids.decode(Namespace::Goal, &id) else {
+            return Err(Status::invalid_argument("bad goal id"));
+        };
+        if let Err(err) = sqlx::query!(
+            "delete from savings_goals where savings_goals.ledger_id = $1 and savings_goals.id = $2",
+            ledger.ledger_id,
+            id
+        )
+        .execute(&self.state.database)
+        .await
+        {
+            error!(action = "delete savings goal", error = ?err);
+            return Err(Status::internal(String::new()));
+        }
+        Ok(Response::new(()))
+    }
+}
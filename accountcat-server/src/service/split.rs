@@ -0,0 +1,561 @@
+use std::{collections::HashSet, sync::Arc};
+
+use iso_currency::Currency;
+use num_traits::Zero;
+use sqlx::{PgExecutor, types::BigDecimal};
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::{
+    amount::{exponent, parse_money},
+    idcodec::Namespace,
+    idl::split::{
+        Balance, BalanceList, Contact, ContactList, NewContact, NewSettlement, NewSharedExpense,
+        Settlement, SettlementDirection, Share, SharedExpense, SharedExpenseList, SplitMethod,
+        split_server,
+    },
+    ledger::{self, LedgerAccess, Role},
+    protobufutils::{from_proto_timestamp, to_proto_timestamp},
+    server::ServerState,
+    service::accounting::format_amount,
+    split::{Method, split},
+};
+
+pub struct SplitApi {
+    state: Arc<ServerState>,
+}
+
+impl SplitApi {
+    pub fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    /// Decode a contact id. An empty id refers to the contact of the requesting user
+    fn contact_id(&self, id: &str, me: i32) -> tonic::Result<i32> {
+        if id.is_empty() {
+            return Ok(me);
+        }
+        self.state
+            .ids
+            .decode(Namespace::Contact, id)
+            .ok_or_else(|| Status::invalid_argument("bad contact id"))
+    }
+}
+
+/// Find the contact representing the user in the ledger, creating it on first use
+async fn ensure_own_contact<'e, E: PgExecutor<'e>>(
+    executor: E,
+    ledger: &LedgerAccess,
+) -> tonic::Result<i32> {
+    match sqlx::query_scalar!(
+        r#"with inserted as (
+    insert into contacts (ledger_id, name, user_id)
+    select $1, coalesce(users.name, 'Me'), users.id
+    from users
+    where users.id = $2
+    on conflict (ledger_id, user_id) do nothing
+    returning contacts.id
+)
+select inserted.id as "id!" from inserted
+union all
+select contacts.id from contacts where contacts.ledger_id = $1 and contacts.user_id = $2"#,
+        ledger.ledger_id,
+        ledger.user_id,
+    )
+    .fetch_one(executor)
+    .await
+    {
+        Ok(id) => Ok(id),
+        Err(err) => {
+            error!(action = "ensure own contact", error = ?err);
+            Err(Status::internal(String::new()))
+        }
+    }
+}
+
+fn parse_positive_money(amount: &str, currency: &str) -> tonic::Result<(BigDecimal, Currency)> {
+    let (amount, currency) = parse_money("amount", amount, "currency", currency)?;
+    if amount.is_zero() {
+        return Err(Status::invalid_argument("amount must be a positive number"));
+    }
+    Ok((amount, currency))
+}
+
+fn method_from_proto(method: i32) -> tonic::Result<Method> {
+    match SplitMethod::try_from(method) {
+        Ok(SplitMethod::Equal) => Ok(Method::Equal),
+        Ok(SplitMethod::Shares) => Ok(Method::Shares),
+        Ok(SplitMethod::Exact) => Ok(Method::Exact),
+        Err(_) => Err(Status::invalid_argument("bad split method")),
+    }
+}
+
+fn method_from_record(method: &str) -> SplitMethod {
+    match method {
+        "shares" => SplitMethod::Shares,
+        "exact" => SplitMethod::Exact,
+        _ => SplitMethod::Equal,
+    }
+}
+
+#[tonic::async_trait]
+impl split_server::Split for SplitApi {
+    async fn list_contacts(&self, request: Request<()>) -> tonic::Result<Response<ContactList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let me = ensure_own_contact(&self.state.database, &ledger).await?;
+        match sqlx::query!(
+            "select contacts.id, contacts.name, contacts.user_id
+from contacts
+where contacts.ledger_id = $1
+order by contacts.name",
+            ledger.ledger_id
+        )
+        .map(|r| Contact {
+            id: self.state.ids.encode(Namespace::Contact, r.id),
+            name: r.name,
+            user_id: r
+                .user_id
+                .map(|id| self.state.ids.encode(Namespace::User, id)),
+            me: r.id == me,
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(contacts) => Ok(Response::new(ContactList { contacts })),
+            Err(err) => {
+                error!(action = "list contacts", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn create_contact(
+        &self,
+        request: Request<NewContact>,
+    ) -> tonic::Result<Response<Contact>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewContact { name, user_id } = request.into_inner();
+        if name.trim().is_empty() {
+            return Err(Status::invalid_argument("missing name"));
+        }
+        let user_id = match user_id.filter(|x| !x.is_empty()) {
+            Some(id) => Some(
+                self.state
+                    .ids
+                    .decode(Namespace::User, &id)
+                    .ok_or_else(|| Status::invalid_argument("bad user id"))?,
+            ),
+            None => None,
+        };
+        // only members of the ledger can be linked, so users can't be looked up by guessing ids
+        let record = match sqlx::query!(
+            "insert into contacts (ledger_id, name, user_id)
+select $1, $2, $3
+where $3::integer is null
+      or exists (select 1 from ledger_members where ledger_members.ledger_id = $1 and ledger_members.user_id = $3)
+on conflict (ledger_id, user_id) do nothing
+returning contacts.id",
+            ledger.ledger_id,
+            name,
+            user_id,
+        )
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                return Err(Status::failed_precondition(
+                    "the user isn't a member of the ledger or already has a contact",
+                ));
+            }
+            Err(err) => {
+                error!(action = "create contact", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        Ok(Response::new(Contact {
+            id: self.state.ids.encode(Namespace::Contact, record.id),
+            name,
+            user_id: user_id.map(|id| self.state.ids.encode(Namespace::User, id)),
+            me: user_id == Some(ledger.user_id),
+        }))
+    }
+
+    async fn add_shared_expense(
+        &self,
+        request: Request<NewSharedExpense>,
+    ) -> tonic::Result<Response<SharedExpense>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewSharedExpense {
+            name,
+            currency,
+            amount,
+            payer_id,
+            method,
+            participants,
+            occurred_at,
+        } = request.into_inner();
+        let (amount, parsed_currency) = parse_positive_money(&amount, &currency)?;
+        let method = method_from_proto(method)?;
+        let occurred_at = match occurred_at {
+            Some(x) => Some(
+                from_proto_timestamp(x).map_err(|_| Status::invalid_argument("bad occurred_at"))?,
+            ),
+            None => None,
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let me = ensure_own_contact(&mut *tx, &ledger).await?;
+        let payer_id = self.contact_id(&payer_id, me)?;
+        let mut contact_ids = Vec::with_capacity(participants.len());
+        let mut values = Vec::with_capacity(participants.len());
+        for participant in participants.iter() {
+            contact_ids.push(self.contact_id(&participant.contact_id, me)?);
+            values.push(match method {
+                Method::Equal => BigDecimal::from(0),
+                _ => participant
+                    .value
+                    .parse::<BigDecimal>()
+                    .map_err(|_| Status::invalid_argument("value isn't numeric"))?,
+            });
+        }
+        if contact_ids.iter().collect::<HashSet<_>>().len() != contact_ids.len() {
+            return Err(Status::invalid_argument("duplicated participant"));
+        }
+        let amounts = split(&amount, exponent(parsed_currency), method, &values)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let unique: Vec<i32> = contact_ids
+            .iter()
+            .copied()
+            .chain([payer_id])
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        match sqlx::query_scalar!(
+            r#"select count(*) as "count!" from contacts where contacts.ledger_id = $1 and contacts.id = any($2)"#,
+            ledger.ledger_id,
+            &unique[..],
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(count) if count as usize == unique.len() => {}
+            Ok(_) => return Err(Status::invalid_argument("contact not found")),
+            Err(err) => {
+                error!(action = "check contacts", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        }
+        // the payment only leaves the ledger when the requesting user paid
+        let item_id = if payer_id == me {
+            match sqlx::query_scalar!(
+                "insert into accounting_items (ledger_id, user_id, name, amount, currency, occurred_at)
+values ($1, $2, $3, $4, $5, coalesce($6, now()))
+returning accounting_items.id",
+                ledger.ledger_id,
+                ledger.user_id,
+                name,
+                -amount.clone(),
+                currency,
+                occurred_at,
+            )
+            .fetch_one(&mut *tx)
+            .await
+            {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    error!(action = "insert shared expense item", error = ?err);
+                    return Err(Status::internal(String::new()));
+                }
+            }
+        } else {
+            None
+        };
+        let expense = match sqlx::query!(
+            "insert into shared_expenses (ledger_id, name, amount, currency, payer_contact_id, split_method, accounting_item_id, occurred_at)
+values ($1, $2, $3, $4, $5, $6, $7, coalesce($8, now()))
+returning shared_expenses.id, shared_expenses.occurred_at",
+            ledger.ledger_id,
+            name,
+            amount,
+            currency,
+            payer_id,
+            method.as_str(),
+            item_id,
+            occurred_at,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "insert shared expense", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        for ((contact_id, share), value) in contact_ids.iter().zip(amounts.iter()).zip(values) {
+            if let Err(err) = sqlx::query!(
+                "insert into shared_expense_shares (shared_expense_id, contact_id, amount, shares)
+values ($1, $2, $3, $4)",
+                expense.id,
+                contact_id,
+                share,
+                (method == Method::Shares).then_some(value),
+            )
+            .execute(&mut *tx)
+            .await
+            {
+                error!(action = "insert shared expense share", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        }
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(SharedExpense {
+            id: self.state.ids.encode(Namespace::SharedExpense, expense.id),
+            name,
+            currency,
+            amount: format_amount(&amount),
+            payer_id: self.state.ids.encode(Namespace::Contact, payer_id),
+            method: SplitMethod::from(method) as i32,
+            shares: contact_ids
+                .iter()
+                .zip(amounts.iter())
+                .map(|(contact_id, amount)| Share {
+                    contact_id: self.state.ids.encode(Namespace::Contact, *contact_id),
+                    amount: format_amount(amount),
+                })
+                .collect(),
+            item_id: item_id.map(|id| self.state.ids.encode(Namespace::Item, id)),
+            occurred_at: Some(to_proto_timestamp(expense.occurred_at)),
+        }))
+    }
+
+    async fn list_shared_expenses(
+        &self,
+        request: Request<()>,
+    ) -> tonic::Result<Response<SharedExpenseList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let me = ensure_own_contact(&self.state.database, &ledger).await?;
+        match sqlx::query!(
+            r#"select shared_expenses.id,
+       shared_expenses.name,
+       shared_expenses.currency,
+       shared_expenses.amount,
+       shared_expenses.payer_contact_id,
+       shared_expenses.split_method,
+       shared_expenses.accounting_item_id,
+       shared_expenses.occurred_at,
+       array(select shared_expense_shares.contact_id from shared_expense_shares where shared_expense_shares.shared_expense_id = shared_expenses.id order by shared_expense_shares.contact_id) as "contacts!",
+       array(select shared_expense_shares.amount from shared_expense_shares where shared_expense_shares.shared_expense_id = shared_expenses.id order by shared_expense_shares.contact_id) as "amounts!"
+from shared_expenses
+where shared_expenses.ledger_id = $1
+order by shared_expenses.occurred_at desc"#,
+            ledger.ledger_id
+        )
+        .map(|r| SharedExpense {
+            id: self.state.ids.encode(Namespace::SharedExpense, r.id),
+            name: r.name,
+            currency: r.currency,
+            amount: format_amount(&r.amount),
+            payer_id: self.state.ids.encode(Namespace::Contact, r.payer_contact_id),
+            method: method_from_record(&r.split_method) as i32,
+            shares: r
+                .contacts
+                .iter()
+                .zip(r.amounts.iter())
+                .map(|(contact_id, amount)| Share {
+                    contact_id: self.state.ids.encode(Namespace::Contact, *contact_id),
+                    amount: format_amount(amount),
+                })
+                .collect(),
+            item_id: r
+                .accounting_item_id
+                .filter(|_| r.payer_contact_id == me)
+                .map(|id| self.state.ids.encode(Namespace::Item, id)),
+            occurred_at: Some(to_proto_timestamp(r.occurred_at)),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(expenses) => Ok(Response::new(SharedExpenseList { expenses })),
+            Err(err) => {
+                error!(action = "list shared expenses", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn get_balances(&self, request: Request<()>) -> tonic::Result<Response<BalanceList>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Viewer).await?;
+        let me = ensure_own_contact(&self.state.database, &ledger).await?;
+        match sqlx::query!(
+            r#"select balances.contact_id as "contact_id!", balances.currency as "currency!", sum(balances.amount) as "amount!"
+from (
+    -- shares of expenses I paid are owed to me
+    select shared_expense_shares.contact_id, shared_expenses.currency, shared_expense_shares.amount
+    from shared_expense_shares
+    join shared_expenses on shared_expenses.id = shared_expense_shares.shared_expense_id
+    where shared_expenses.ledger_id = $1 and shared_expenses.payer_contact_id = $2 and shared_expense_shares.contact_id <> $2
+    union all
+    -- my shares of expenses others paid are owed by me
+    select shared_expenses.payer_contact_id, shared_expenses.currency, -shared_expense_shares.amount
+    from shared_expense_shares
+    join shared_expenses on shared_expenses.id = shared_expense_shares.shared_expense_id
+    where shared_expenses.ledger_id = $1 and shared_expenses.payer_contact_id <> $2 and shared_expense_shares.contact_id = $2
+    union all
+    select settlements.to_contact_id, settlements.currency, settlements.amount
+    from settlements
+    where settlements.ledger_id = $1 and settlements.from_contact_id = $2
+    union all
+    select settlements.from_contact_id, settlements.currency, -settlements.amount
+    from settlements
+    where settlements.ledger_id = $1 and settlements.to_contact_id = $2
+) balances
+group by balances.contact_id, balances.currency
+having sum(balances.amount) <> 0
+order by balances.contact_id, balances.currency"#,
+            ledger.ledger_id,
+            me,
+        )
+        .map(|r| Balance {
+            contact_id: self.state.ids.encode(Namespace::Contact, r.contact_id),
+            currency: r.currency,
+            amount: format_amount(&r.amount),
+        })
+        .fetch_all(&self.state.database)
+        .await
+        {
+            Ok(balances) => Ok(Response::new(BalanceList { balances })),
+            Err(err) => {
+                error!(action = "get balances", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+
+    async fn settle(&self, request: Request<NewSettlement>) -> tonic::Result<Response<Settlement>> {
+        let ledger = ledger::authorize_request(&self.state, &request, Role::Editor).await?;
+        let NewSettlement {
+            contact_id,
+            direction,
+            currency,
+            amount,
+            occurred_at,
+        } = request.into_inner();
+        let (amount, _) = parse_positive_money(&amount, &currency)?;
+        let Ok(direction) = SettlementDirection::try_from(direction) else {
+            return Err(Status::invalid_argument("bad direction"));
+        };
+        let occurred_at = match occurred_at {
+            Some(x) => Some(
+                from_proto_timestamp(x).map_err(|_| Status::invalid_argument("bad occurred_at"))?,
+            ),
+            None => None,
+        };
+        let Some(contact_id) = self.state.ids.decode(Namespace::Contact, &contact_id) else {
+            return Err(Status::invalid_argument("bad contact id"));
+        };
+        let Ok(mut tx) = self.state.database.begin().await else {
+            return Err(Status::internal(String::new()));
+        };
+        let me = ensure_own_contact(&mut *tx, &ledger).await?;
+        if contact_id == me {
+            return Err(Status::invalid_argument("can't settle with yourself"));
+        }
+        let contact = match sqlx::query_scalar!(
+            "select contacts.name from contacts where contacts.ledger_id = $1 and contacts.id = $2",
+            ledger.ledger_id,
+            contact_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        {
+            Ok(Some(name)) => name,
+            Ok(None) => return Err(Status::not_found("contact not found")),
+            Err(err) => {
+                error!(action = "find contact", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let (from, to, item_name, item_amount) = match direction {
+            SettlementDirection::Received => (
+                contact_id,
+                me,
+                format!("Settlement from {contact}"),
+                amount.clone(),
+            ),
+            SettlementDirection::Paid => (
+                me,
+                contact_id,
+                format!("Settlement to {contact}"),
+                -amount.clone(),
+            ),
+        };
+        let item = match sqlx::query!(
+            "insert into accounting_items (ledger_id, user_id, name, amount, currency, occurred_at)
+values ($1, $2, $3, $4, $5, coalesce($6, now()))
+returning accounting_items.id, accounting_items.occurred_at",
+            ledger.ledger_id,
+            ledger.user_id,
+            item_name,
+            item_amount,
+            currency,
+            occurred_at,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                error!(action = "insert settlement item", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        let settlement_id = match sqlx::query_scalar!(
+            "insert into settlements (ledger_id, from_contact_id, to_contact_id, amount, currency, accounting_item_id, occurred_at)
+values ($1, $2, $3, $4, $5, $6, $7)
+returning settlements.id",
+            ledger.ledger_id,
+            from,
+            to,
+            amount,
+            currency,
+            item.id,
+            item.occurred_at,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(id) => id,
+            Err(err) => {
+                error!(action = "insert settlement", error = ?err);
+                return Err(Status::internal(String::new()));
+            }
+        };
+        tx.commit()
+            .await
+            .map_err(|_err| Status::internal(String::new()))?;
+        Ok(Response::new(Settlement {
+            id: self.state.ids.encode(Namespace::Settlement, settlement_id),
+            contact_id: self.state.ids.encode(Namespace::Contact, contact_id),
+            direction: direction as i32,
+            currency,
+            amount: format_amount(&amount),
+            item_id: self.state.ids.encode(Namespace::Item, item.id),
+            occurred_at: Some(to_proto_timestamp(item.occurred_at)),
+        }))
+    }
+}
+
+impl From<Method> for SplitMethod {
+    fn from(value: Method) -> Self {
+        match value {
+            Method::Equal => SplitMethod::Equal,
+            Method::Shares => SplitMethod::Shares,
+            Method::Exact => SplitMethod::Exact,
+        }
+    }
+}
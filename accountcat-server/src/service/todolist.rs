@@ -4,6 +4,7 @@ use tonic::{Request, Response};
 
 use crate::{
     auth::claims_from_request,
+    idcodec::Namespace,
     idl::todolist::{ListResult, NewTask, Task, TaskUpdate, todolist_server::Todolist},
     protobufutils::to_proto_timestamp,
     server::ServerState,
@@ -32,7 +33,7 @@ order by todo_tasks.created_at desc",
             claims.sub
         )
         .map(|x| Task {
-            id: x.id.to_string(),
+            id: self.state.ids.encode(Namespace::Task, x.id),
             name: x.name.unwrap_or_default(),
             completed: x.completed,
             description: x.description,
@@ -74,7 +75,7 @@ where google_sub = $3",
     async fn update_task(&self, request: Request<TaskUpdate>) -> tonic::Result<Response<Task>> {
         let claims = claims_from_request(&request)?;
         let TaskUpdate { id, completed } = request.into_inner();
-        let Ok(id) = id.parse::<i32>() else {
+        let Some(id) = self.state.ids.decode(Namespace::Task, &id) else {
             return Err(tonic::Status::not_found(String::new()));
         };
         let Ok(task) = sqlx::query!(
@@ -93,7 +94,7 @@ returning todo_tasks.*",
             return Err(tonic::Status::internal(String::new()));
         };
         Ok(Response::new(Task {
-            id: task.id.to_string(),
+            id: self.state.ids.encode(Namespace::Task, task.id),
             name: task.name.unwrap_or_default(),
             completed: task.completed,
             description: task.description,
@@ -1,13 +1,20 @@
 use std::{collections::HashSet, sync::Arc};
 
+use iso_currency::Currency;
 use secrecy::{ExposeSecret, SecretString};
 use tonic::{Request, Response, Status};
 use tower_sessions::Session;
 use tracing::error;
 
 use crate::{
-    idl::user::{LoginRequest, Param, Profile, user_server::User},
+    auth::claims_from_request,
+    idl::user::{
+        LoginRequest, Param, Preference, Profile, UpdatePreferenceRequest, Weekday,
+        user_server::User,
+    },
     jwtutils::Claims,
+    ledger::ensure_personal_ledger,
+    period::MAX_MONTH_START_DAY,
     server::{SESSION_KEY_CLAIMS, ServerState},
 };
 
@@ -57,12 +64,16 @@ impl User for UserApi {
             .verify(&request.get_ref().token)
             .map_err(|_| tonic::Status::unauthenticated("invalid token"))?;
         sqlx::query!(
-            "insert into users (google_sub) values ($1) on conflict (google_sub) do nothing;",
-            claims.sub
+            "insert into users (google_sub, name) values ($1, $2) on conflict (google_sub) do update set name = excluded.name;",
+            claims.sub,
+            claims.name
         )
         .execute(&self.state.database)
         .await
         .map_err(|_| tonic::Status::internal(String::new()))?;
+        ensure_personal_ledger(&self.state.database, &claims.sub)
+            .await
+            .map_err(|_| tonic::Status::internal(String::new()))?;
         session
             .insert(SESSION_KEY_CLAIMS, &claims)
             .await
@@ -108,4 +119,91 @@ impl User for UserApi {
             announcement,
         }))
     }
+    async fn get_preference(&self, request: Request<()>) -> tonic::Result<Response<Preference>> {
+        let claims = claims_from_request(&request)?;
+        match sqlx::query!(
+            "select users.base_currency, users.spread_installments, users.time_zone, users.month_start_day, users.week_start from users where users.google_sub = $1",
+            claims.sub
+        )
+        .map(|r| Preference {
+            base_currency: r.base_currency,
+            spread_installments: r.spread_installments,
+            time_zone: r.time_zone,
+            month_start_day: r.month_start_day as u32,
+            week_start: i32::from(r.week_start),
+        })
+        .fetch_one(&self.state.database)
+        .await
+        {
+            Ok(preference) => Ok(Response::new(preference)),
+            Err(err) => {
+                error!(action = "get preference", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
+    async fn update_preference(
+        &self,
+        request: Request<UpdatePreferenceRequest>,
+    ) -> tonic::Result<Response<Preference>> {
+        let claims = claims_from_request(&request)?;
+        let UpdatePreferenceRequest {
+            base_currency,
+            spread_installments,
+            time_zone,
+            month_start_day,
+            week_start,
+        } = request.into_inner();
+        if let Some(base_currency) = base_currency.as_deref()
+            && Currency::from_code(base_currency).is_none()
+        {
+            return Err(Status::invalid_argument("unknown currency"));
+        }
+        if let Some(month_start_day) = month_start_day
+            && !(1..=u32::from(MAX_MONTH_START_DAY)).contains(&month_start_day)
+        {
+            return Err(Status::invalid_argument(format!(
+                "month_start_day must be from 1 to {MAX_MONTH_START_DAY}"
+            )));
+        }
+        if let Some(week_start) = week_start
+            && Weekday::try_from(week_start).is_err()
+        {
+            return Err(Status::invalid_argument("bad week_start"));
+        }
+        match sqlx::query!(
+            "update users
+set base_currency = coalesce($2, base_currency),
+    spread_installments = coalesce($3, spread_installments),
+    time_zone = coalesce($4, time_zone),
+    month_start_day = coalesce($5, month_start_day),
+    week_start = coalesce($6, week_start)
+where users.google_sub = $1
+      and ($4::text is null or exists (select 1 from pg_timezone_names where pg_timezone_names.name = $4))
+returning users.base_currency, users.spread_installments, users.time_zone, users.month_start_day, users.week_start",
+            claims.sub,
+            base_currency,
+            spread_installments,
+            time_zone,
+            month_start_day.map(|x| x as i16),
+            week_start.map(|x| x as i16),
+        )
+        .map(|r| Preference {
+            base_currency: r.base_currency,
+            spread_installments: r.spread_installments,
+            time_zone: r.time_zone,
+            month_start_day: r.month_start_day as u32,
+            week_start: i32::from(r.week_start),
+        })
+        .fetch_optional(&self.state.database)
+        .await
+        {
+            Ok(Some(preference)) => Ok(Response::new(preference)),
+            Ok(None) => Err(Status::invalid_argument("unknown time zone")),
+            Err(err) => {
+                error!(action = "update preference", error = ?err);
+                Err(Status::internal(String::new()))
+            }
+        }
+    }
 }
@@ -0,0 +1,211 @@
+use num_traits::{ToPrimitive, Zero};
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Equal,
+    /// Proportional to the given weight of each participant
+    Shares,
+    /// Each participant's amount is given, and they have to add up to the total
+    Exact,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Equal => "equal",
+            Method::Shares => "shares",
+            Method::Exact => "exact",
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SplitError {
+    #[error("no participant")]
+    NoParticipant,
+    #[error("amount must be positive")]
+    NonPositiveAmount,
+    #[error("amount has more decimal places than the currency allows")]
+    TooPrecise,
+    #[error("shares must be positive")]
+    NonPositiveShare,
+    #[error("amounts of participants add up to {0} instead of the total")]
+    Mismatch(BigDecimal),
+}
+
+/// Split the total among participants, in amounts with `scale` decimal places, the minor unit of
+/// the currency. `values` holds the weight of each participant for
+/// [Method::Shares], the amount of each participant for [Method::Exact], and is ignored for
+/// [Method::Equal] besides its length.
+///
+/// Amounts that can't be divided evenly are rounded down, and the remaining minor units go to the
+/// participants in order, so the result always adds up to the total.
+pub fn split(
+    total: &BigDecimal,
+    scale: u16,
+    method: Method,
+    values: &[BigDecimal],
+) -> Result<Vec<BigDecimal>, SplitError> {
+    if values.is_empty() {
+        return Err(SplitError::NoParticipant);
+    }
+    let total_cents = to_cents(total, scale)?;
+    if total_cents <= 0 {
+        return Err(SplitError::NonPositiveAmount);
+    }
+    let cents = match method {
+        Method::Equal => distribute(total_cents, &vec![BigDecimal::from(1); values.len()]),
+        Method::Shares => {
+            if values.iter().any(|x| *x <= BigDecimal::zero()) {
+                return Err(SplitError::NonPositiveShare);
+            }
+            distribute(total_cents, values)
+        }
+        Method::Exact => {
+            let cents = values
+                .iter()
+                .map(|x| match to_cents(x, scale)? {
+                    c if c < 0 => Err(SplitError::NonPositiveAmount),
+                    c => Ok(c),
+                })
+                .collect::<Result<Vec<i64>, SplitError>>()?;
+            let sum: i64 = cents.iter().sum();
+            if sum != total_cents {
+                return Err(SplitError::Mismatch(from_cents(sum, scale)));
+            }
+            cents
+        }
+    };
+    Ok(cents.into_iter().map(|x| from_cents(x, scale)).collect())
+}
+
+fn distribute(total_cents: i64, weights: &[BigDecimal]) -> Vec<i64> {
+    let weight_sum: BigDecimal = weights.iter().sum();
+    let mut cents: Vec<i64> = weights
+        .iter()
+        .map(|w| {
+            (BigDecimal::from(total_cents) * w / &weight_sum)
+                .with_scale(0)
+                .to_i64()
+                .unwrap_or_default()
+        })
+        .collect();
+    let remainder = total_cents - cents.iter().sum::<i64>();
+    for c in cents.iter_mut().take(remainder as usize) {
+        *c += 1;
+    }
+    cents
+}
+
+fn to_cents(amount: &BigDecimal, scale: u16) -> Result<i64, SplitError> {
+    let scaled = amount * BigDecimal::from(10i64.pow(u32::from(scale)));
+    if !scaled.is_integer() {
+        return Err(SplitError::TooPrecise);
+    }
+    scaled.to_i64().ok_or(SplitError::TooPrecise)
+}
+
+fn from_cents(cents: i64, scale: u16) -> BigDecimal {
+    BigDecimal::new(cents.into(), i64::from(scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amounts(values: &[&str]) -> Vec<BigDecimal> {
+        values.iter().map(|x| x.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_split_equal_with_remainder() {
+        let result = split(
+            &"100".parse().unwrap(),
+            2,
+            Method::Equal,
+            &amounts(&["0"; 3]),
+        )
+        .unwrap();
+        assert_eq!(amounts(&["33.34", "33.33", "33.33"]), result);
+    }
+
+    #[test]
+    fn test_split_minor_units_of_currency() {
+        let result = split(
+            &"100".parse().unwrap(),
+            0,
+            Method::Equal,
+            &amounts(&["0"; 3]),
+        )
+        .unwrap();
+        assert_eq!(amounts(&["34", "33", "33"]), result);
+        let result = split(&"1".parse().unwrap(), 3, Method::Equal, &amounts(&["0"; 3])).unwrap();
+        assert_eq!(amounts(&["0.334", "0.333", "0.333"]), result);
+        assert_eq!(
+            Err(SplitError::TooPrecise),
+            split(
+                &"100.5".parse().unwrap(),
+                0,
+                Method::Equal,
+                &amounts(&["0"])
+            )
+        );
+    }
+
+    #[test]
+    fn test_split_by_shares() {
+        let result = split(
+            &"1000".parse().unwrap(),
+            2,
+            Method::Shares,
+            &amounts(&["2", "1", "1"]),
+        )
+        .unwrap();
+        assert_eq!(amounts(&["500", "250", "250"]), result);
+        assert_eq!(
+            Err(SplitError::NonPositiveShare),
+            split(
+                &"1000".parse().unwrap(),
+                2,
+                Method::Shares,
+                &amounts(&["1", "0"])
+            )
+        );
+    }
+
+    #[test]
+    fn test_split_exact() {
+        let total = "120.5".parse().unwrap();
+        assert_eq!(
+            amounts(&["100", "20.5"]),
+            split(&total, 2, Method::Exact, &amounts(&["100", "20.5"])).unwrap()
+        );
+        assert_eq!(
+            Err(SplitError::Mismatch("120".parse().unwrap())),
+            split(&total, 2, Method::Exact, &amounts(&["100", "20"]))
+        );
+    }
+
+    #[test]
+    fn test_split_rejects_bad_total() {
+        assert_eq!(
+            Err(SplitError::TooPrecise),
+            split(
+                &"1.001".parse().unwrap(),
+                2,
+                Method::Equal,
+                &amounts(&["0"])
+            )
+        );
+        assert_eq!(
+            Err(SplitError::NonPositiveAmount),
+            split(&"-1".parse().unwrap(), 2, Method::Equal, &amounts(&["0"]))
+        );
+        assert_eq!(
+            Err(SplitError::NoParticipant),
+            split(&"1".parse().unwrap(), 2, Method::Equal, &[])
+        );
+    }
+}
@@ -28,8 +28,13 @@ pub fn with_claims<T, S: Into<String>>(mut request: Request<T>, sub: S) -> Reque
 }
 
 pub async fn insert_fake_user(pool: &PgPool) -> sqlx::Result<()> {
+    insert_user(pool, "testing").await
+}
+
+pub async fn insert_user(pool: &PgPool, sub: &str) -> sqlx::Result<()> {
     sqlx::query!(
-        r#"insert into users (google_sub) values ('testing') on conflict (google_sub) do nothing"#
+        r#"insert into users (google_sub) values ($1) on conflict (google_sub) do nothing"#,
+        sub
     )
     .execute(pool)
     .await?;
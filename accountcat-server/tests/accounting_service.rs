@@ -2,18 +2,29 @@ use std::sync::Arc;
 
 use accountcat::{
     config::{Config, General, HashIds, Login, Pki},
-    idl::accounting::{
-        Amount, AmountType, Item, ItemList, NewItem, UpdateItemRequest,
-        accounting_server::Accounting,
+    dateutils,
+    idcodec::Namespace,
+    idl::{
+        accounting::{
+            Amount, AmountType, ConfirmItemRequest, DismissDuplicateRequest, DuplicateSearch,
+            HeatmapRequest, Item, ItemList, ItemStatus, ItemSuggestionRequest,
+            MergeDuplicateRequest, NewItem, NewPayee, NewTag, Offset, PayeeSearch,
+            PayeeStatisticsRequest, PriceHistoryRequest, QuickAddRequest, RemoveOffsetRequest,
+            SearchRequest, TagSearch, UpdateItemRequest, accounting_server::Accounting,
+            price_history_request::Subject,
+        },
+        user::{UpdatePreferenceRequest, Weekday, user_server::User},
     },
-    protobufutils::to_proto_timestamp,
+    period::Period,
+    protobufutils::{from_proto_timestamp, to_proto_timestamp},
     server::{ServerState, init_state},
-    service::accounting::AccountingApi,
+    service::{accounting::AccountingApi, user::UserApi},
     testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
 };
 use secrecy::SecretString;
-use time::OffsetDateTime;
-use tonic::Request;
+use time::{Duration, OffsetDateTime, Time, UtcOffset};
+use tonic::{Code, Request};
+use tonic_types::StatusExt;
 
 const USER_SUB: &str = "testing";
 
@@ -29,6 +40,7 @@ async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
         database: database.clone(),
         hashids: HashIds {
             salt: SecretString::from("dummy"),
+            previous_salts: None,
         },
         pki: Pki::default(),
     })
@@ -40,7 +52,7 @@ async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
 async fn test_add_accounting_item() {
     let (_test_database, server_state) = init_test_database_and_server_state().await;
     insert_fake_user(&server_state.database).await.unwrap();
-    let accounting_api = AccountingApi::new(Arc::new(server_state), SecretString::from("dummy"));
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
 
     let test_add =
         async |amount: &'static str, amount_type: AmountType, expected_amount: &'static str| {
@@ -52,7 +64,7 @@ async fn test_add_accounting_item() {
                         currency: String::from("TWD"),
                     }),
                     r#type: amount_type as i32,
-                    tags: Default::default(),
+                    ..Default::default()
                 }),
                 USER_SUB,
             );
@@ -64,13 +76,8 @@ async fn test_add_accounting_item() {
                 amount,
             );
         };
-    // positive income represents an income
+    // the type tells expenses from incomes
     test_add("999999.99", AmountType::Income, "999999.99").await;
-    // negative income represents an expense
-    test_add("-999999.99", AmountType::Income, "-999999.99").await;
-    // negative expense represents an income
-    test_add("-999999.99", AmountType::Expense, "999999.99").await;
-    // positive expense represents an expense
     test_add("999999.99", AmountType::Expense, "-999999.99").await;
     let list = accounting_api
         .list(with_claims(Request::new(()), USER_SUB))
@@ -80,8 +87,6 @@ async fn test_add_accounting_item() {
     let expected = vec![
         (String::from("-999999.99"), AmountType::Expense as i32),
         (String::from("999999.99"), AmountType::Income as i32),
-        (String::from("-999999.99"), AmountType::Expense as i32),
-        (String::from("999999.99"), AmountType::Income as i32),
     ];
     assert_eq!(
         expected,
@@ -92,11 +97,71 @@ async fn test_add_accounting_item() {
     )
 }
 
+#[tokio::test]
+async fn test_add_accounting_item_rejects_bad_amount() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let test_add = async |amount: &'static str, currency: &'static str| {
+        let req = with_claims(
+            Request::new(NewItem {
+                name: String::from("test item"),
+                amount: Some(Amount {
+                    amount: String::from(amount),
+                    currency: String::from(currency),
+                }),
+                r#type: AmountType::Expense as i32,
+                ..Default::default()
+            }),
+            USER_SUB,
+        );
+        let status = accounting_api.add(req).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code(), "{amount} {currency}");
+        status
+            .get_details_bad_request()
+            .unwrap()
+            .field_violations
+            .into_iter()
+            .map(|x| x.field)
+            .collect::<Vec<String>>()
+    };
+    let amount_field = vec![String::from("amount.amount")];
+    assert_eq!(amount_field, test_add("1e30", "TWD").await);
+    assert_eq!(amount_field, test_add("NaN", "TWD").await);
+    assert_eq!(amount_field, test_add("abc", "TWD").await);
+    assert_eq!(amount_field, test_add("-1", "TWD").await);
+    assert_eq!(amount_field, test_add("100.5", "JPY").await);
+    assert_eq!(
+        vec![String::from("amount.currency")],
+        test_add("100", "XYZ").await
+    );
+
+    // three decimal places are kept for currencies like KWD
+    let item = accounting_api
+        .add(with_claims(
+            Request::new(NewItem {
+                name: String::from("test item"),
+                amount: Some(Amount {
+                    amount: String::from("1.234"),
+                    currency: String::from("KWD"),
+                }),
+                r#type: AmountType::Income as i32,
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("1.234", item.amount.unwrap().amount);
+}
+
 #[tokio::test]
 async fn test_update_accounting_item_occurred_at() {
     let (_test_database, server_state) = init_test_database_and_server_state().await;
     insert_fake_user(&server_state.database).await.unwrap();
-    let accounting_api = AccountingApi::new(Arc::new(server_state), SecretString::from("dummy"));
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
 
     let req = with_claims(
         Request::new(NewItem {
@@ -106,7 +171,7 @@ async fn test_update_accounting_item_occurred_at() {
                 currency: String::from("TWD"),
             }),
             r#type: AmountType::Expense as i32,
-            tags: Default::default(),
+            ..Default::default()
         }),
         USER_SUB,
     );
@@ -132,6 +197,7 @@ async fn test_update_accounting_item_occurred_at() {
                 occurred_at: Some(to_proto_timestamp(
                     OffsetDateTime::from_unix_timestamp(1753599600).unwrap(),
                 )),
+                ..Default::default()
             }),
             USER_SUB,
         ))
@@ -154,7 +220,7 @@ async fn test_update_accounting_item_amount_magnitude(
 ) {
     let (_test_database, server_state) = init_test_database_and_server_state().await;
     insert_fake_user(&server_state.database).await.unwrap();
-    let accounting_api = AccountingApi::new(Arc::new(server_state), SecretString::from("dummy"));
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
 
     let req = with_claims(
         Request::new(NewItem {
@@ -164,7 +230,7 @@ async fn test_update_accounting_item_amount_magnitude(
                 currency: String::from("TWD"),
             }),
             r#type: amount_type as i32,
-            tags: Default::default(),
+            ..Default::default()
         }),
         USER_SUB,
     );
@@ -191,6 +257,7 @@ async fn test_update_accounting_item_amount_magnitude(
                     amount: String::from(modified),
                 }),
                 occurred_at: None,
+                ..Default::default()
             }),
             USER_SUB,
         ))
@@ -230,7 +297,7 @@ async fn test_update_accounting_item_amount_magnitude_zero_income() {
 async fn test_update_accounting_item_name() {
     let (_test_database, server_state) = init_test_database_and_server_state().await;
     insert_fake_user(&server_state.database).await.unwrap();
-    let accounting_api = AccountingApi::new(Arc::new(server_state), SecretString::from("dummy"));
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
 
     let req = with_claims(
         Request::new(NewItem {
@@ -240,7 +307,7 @@ async fn test_update_accounting_item_name() {
                 currency: String::from("TWD"),
             }),
             r#type: AmountType::Expense as i32,
-            tags: Default::default(),
+            ..Default::default()
         }),
         USER_SUB,
     );
@@ -264,6 +331,7 @@ async fn test_update_accounting_item_name() {
                 name: Some(String::from("test item1")),
                 amount: None,
                 occurred_at: None,
+                ..Default::default()
             }),
             USER_SUB,
         ))
@@ -277,3 +345,1377 @@ async fn test_update_accounting_item_name() {
     assert_eq!(original_item.created_at, item.created_at);
     assert_eq!(original_item.amount, item.amount);
 }
+
+#[tokio::test]
+async fn test_search_accounting_items() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let health_tag = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("health"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    for (name, tags) in [
+        ("春天看牙醫", vec![health_tag.id.clone()]),
+        ("Costco groceries", vec![]),
+        ("午餐", vec![]),
+    ] {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from("100"),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    tags,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    }
+    let search = async |query: &str| {
+        accounting_api
+            .search(with_claims(
+                Request::new(SearchRequest {
+                    query: String::from(query),
+                    limit: 0,
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .results
+            .into_iter()
+            .map(|r| r.item.unwrap().name)
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(vec!["春天看牙醫"], search("牙醫").await);
+    assert_eq!(vec!["春天看牙醫"], search("health").await);
+    assert_eq!(vec!["春天看牙醫"], search("牙醫 health").await);
+    assert!(search("牙醫 costco").await.is_empty());
+    // tolerates typos
+    assert_eq!(vec!["Costco groceries"], search("costco grocerys").await);
+    assert!(search("  ").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_payee() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let grocery_tag = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("grocery"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let payee = accounting_api
+        .create_payee(with_claims(
+            Request::new(NewPayee {
+                name: String::from("Costco"),
+                aliases: vec![String::from("好市多")],
+                default_tags: vec![grocery_tag.id.clone()],
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(vec![String::from("好市多")], payee.aliases);
+    assert_eq!(vec![grocery_tag.id.clone()], payee.default_tags);
+
+    let completed = accounting_api
+        .complete_payee(with_claims(
+            Request::new(PayeeSearch {
+                keyword: String::from("市多"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        vec![payee.id.clone()],
+        completed
+            .payees
+            .into_iter()
+            .map(|p| p.id)
+            .collect::<Vec<String>>()
+    );
+
+    for amount in ["100", "300"] {
+        let item = accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("weekly groceries"),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    payee_id: Some(payee.id.clone()),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(Some(payee.id.clone()), item.payee_id);
+    }
+    // default tags are applied
+    let results = accounting_api
+        .search(with_claims(
+            Request::new(SearchRequest {
+                query: String::from("grocery"),
+                limit: 0,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+    assert_eq!(2, results.len());
+
+    let statistics = accounting_api
+        .get_payee_statistics(with_claims(
+            Request::new(PayeeStatisticsRequest {
+                payee_id: payee.id.clone(),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(1, statistics.currencies.len());
+    let twd = &statistics.currencies[0];
+    assert_eq!(
+        ("-400", 2, "-200"),
+        (twd.total.as_str(), twd.count, twd.average.as_str())
+    );
+    assert_eq!("-300", statistics.last_item.unwrap().amount.unwrap().amount);
+}
+
+#[tokio::test]
+async fn test_payee_statistics_round_by_currency() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let unknown_payee_id = server_state.ids.encode(Namespace::Payee, 9999);
+    let unknown_account_id = server_state.ids.encode(Namespace::Account, 9999);
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let payee = accounting_api
+        .create_payee(with_claims(
+            Request::new(NewPayee {
+                name: String::from("Duty Free"),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut item = None;
+    for (amount, currency) in [
+        ("100", "JPY"),
+        ("101", "JPY"),
+        ("101", "JPY"),
+        ("1", "KWD"),
+        ("1.001", "KWD"),
+        ("1.001", "KWD"),
+    ] {
+        item = Some(
+            accounting_api
+                .add(with_claims(
+                    Request::new(NewItem {
+                        name: String::from("souvenir"),
+                        amount: Some(Amount {
+                            amount: String::from(amount),
+                            currency: String::from(currency),
+                        }),
+                        r#type: AmountType::Expense as i32,
+                        payee_id: Some(payee.id.clone()),
+                        ..Default::default()
+                    }),
+                    USER_SUB,
+                ))
+                .await
+                .unwrap()
+                .into_inner(),
+        );
+    }
+    let statistics = accounting_api
+        .get_payee_statistics(with_claims(
+            Request::new(PayeeStatisticsRequest {
+                payee_id: payee.id.clone(),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        vec![("JPY", "-101"), ("KWD", "-1.001")],
+        statistics
+            .currencies
+            .iter()
+            .map(|x| (x.currency.as_str(), x.average.as_str()))
+            .collect::<Vec<_>>()
+    );
+
+    // unknown payees and accounts are rejected instead of being cleared
+    let item = item.unwrap();
+    for request in [
+        UpdateItemRequest {
+            id: item.id.clone(),
+            payee_id: Some(unknown_payee_id),
+            ..Default::default()
+        },
+        UpdateItemRequest {
+            id: item.id.clone(),
+            account_id: Some(unknown_account_id),
+            ..Default::default()
+        },
+    ] {
+        let status = accounting_api
+            .update_item(with_claims(Request::new(request), USER_SUB))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+    }
+    let results = accounting_api
+        .search(with_claims(
+            Request::new(SearchRequest {
+                query: String::from("souvenir"),
+                limit: 0,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+    assert!(
+        results
+            .iter()
+            .all(|x| x.item.as_ref().unwrap().payee_id == Some(payee.id.clone()))
+    );
+}
+
+#[tokio::test]
+async fn test_item_quantity_and_price_history() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let add = async |amount: &str, quantity: &str| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("Rice"),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    note: Some(String::from("from the market")),
+                    quantity: Some(String::from(quantity)),
+                    unit: Some(String::from("kg")),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    let item = add("250", "2.5").await.unwrap().into_inner();
+    assert_eq!(Some(String::from("100")), item.unit_price);
+    assert_eq!(Some(String::from("2.5")), item.quantity);
+    assert_eq!(Some(String::from("kg")), item.unit);
+    assert_eq!(Some(String::from("from the market")), item.note);
+    add("330", "3").await.unwrap();
+    for quantity in ["0", "0.00001", "100000000000000"] {
+        assert_eq!(
+            tonic::Code::InvalidArgument,
+            add("100", quantity).await.unwrap_err().code(),
+            "{quantity}"
+        );
+    }
+
+    accounting_api
+        .update_item(with_claims(
+            Request::new(UpdateItemRequest {
+                id: item.id.clone(),
+                note: Some(String::new()),
+                quantity: Some(String::from("2")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let ItemList { items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    let updated = items.iter().find(|x| x.id == item.id).unwrap();
+    assert_eq!(None, updated.note);
+    assert_eq!(Some(String::from("125")), updated.unit_price);
+    assert_eq!(Some(String::from("kg")), updated.unit);
+
+    let history = accounting_api
+        .get_price_history(with_claims(
+            Request::new(PriceHistoryRequest {
+                subject: Some(Subject::Name(String::from("rice"))),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        vec!["125", "110"],
+        history
+            .points
+            .iter()
+            .map(|x| x.unit_price.as_str())
+            .collect::<Vec<&str>>()
+    );
+}
+
+#[tokio::test]
+async fn test_quick_add() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let food = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("food"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let yesterday = dateutils::today() - Duration::days(1);
+
+    let result = accounting_api
+        .quick_add(with_claims(
+            Request::new(QuickAddRequest {
+                text: String::from("午餐 120 #food #drink 昨天"),
+                parse_only: true,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(None, result.added);
+    assert_eq!(vec![String::from("drink")], result.unknown_tags);
+    let item = result.item.unwrap();
+    assert_eq!("午餐", item.name);
+    assert_eq!(
+        Some(Amount {
+            amount: String::from("120"),
+            currency: String::from("TWD"),
+        }),
+        item.amount
+    );
+    assert_eq!(AmountType::Expense as i32, item.r#type);
+    assert_eq!(vec![food.clone()], item.tags);
+    let occurred_at = from_proto_timestamp(item.occurred_at.unwrap()).unwrap();
+    assert_eq!(
+        yesterday,
+        occurred_at
+            .to_offset(UtcOffset::from_hms(8, 0, 0).unwrap())
+            .date()
+    );
+    let ItemList { items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(items.is_empty());
+
+    let added = accounting_api
+        .quick_add(with_claims(
+            Request::new(QuickAddRequest {
+                text: String::from("taxi 350 JPY yesterday 21:00"),
+                parse_only: false,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .added
+        .unwrap();
+    assert_eq!("taxi", added.name);
+    assert_eq!(
+        Some(Amount {
+            amount: String::from("-350"),
+            currency: String::from("JPY"),
+        }),
+        added.amount
+    );
+    assert_eq!(
+        Some(to_proto_timestamp(
+            yesterday
+                .with_time(Time::from_hms(21, 0, 0).unwrap())
+                .assume_offset(UtcOffset::from_hms(8, 0, 0).unwrap())
+        )),
+        added.occurred_at
+    );
+
+    let status = accounting_api
+        .quick_add(with_claims(
+            Request::new(QuickAddRequest {
+                text: String::from("coffee"),
+                parse_only: true,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+}
+
+#[tokio::test]
+async fn test_duplicates() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let food = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("food"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let noon = OffsetDateTime::from_unix_timestamp(1741752000).unwrap();
+    let add = async |name: &str, amount: &str, hours: i64, tags: Vec<String>| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    tags,
+                    occurred_at: Some(to_proto_timestamp(noon + Duration::hours(hours))),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+
+    let lunch = add("Lunch", "120", 0, vec![food.clone()]).await;
+    assert!(lunch.duplicate_of.is_empty());
+    let again = add("lunch ", "120", 1, Vec::new()).await;
+    assert_eq!(vec![lunch.id.clone()], again.duplicate_of);
+    assert!(
+        add("Dinner", "120", 2, Vec::new())
+            .await
+            .duplicate_of
+            .is_empty()
+    );
+    assert!(
+        add("Lunch", "130", 0, Vec::new())
+            .await
+            .duplicate_of
+            .is_empty()
+    );
+    assert!(
+        add("Lunch", "120", 72, Vec::new())
+            .await
+            .duplicate_of
+            .is_empty()
+    );
+    let taxi = add("Taxi", "300", 0, Vec::new()).await;
+    let uber = add("TAXI to airport", "300", 3, Vec::new()).await;
+    assert_eq!(vec![taxi.id.clone()], uber.duplicate_of);
+
+    let find = async || {
+        accounting_api
+            .find_duplicates(with_claims(
+                Request::new(DuplicateSearch::default()),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .pairs
+            .into_iter()
+            .map(|x| (x.item.unwrap().id, x.other.unwrap().id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec![
+            (taxi.id.clone(), uber.id.clone()),
+            (lunch.id.clone(), again.id.clone())
+        ],
+        find().await
+    );
+
+    accounting_api
+        .dismiss_duplicate(with_claims(
+            Request::new(DismissDuplicateRequest {
+                item_id: uber.id.clone(),
+                other_item_id: taxi.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(vec![(lunch.id.clone(), again.id.clone())], find().await);
+
+    let merged = accounting_api
+        .merge_duplicates(with_claims(
+            Request::new(MergeDuplicateRequest {
+                keep_id: again.id.clone(),
+                remove_id: lunch.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(again.id, merged.id);
+    assert!(find().await.is_empty());
+    let ItemList { items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(items.iter().all(|x| x.id != lunch.id));
+    let status = accounting_api
+        .merge_duplicates(with_claims(
+            Request::new(MergeDuplicateRequest {
+                keep_id: again.id.clone(),
+                remove_id: lunch.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::NotFound, status.code());
+}
+
+#[tokio::test]
+async fn test_merge_duplicates_keeps_offsets() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let add = async |name: &str, amount: &str, r#type: AmountType, reimbursable| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: r#type as i32,
+                    reimbursable,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let link = async |income_id: &str, expense_id: &str, amount: &str| {
+        accounting_api
+            .link_offset(with_claims(
+                Request::new(Offset {
+                    income_id: String::from(income_id),
+                    expense_id: String::from(expense_id),
+                    amount: Some(String::from(amount)),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    };
+    let merge = async |keep_id: &str, remove_id: &str| {
+        accounting_api
+            .merge_duplicates(with_claims(
+                Request::new(MergeDuplicateRequest {
+                    keep_id: String::from(keep_id),
+                    remove_id: String::from(remove_id),
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+
+    let laptop = add("laptop", "30000", AmountType::Expense, true).await;
+    let again = add("laptop", "30000", AmountType::Expense, true).await;
+    let reimbursement = add("reimbursement", "20000", AmountType::Income, false).await;
+    link(&reimbursement, &laptop, "5000").await;
+    link(&reimbursement, &again, "5000").await;
+    merge(&laptop, &again).await.unwrap();
+    let outstanding = accounting_api
+        .get_awaiting_repayment(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .items
+        .into_iter()
+        .map(|x| (x.item.unwrap().id, x.outstanding))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![(laptop.clone(), String::from("20000"))], outstanding);
+
+    let status = merge(&laptop, &reimbursement).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+}
+
+#[tokio::test]
+async fn test_offsets() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let add = async |name: &str, amount: &str, currency: &str, r#type: AmountType, reimbursable| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from(currency),
+                    }),
+                    r#type: r#type as i32,
+                    reimbursable,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let link = async |income_id: &str, expense_id: &str, amount: Option<&str>| {
+        accounting_api
+            .link_offset(with_claims(
+                Request::new(Offset {
+                    income_id: String::from(income_id),
+                    expense_id: String::from(expense_id),
+                    amount: amount.map(String::from),
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    let this_month = async || {
+        let month = dateutils::today().month() as u8;
+        let summary = accounting_api
+            .get_yearly_summary(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner();
+        let month = summary
+            .months
+            .into_iter()
+            .find(|x| x.date == format!("{month:02}"))
+            .unwrap();
+        (month.income, month.expense)
+    };
+    let awaiting = async || {
+        accounting_api
+            .get_awaiting_repayment(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner()
+            .items
+            .into_iter()
+            .map(|x| (x.item.unwrap().name, x.outstanding))
+            .collect::<Vec<_>>()
+    };
+
+    let laptop = add("laptop", "30000", "TWD", AmountType::Expense, true).await;
+    add("coffee", "100", "TWD", AmountType::Expense, false).await;
+    let reimbursement = add("reimbursement", "20000", "TWD", AmountType::Income, false).await;
+    let refund = add("refund", "15000", "TWD", AmountType::Income, false).await;
+    let yen = add("yen refund", "1000", "JPY", AmountType::Income, false).await;
+    assert_eq!((35000.0, -30100.0), this_month().await);
+    assert_eq!(
+        vec![(String::from("laptop"), String::from("30000"))],
+        awaiting().await
+    );
+
+    let offset = link(&reimbursement, &laptop, Some("20000"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(Some(String::from("20000")), offset.amount);
+    assert_eq!((15000.0, -10100.0), this_month().await);
+    assert_eq!(
+        vec![(String::from("laptop"), String::from("10000"))],
+        awaiting().await
+    );
+
+    assert_eq!(
+        Code::InvalidArgument,
+        link(&refund, &laptop, Some("10001"))
+            .await
+            .unwrap_err()
+            .code()
+    );
+    assert_eq!(
+        Code::InvalidArgument,
+        link(&yen, &laptop, None).await.unwrap_err().code()
+    );
+    assert_eq!(
+        Code::InvalidArgument,
+        link(&laptop, &refund, None).await.unwrap_err().code()
+    );
+    // the rest of the expense
+    let offset = link(&refund, &laptop, None).await.unwrap().into_inner();
+    assert_eq!(Some(String::from("10000")), offset.amount);
+    assert!(awaiting().await.is_empty());
+    assert_eq!((5000.0, -100.0), this_month().await);
+
+    accounting_api
+        .remove_offset(with_claims(
+            Request::new(RemoveOffsetRequest {
+                income_id: refund.clone(),
+                expense_id: laptop.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![(String::from("laptop"), String::from("10000"))],
+        awaiting().await
+    );
+}
+
+#[tokio::test]
+async fn test_pending_and_upcoming_items() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let add = async |name: &str, status: ItemStatus, occurred_at: OffsetDateTime| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from("100"),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    occurred_at: Some(to_proto_timestamp(occurred_at)),
+                    status: status as i32,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    let confirm = async |id: &str, amount: Option<&str>| {
+        accounting_api
+            .confirm_item(with_claims(
+                Request::new(ConfirmItemRequest {
+                    id: String::from(id),
+                    amount: amount.map(String::from),
+                    occurred_at: None,
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    let today_expense = async || {
+        accounting_api
+            .get_daily_spending(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner()
+            .expense
+    };
+
+    let now = dateutils::now();
+    let pending = add("hotel deposit", ItemStatus::Pending, now).await;
+    assert_eq!(ItemStatus::Pending as i32, pending.status);
+    assert_eq!("100", today_expense().await);
+    let confirmed = confirm(&pending.id, Some("120"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(ItemStatus::Cleared as i32, confirmed.status);
+    assert_eq!("-120", confirmed.amount.unwrap().amount);
+    assert_eq!(AmountType::Expense as i32, confirmed.r#type);
+    let status = confirm(&pending.id, None).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+    let status = confirm(&pending.id, Some("1.234")).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+
+    let rent = add("rent", ItemStatus::Cleared, now + Duration::days(3)).await;
+    let later = add("insurance", ItemStatus::Pending, now + Duration::days(30)).await;
+    let ItemList { items } = accounting_api
+        .list_upcoming(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        vec![rent.id.as_str(), later.id.as_str()],
+        items.iter().map(|x| x.id.as_str()).collect::<Vec<_>>()
+    );
+    // not counted before their dates
+    let summary = accounting_api
+        .get_yearly_summary(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        -120.0,
+        summary.months.iter().map(|x| x.expense).sum::<f64>()
+    );
+
+    accounting_api
+        .update_item(with_claims(
+            Request::new(UpdateItemRequest {
+                id: later.id.clone(),
+                status: Some(ItemStatus::Cleared as i32),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let status = confirm(&later.id, None).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+}
+
+#[tokio::test]
+async fn test_suggest_items() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let drinks = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("drinks"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let now = dateutils::now();
+    for (name, amount, days, tags) in [
+        ("coffee", "55", 3, vec![]),
+        ("Coffee", "50", 2, vec![]),
+        ("Coffee", "60", 1, vec![drinks.id.clone()]),
+        ("coffee beans", "450", 0, vec![]),
+        ("cola", "30", 400, vec![]),
+        ("tea", "40", 0, vec![]),
+        ("c_la", "35", 0, vec![]),
+    ] {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    tags,
+                    occurred_at: Some(to_proto_timestamp(now - Duration::days(days))),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    }
+    let suggest = async |prefix: &str, limit: u32| {
+        accounting_api
+            .suggest_items(with_claims(
+                Request::new(ItemSuggestionRequest {
+                    prefix: String::from(prefix),
+                    limit,
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .suggestions
+    };
+
+    let suggestions = suggest("CO", 0).await;
+    assert_eq!(
+        vec!["Coffee", "coffee beans", "cola"],
+        suggestions
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    let coffee = &suggestions[0];
+    assert_eq!(3, coffee.count);
+    assert_eq!("60", coffee.amount.as_ref().unwrap().amount);
+    assert_eq!(AmountType::Expense as i32, coffee.r#type);
+    assert_eq!(vec![drinks.clone()], coffee.tags);
+    assert_eq!(1, suggest("co", 1).await.len());
+    assert!(suggest("c%", 0).await.is_empty());
+    let suggestions = suggest("c_", 0).await;
+    assert_eq!(1, suggestions.len());
+    assert_eq!("c_la", suggestions[0].name);
+    assert!(suggest(" ", 0).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_complete_tag_ranking() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+    let mut tags = Vec::new();
+    for name in ["food", "fast food", "seafood", "travel"] {
+        let tag = accounting_api
+            .create_tag(with_claims(
+                Request::new(NewTag {
+                    name: String::from(name),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        tags.push(tag.id);
+    }
+    for (name, tag) in [
+        ("lunch", &tags[1]),
+        ("lunch", &tags[1]),
+        ("dinner", &tags[2]),
+        ("flight", &tags[3]),
+    ] {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from("100"),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    tags: vec![tag.clone()],
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    }
+    let complete = async |keyword: &str, limit: u32, item_name: Option<&str>| {
+        accounting_api
+            .complete_tag(with_claims(
+                Request::new(TagSearch {
+                    keyword: String::from(keyword),
+                    limit,
+                    item_name: item_name.map(String::from),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .tags
+            .into_iter()
+            .map(|x| x.name)
+            .collect::<Vec<_>>()
+    };
+
+    // prefix matches first, then by uses
+    assert_eq!(
+        vec!["food", "fast food", "seafood"],
+        complete("FOOD", 0, None).await
+    );
+    assert_eq!(
+        vec!["fast food", "food", "seafood"],
+        complete("food", 0, Some("Lunch")).await
+    );
+    assert_eq!(vec!["food"], complete("food", 1, None).await);
+    // every tag without a keyword
+    let all = complete("", 0, Some("lunch")).await;
+    assert_eq!(4, all.len());
+    assert_eq!("fast food", all[0]);
+}
+
+#[tokio::test]
+async fn test_daily_spending_in_user_time_zone() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let user_api = UserApi::new(
+        server_state,
+        SecretString::from("dummy"),
+        Default::default(),
+    );
+    // 6 hours ahead of Taipei, without daylight saving time
+    user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                time_zone: Some(String::from("Pacific/Kiritimati")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let offset = UtcOffset::from_hms(14, 0, 0).unwrap();
+    let today = OffsetDateTime::now_utc().to_offset(offset).date();
+    // late today there, which can be tomorrow in Taipei
+    accounting_api
+        .add(with_claims(
+            Request::new(NewItem {
+                name: String::from("late dinner"),
+                amount: Some(Amount {
+                    amount: String::from("300"),
+                    currency: String::from("TWD"),
+                }),
+                r#type: AmountType::Expense as i32,
+                occurred_at: Some(to_proto_timestamp(
+                    today
+                        .with_time(Time::from_hms(23, 59, 0).unwrap())
+                        .assume_offset(offset),
+                )),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let daily = accounting_api
+        .get_daily_spending(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(dateutils::format_date(today), daily.date);
+    assert_eq!(("300", 1), (daily.expense.as_str(), daily.count));
+    let histogram = accounting_api
+        .get_last7_day_histogram(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .data;
+    assert_eq!(7, histogram.len());
+    let last = histogram.last().unwrap();
+    assert_eq!(dateutils::format_date(today).replace('-', "/"), last.date);
+    assert_eq!(300.0, last.expense);
+}
+
+#[tokio::test]
+async fn test_quick_add_in_user_time_zone() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let user_api = UserApi::new(
+        server_state,
+        SecretString::from("dummy"),
+        Default::default(),
+    );
+    user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                time_zone: Some(String::from("Pacific/Kiritimati")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let offset = UtcOffset::from_hms(14, 0, 0).unwrap();
+    let yesterday = OffsetDateTime::now_utc().to_offset(offset).date() - Duration::days(1);
+    let item = accounting_api
+        .quick_add(with_claims(
+            Request::new(QuickAddRequest {
+                text: String::from("lunch 120 yesterday 12:00"),
+                parse_only: true,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .item
+        .unwrap();
+    assert_eq!(
+        Some(to_proto_timestamp(
+            yesterday
+                .with_time(Time::from_hms(12, 0, 0).unwrap())
+                .assume_offset(offset)
+        )),
+        item.occurred_at
+    );
+}
+
+#[tokio::test]
+async fn test_yearly_summary_periods() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let user_api = UserApi::new(
+        server_state,
+        SecretString::from("dummy"),
+        Default::default(),
+    );
+    let update = async |month_start_day: u32| {
+        user_api
+            .update_preference(with_claims(
+                Request::new(UpdatePreferenceRequest {
+                    month_start_day: Some(month_start_day),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    for month_start_day in [0, 29] {
+        let status = update(month_start_day).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+    }
+    assert_eq!(25, update(25).await.unwrap().into_inner().month_start_day);
+
+    // paid on the first day of the current period, and spent the day before it
+    let current = Period::containing(dateutils::today(), 25);
+    for (date, amount, r#type) in [
+        (current.start, "50000", AmountType::Income),
+        (
+            current.start - Duration::days(1),
+            "300",
+            AmountType::Expense,
+        ),
+    ] {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("item"),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: r#type as i32,
+                    occurred_at: Some(to_proto_timestamp(
+                        date.with_time(Time::from_hms(12, 0, 0).unwrap())
+                            .assume_offset(UtcOffset::from_hms(8, 0, 0).unwrap()),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    }
+    let months = accounting_api
+        .get_yearly_summary(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .months;
+    assert_eq!(12, months.len());
+    assert!(months.windows(2).all(|x| x[0].end_date < x[1].start_date));
+    let index = months
+        .iter()
+        .position(|x| x.start_date == dateutils::format_date(current.start))
+        .unwrap();
+    let month = &months[index];
+    assert_eq!(format!("{:02}", current.month.month() as u8), month.date);
+    assert_eq!(dateutils::format_date(current.last_day()), month.end_date);
+    assert_eq!(
+        format!("{} – {}", month.start_date, month.end_date),
+        month.label
+    );
+    assert_eq!((50000.0, 0.0), (month.income, month.expense));
+    // the period before it is in the previous year in January
+    if index > 0 {
+        assert_eq!(-300.0, months[index - 1].expense);
+    }
+}
+
+#[tokio::test]
+async fn test_heatmap() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let user_api = UserApi::new(
+        server_state,
+        SecretString::from("dummy"),
+        Default::default(),
+    );
+    // 2025-03-11 00:30, 2025-03-12 12:00, 2025-03-13 12:00 and 2025-06-01 12:00 in Taipei
+    for (timestamp, amount, r#type) in [
+        (1741624200, "100", AmountType::Expense),
+        (1741752000, "300", AmountType::Expense),
+        (1741838400, "1000", AmountType::Income),
+        (1748750400, "50", AmountType::Expense),
+    ] {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("item"),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: r#type as i32,
+                    occurred_at: Some(to_proto_timestamp(
+                        OffsetDateTime::from_unix_timestamp(timestamp).unwrap(),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    }
+    let get_heatmap = async |request: HeatmapRequest| {
+        accounting_api
+            .get_heatmap(with_claims(Request::new(request), USER_SUB))
+            .await
+    };
+    let range = |start: &str, end: &str| HeatmapRequest {
+        start_date: Some(String::from(start)),
+        end_date: Some(String::from(end)),
+        ..Default::default()
+    };
+
+    let heatmap = get_heatmap(HeatmapRequest {
+        year: Some(2025),
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+    .into_inner();
+    assert_eq!("TWD", heatmap.currency);
+    assert_eq!("Asia/Taipei", heatmap.time_zone);
+    assert_eq!(365, heatmap.days.len());
+    assert_eq!(vec!["50", "100", "300"], heatmap.thresholds);
+    let day = |date: &str| {
+        let day = heatmap.days.iter().find(|x| x.date == date).unwrap();
+        (day.expense.as_str(), day.income.as_str(), day.level)
+    };
+    assert_eq!(("0", "0", 0), day("2025-03-10"));
+    assert_eq!(("100", "0", 2), day("2025-03-11"));
+    assert_eq!(("300", "0", 3), day("2025-03-12"));
+    assert_eq!(("0", "1000", 0), day("2025-03-13"));
+    assert_eq!(("50", "0", 1), day("2025-06-01"));
+
+    let status = user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                time_zone: Some(String::from("Mars/Olympus")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                time_zone: Some(String::from("UTC")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let heatmap = get_heatmap(range("2025-03-10", "2025-03-12"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("UTC", heatmap.time_zone);
+    assert_eq!(
+        vec![
+            ("2025-03-10", "100"),
+            ("2025-03-11", "0"),
+            ("2025-03-12", "300")
+        ],
+        heatmap
+            .days
+            .iter()
+            .map(|x| (x.date.as_str(), x.expense.as_str()))
+            .collect::<Vec<_>>()
+    );
+    user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                week_start: Some(Weekday::Sunday as i32),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let days = get_heatmap(HeatmapRequest::default())
+        .await
+        .unwrap()
+        .into_inner()
+        .days;
+    // 51 weeks before the current week and the days of it so far
+    assert!((358..=364).contains(&days.len()));
+    let first = dateutils::parse_date(&days[0].date).unwrap();
+    assert_eq!(time::Weekday::Sunday, first.weekday());
+
+    for request in [
+        range("2025-01-01", "2026-01-02"),
+        range("2025-03-12", "2025-03-10"),
+        HeatmapRequest {
+            year: Some(2025),
+            start_date: Some(String::from("2025-01-01")),
+            ..Default::default()
+        },
+        HeatmapRequest {
+            start_date: Some(String::from("2025-01-01")),
+            ..Default::default()
+        },
+    ] {
+        let status = get_heatmap(request).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+    }
+}
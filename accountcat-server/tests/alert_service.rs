@@ -0,0 +1,402 @@
+use std::sync::Arc;
+
+use accountcat::{
+    anomaly::detector::detect,
+    config::{Config, General, HashIds, Login, Pki},
+    idl::{
+        accounting::{
+            Amount, AmountType, NewItem, NewPayee, NewTag, Offset, UpdateItemRequest,
+            accounting_server::Accounting,
+        },
+        alert::{AlertIdRequest, AlertKind, AlertSettings, alert_server::Alert},
+    },
+    protobufutils::to_proto_timestamp,
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, alert::AlertApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset};
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+#[tokio::test]
+async fn test_spending_alerts() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let database = server_state.database.clone();
+    let state = Arc::new(server_state);
+    let alert_api = AlertApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let taipei = UtcOffset::from_hms(8, 0, 0).unwrap();
+    let today = OffsetDateTime::now_utc().to_offset(taipei).date();
+    let food = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("food"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let cafe = accounting_api
+        .create_payee(with_claims(
+            Request::new(NewPayee {
+                name: String::from("Cafe"),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let add_item = async |name: &str,
+                          amount: &str,
+                          tags: Vec<String>,
+                          payee_id: Option<String>,
+                          date: Date| {
+        let id = accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    tags,
+                    payee_id,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        accounting_api
+            .update_item(with_claims(
+                Request::new(UpdateItemRequest {
+                    id,
+                    occurred_at: Some(to_proto_timestamp(
+                        date.with_time(Time::MIDNIGHT).assume_offset(taipei),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    };
+    let this_month = today.replace_day(1).unwrap();
+    let last_month = (this_month - Duration::days(1)).replace_day(15).unwrap();
+    let two_months_ago = (last_month.replace_day(1).unwrap() - Duration::days(1))
+        .replace_day(15)
+        .unwrap();
+    // 100 a month on food, then 1000 this month
+    add_item("lunch", "100", vec![food.clone()], None, two_months_ago).await;
+    add_item("lunch", "100", vec![food.clone()], None, last_month).await;
+    add_item("banquet", "1000", vec![food], None, this_month).await;
+    // coffee usually costs 50
+    for days in [30, 20, 10] {
+        add_item(
+            "coffee",
+            "50",
+            Vec::new(),
+            Some(cafe.clone()),
+            today - Duration::days(days),
+        )
+        .await;
+    }
+    add_item("beans", "500", Vec::new(), Some(cafe), today).await;
+
+    assert_eq!(2, detect(&database).await.unwrap());
+    // nothing changed since the last run
+    assert_eq!(0, detect(&database).await.unwrap());
+
+    let list_alerts = async || {
+        alert_api
+            .list_alerts(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner()
+            .alerts
+    };
+    let alerts = list_alerts().await;
+    let tag_alert = alerts
+        .iter()
+        .find(|x| x.kind == AlertKind::TagMonth as i32)
+        .unwrap();
+    assert_eq!("100", tag_alert.baseline);
+    assert_eq!("1000", tag_alert.actual);
+    assert_eq!("10", tag_alert.deviation);
+    assert!(tag_alert.explanation.contains("food"));
+    let item_alert = alerts
+        .iter()
+        .find(|x| x.kind == AlertKind::LargeItem as i32)
+        .unwrap();
+    assert_eq!("50", item_alert.baseline);
+    assert_eq!("500", item_alert.actual);
+    assert!(item_alert.explanation.contains("payee Cafe"));
+
+    let unread_count = async || {
+        alert_api
+            .get_unread_count(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner()
+            .count
+    };
+    assert_eq!(2, unread_count().await);
+    alert_api
+        .mark_read(with_claims(
+            Request::new(AlertIdRequest {
+                id: tag_alert.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(1, unread_count().await);
+    alert_api
+        .dismiss(with_claims(
+            Request::new(AlertIdRequest {
+                id: item_alert.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(0, unread_count().await);
+    let alerts = list_alerts().await;
+    assert_eq!(1, alerts.len());
+    assert!(alerts[0].read);
+
+    // alerts of ledgers the user left are hidden
+    sqlx::query!("delete from ledger_members")
+        .execute(&database)
+        .await
+        .unwrap();
+    assert!(list_alerts().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_alert_settings() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let alert_api = AlertApi::new(Arc::new(server_state));
+
+    let settings = alert_api
+        .get_settings(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        AlertSettings {
+            enabled: true,
+            tag_ratio: String::from("1.5"),
+            item_ratio: String::from("3"),
+            min_amount: String::from("0"),
+        },
+        settings
+    );
+    let updated = AlertSettings {
+        enabled: false,
+        tag_ratio: String::from("2"),
+        item_ratio: String::from("5.5"),
+        min_amount: String::from("300"),
+    };
+    let settings = alert_api
+        .update_settings(with_claims(Request::new(updated.clone()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(updated, settings);
+    let settings = alert_api
+        .get_settings(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(updated, settings);
+
+    let status = alert_api
+        .update_settings(with_claims(
+            Request::new(AlertSettings {
+                tag_ratio: String::from("0.5"),
+                ..updated
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+}
+
+#[tokio::test]
+async fn test_alerts_count_net_spending() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let database = server_state.database.clone();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let taipei = UtcOffset::from_hms(8, 0, 0).unwrap();
+    let today = OffsetDateTime::now_utc().to_offset(taipei).date();
+    let food = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("food"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let cafe = accounting_api
+        .create_payee(with_claims(
+            Request::new(NewPayee {
+                name: String::from("Cafe"),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let add_item = async |name: &str,
+                          amount: &str,
+                          r#type: AmountType,
+                          tags: Vec<String>,
+                          payee_id: Option<String>,
+                          date: Date| {
+        let id = accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: r#type as i32,
+                    tags,
+                    payee_id,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        accounting_api
+            .update_item(with_claims(
+                Request::new(UpdateItemRequest {
+                    id: id.clone(),
+                    occurred_at: Some(to_proto_timestamp(
+                        date.with_time(Time::MIDNIGHT).assume_offset(taipei),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+        id
+    };
+    let refund = async |expense_id: &str, amount: &str| {
+        let income_id = add_item(
+            "refund",
+            amount,
+            AmountType::Income,
+            Vec::new(),
+            None,
+            today,
+        )
+        .await;
+        accounting_api
+            .link_offset(with_claims(
+                Request::new(Offset {
+                    income_id,
+                    expense_id: String::from(expense_id),
+                    amount: None,
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    };
+    let this_month = today.replace_day(1).unwrap();
+    let last_month = (this_month - Duration::days(1)).replace_day(15).unwrap();
+    let expense = AmountType::Expense;
+    add_item(
+        "lunch",
+        "100",
+        expense,
+        vec![food.clone()],
+        None,
+        last_month,
+    )
+    .await;
+    // mostly refunded, 100 spent this month
+    let banquet = add_item("banquet", "1000", expense, vec![food], None, this_month).await;
+    refund(&banquet, "900").await;
+    for days in [30, 20, 10] {
+        add_item(
+            "coffee",
+            "50",
+            expense,
+            Vec::new(),
+            Some(cafe.clone()),
+            today - Duration::days(days),
+        )
+        .await;
+    }
+    let beans = add_item(
+        "beans",
+        "500",
+        expense,
+        Vec::new(),
+        Some(cafe.clone()),
+        today,
+    )
+    .await;
+    refund(&beans, "500").await;
+    // not spent yet
+    add_item(
+        "grinder",
+        "500",
+        expense,
+        Vec::new(),
+        Some(cafe),
+        today + Duration::days(10),
+    )
+    .await;
+
+    assert_eq!(0, detect(&database).await.unwrap());
+}
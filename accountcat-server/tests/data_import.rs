@@ -0,0 +1,275 @@
+use std::{collections::HashMap, sync::Arc};
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    idl::{
+        accounting::{Amount, ItemList, NewTag, accounting_server::Accounting},
+        data_import::{
+            App, AppExport, AppImportRequest, EInvoiceFile, EInvoiceRequest,
+            data_import_server::DataImport,
+        },
+    },
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, data_import::DataImportApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+const MARCH: &str = "M|手機條碼|/ABC1234|20250301|12345678|全家便利商店|AB12345678|85|開立|
+D|AB12345678|45|鮮奶|
+D|AB12345678|40|麵包|
+M|手機條碼|/ABC1234|20250302|87654321|麥當勞|CD00000001|120|開立|
+D|CD00000001|120|大麥克|
+";
+
+// overlaps with the download of March
+const APRIL: &str = "M|手機條碼|/ABC1234|20250302|87654321|麥當勞|CD00000001|120|開立|
+D|CD00000001|120|大麥克|
+M|手機條碼|/ABC1234|20250401|12345678|全家便利商店|EF00000002|30|開立|
+D|EF00000002|30|茶葉蛋|
+";
+
+#[tokio::test]
+async fn test_import_e_invoices() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let data_import_api = DataImportApi::new(server_state.clone());
+    let accounting_api = AccountingApi::new(server_state);
+    let import = async |content: &str| {
+        data_import_api
+            .import_e_invoices(with_claims(
+                Request::new(EInvoiceFile {
+                    content: String::from(content),
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+
+    let summary = import(MARCH).await.unwrap().into_inner();
+    assert_eq!((2, 0), (summary.created, summary.duplicated));
+    let summary = import(APRIL).await.unwrap().into_inner();
+    assert_eq!((1, 1), (summary.created, summary.duplicated));
+    let status = import("M|手機條碼|/ABC1234|2025-13-01|1|a|AB12345679|1|開立|\n")
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+
+    let ItemList { mut items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    items.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    assert_eq!(
+        vec!["全家便利商店", "全家便利商店", "麥當勞"],
+        items.iter().map(|x| x.name.as_str()).collect::<Vec<_>>()
+    );
+    let family_mart = items
+        .iter()
+        .find(|x| {
+            x.amount
+                == Some(Amount {
+                    amount: String::from("-85"),
+                    currency: String::from("TWD"),
+                })
+        })
+        .unwrap();
+    let invoice = data_import_api
+        .get_e_invoice(with_claims(
+            Request::new(EInvoiceRequest {
+                item_id: family_mart.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("AB12345678", invoice.number);
+    assert_eq!(Some(String::from("12345678")), invoice.seller_tax_id);
+    assert_eq!(
+        vec![("鮮奶", "45"), ("麵包", "40")],
+        invoice
+            .lines
+            .iter()
+            .map(|x| (x.name.as_str(), x.amount.as_str()))
+            .collect::<Vec<_>>()
+    );
+
+    // the number is issued again in a later period
+    let summary =
+        import("M|手機條碼|/ABC1234|20250501|12345678|全家便利商店|AB12345678|60|開立|\n")
+            .await
+            .unwrap()
+            .into_inner();
+    assert_eq!((1, 0), (summary.created, summary.duplicated));
+}
+
+const MOZE: &str = "Account,Currency,Record Type,Main Category,Subcategory,Price,Fee,Bonus,Name,Store,Date,Time,Project,Description,Tags,Target
+Cash,TWD,Expense,Food,Lunch,-120,0,0,,Noodle shop,2023/01/15,12:30,,,,
+Cash,TWD,Expense,Food,Dinner,-300,0,0,Hot pot,,2023/01/15,19:00,,,,
+Cash,JPY,Expense,Transport,Taxi,-3500,0,0,,,2023/01/20,21:00,,,,
+Bank,TWD,Income,Salary,,50000,0,0,January,,2023/01/31,,,,,
+Cash,TWD,Transfer Out,Transfer,,-1000,0,0,,,2023/02/01,09:00,,,,
+";
+
+#[tokio::test]
+async fn test_import_app_export() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let data_import_api = DataImportApi::new(server_state.clone());
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let create_tag = async |name: &str| {
+        accounting_api
+            .create_tag(with_claims(
+                Request::new(NewTag {
+                    name: String::from(name),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let food = create_tag("Food").await;
+    let travel = create_tag("travel").await;
+
+    let preview = data_import_api
+        .preview_app_export(with_claims(
+            Request::new(AppExport {
+                app: App::Moze as i32,
+                content: String::from(MOZE),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!((4, 1), (preview.items, preview.skipped));
+    assert_eq!(
+        vec![
+            ("Food", Some(food.clone()), 2),
+            ("Transport", None, 1),
+            ("Salary", None, 1)
+        ],
+        preview
+            .categories
+            .iter()
+            .map(|x| (x.category.as_str(), x.tag_id.clone(), x.count))
+            .collect::<Vec<_>>()
+    );
+
+    let summary = data_import_api
+        .import_app_export(with_claims(
+            Request::new(AppImportRequest {
+                app: App::Moze as i32,
+                content: String::from(MOZE),
+                category_tags: HashMap::from([(String::from("Transport"), travel)]),
+                create_missing_tags: true,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!((4, 1), (summary.created, summary.skipped));
+
+    let ItemList { items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(4, items.len());
+    let taxi = items.iter().find(|x| x.name == "Taxi").unwrap();
+    assert_eq!(
+        Some(Amount {
+            amount: String::from("-3500"),
+            currency: String::from("JPY"),
+        }),
+        taxi.amount
+    );
+    let tagged = sqlx::query!(
+        r#"select accounting_items.name as "item!", tags.name as "tag!"
+from accounting_items
+join accounting_item_tags on accounting_item_tags.accounting_item_id = accounting_items.id
+join tags on tags.id = accounting_item_tags.tag_id
+order by accounting_items.occurred_at"#
+    )
+    .map(|r| (r.item, r.tag))
+    .fetch_all(&server_state.database)
+    .await
+    .unwrap();
+    assert_eq!(
+        vec![
+            (String::from("Noodle shop"), String::from("Food")),
+            (String::from("Hot pot"), String::from("Food")),
+            (String::from("Taxi"), String::from("travel")),
+            (String::from("January"), String::from("Salary")),
+        ],
+        tagged
+    );
+
+    // importing again adds likely duplicates, and items without categories stay untagged
+    let summary = data_import_api
+        .import_app_export(with_claims(
+            Request::new(AppImportRequest {
+                app: App::Moze as i32,
+                content: format!("{MOZE}Cash,TWD,Expense,,,-50,0,0,Snack,,2023/02/02,10:00,,,,\n"),
+                category_tags: HashMap::new(),
+                create_missing_tags: true,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        (5, 4, 1),
+        (summary.created, summary.duplicated, summary.skipped)
+    );
+    let blank_tags =
+        sqlx::query_scalar!(r#"select count(*) as "count!" from tags where name = ''"#)
+            .fetch_one(&server_state.database)
+            .await
+            .unwrap();
+    assert_eq!(0, blank_tags);
+
+    let status = data_import_api
+        .import_app_export(with_claims(
+            Request::new(AppImportRequest {
+                app: App::Spendee as i32,
+                content: String::from(MOZE),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+}
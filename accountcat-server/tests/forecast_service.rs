@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    dateutils::format_date,
+    idl::{
+        accounting::{
+            Amount, AmountType, NewItem, NewTag, UpdateItemRequest, accounting_server::Accounting,
+        },
+        forecast::{
+            ForecastRequest, NewScheduledItem, Recurrence, ScheduledItemType,
+            forecast_server::Forecast,
+        },
+        net_worth::{AccountKind, NewAccount, net_worth_server::NetWorth},
+    },
+    protobufutils::to_proto_timestamp,
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, forecast::ForecastApi, net_worth::NetWorthApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset};
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+#[tokio::test]
+async fn test_forecast() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let state = Arc::new(server_state);
+    let forecast_api = ForecastApi::new(state.clone());
+    let net_worth_api = NetWorthApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let taipei = UtcOffset::from_hms(8, 0, 0).unwrap();
+    let today = OffsetDateTime::now_utc().to_offset(taipei).date();
+    let bank = net_worth_api
+        .create_account(with_claims(
+            Request::new(NewAccount {
+                name: String::from("bank"),
+                kind: AccountKind::Asset as i32,
+                currency: String::from("TWD"),
+                opening_balance: String::from("1000"),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let create_tag = async |name: &str| {
+        accounting_api
+            .create_tag(with_claims(
+                Request::new(NewTag {
+                    name: String::from(name),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let food = create_tag("food").await;
+    let rent = create_tag("rent").await;
+    let add_item = async |amount: &str,
+                          amount_type: AmountType,
+                          tag: &str,
+                          date: Date,
+                          account_id: Option<&str>| {
+        let id = accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("item"),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: amount_type as i32,
+                    tags: vec![String::from(tag)],
+                    account_id: account_id.map(String::from),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        accounting_api
+            .update_item(with_claims(
+                Request::new(UpdateItemRequest {
+                    id,
+                    occurred_at: Some(to_proto_timestamp(
+                        date.with_time(Time::MIDNIGHT + Duration::hours(12))
+                            .assume_offset(taipei),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    };
+    add_item(
+        "300",
+        AmountType::Expense,
+        &food,
+        today - Duration::days(10),
+        None,
+    )
+    .await;
+    // covered by the scheduled rent, so it's left out of the estimate
+    add_item(
+        "2000",
+        AmountType::Expense,
+        &rent,
+        today - Duration::days(5),
+        None,
+    )
+    .await;
+    add_item(
+        "500",
+        AmountType::Income,
+        &food,
+        today + Duration::days(2),
+        Some(&bank),
+    )
+    .await;
+    // not in an asset account, like the opening balance
+    add_item(
+        "700",
+        AmountType::Income,
+        &food,
+        today + Duration::days(2),
+        None,
+    )
+    .await;
+    let scheduled = forecast_api
+        .create_scheduled_item(with_claims(
+            Request::new(NewScheduledItem {
+                name: String::from("rent"),
+                amount: String::from("2000"),
+                currency: String::from("TWD"),
+                r#type: ScheduledItemType::Expense as i32,
+                recurrence: Recurrence::Monthly as i32,
+                start_date: format_date(today + Duration::days(3)),
+                end_date: None,
+                tag_id: Some(rent),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("2000", scheduled.amount);
+    assert_eq!(ScheduledItemType::Expense as i32, scheduled.r#type);
+
+    let get_forecast = async |days: u32| {
+        forecast_api
+            .get_forecast(with_claims(
+                Request::new(ForecastRequest {
+                    days,
+                    trailing_days: Some(30),
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    let forecast = get_forecast(4).await.unwrap().into_inner();
+    assert_eq!("TWD", forecast.currency);
+    assert_eq!("1000", forecast.opening_balance);
+    let estimates: Vec<(Option<&str>, &str)> = forecast
+        .estimates
+        .iter()
+        .map(|x| (x.tag_name.as_deref(), x.daily_amount.as_str()))
+        .collect();
+    assert_eq!(vec![(Some("food"), "-10")], estimates);
+    let days: Vec<(&str, &str, &str, &str)> = forecast
+        .days
+        .iter()
+        .map(|x| {
+            (
+                x.committed.as_str(),
+                x.estimated.as_str(),
+                x.balance.as_str(),
+                x.committed_balance.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ("0", "-10", "990", "1000"),
+            ("500", "-10", "1480", "1500"),
+            ("-2000", "-10", "-530", "-500"),
+            ("0", "-10", "-540", "-500"),
+        ],
+        days
+    );
+    assert_eq!(
+        Some(format_date(today + Duration::days(3))),
+        forecast.shortfall_date
+    );
+    assert_eq!(
+        format_date(today + Duration::days(1)),
+        forecast.days[0].date
+    );
+
+    assert_eq!(
+        Code::InvalidArgument,
+        get_forecast(0).await.unwrap_err().code()
+    );
+    assert_eq!(
+        Code::InvalidArgument,
+        get_forecast(367).await.unwrap_err().code()
+    );
+}
@@ -0,0 +1,281 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    dateutils::today,
+    idl::{
+        accounting::{
+            Amount, AmountType, ItemList, MergeDuplicateRequest, NewItem,
+            accounting_server::Accounting,
+        },
+        installment::{
+            DeleteInstallmentPlanRequest, InstallmentPlanList, NewInstallmentPlan,
+            installment_server::Installment,
+        },
+        user::{UpdatePreferenceRequest, user_server::User},
+    },
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, installment::InstallmentApi, user::UserApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+#[tokio::test]
+async fn test_installment_plans() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let installment_api = InstallmentApi::new(server_state.clone());
+    let user_api = UserApi::new(
+        server_state,
+        SecretString::from("dummy"),
+        Default::default(),
+    );
+    let add = async |name: &str, amount: &str, r#type: AmountType| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: r#type as i32,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    let create = async |item_id: &str, installments: u32, first_due: &str, fee: Option<&str>| {
+        installment_api
+            .create_installment_plan(with_claims(
+                Request::new(NewInstallmentPlan {
+                    item_id: String::from(item_id),
+                    installments,
+                    first_due: String::from(first_due),
+                    fee: fee.map(String::from),
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    // expenses of the current year the way the user counts them
+    let yearly_expense = async || {
+        accounting_api
+            .get_yearly_summary(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner()
+            .months
+            .iter()
+            .map(|x| x.expense)
+            .sum::<f64>()
+    };
+    let spread = async |spread_installments: bool| {
+        user_api
+            .update_preference(with_claims(
+                Request::new(UpdatePreferenceRequest {
+                    spread_installments: Some(spread_installments),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+
+    let phone = add("phone", "30000", AmountType::Expense).await;
+    let laptop = add("laptop", "1200", AmountType::Expense).await;
+    let salary = add("salary", "50000", AmountType::Income).await;
+    // installments of the current year, due in January to March
+    let first_due = format!("{}-01-01", today().year());
+    let plan = create(&phone.id, 3, &first_due, Some("300"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("30000", plan.principal);
+    assert_eq!("300", plan.fee);
+    assert_eq!(
+        vec!["10100", "10100", "10100"],
+        plan.installments
+            .iter()
+            .map(|x| x.amount.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(first_due, plan.installments[0].due);
+    assert!(plan.installments[0].paid);
+    let future = create(&laptop.id, 12, "2099-01-31", None)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("100", future.installments[0].amount);
+    assert_eq!("2099-02-28", future.installments[1].due);
+    assert_eq!("1200", future.remaining);
+    assert_eq!(12, future.remaining_count);
+    assert_eq!(Some(String::from("2099-01-31")), future.next_due);
+
+    let status = create(&phone.id, 3, &first_due, None).await.unwrap_err();
+    assert_eq!(Code::AlreadyExists, status.code());
+    let status = create(&salary.id, 3, &first_due, None).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    let status = create(&salary.id, 1, &first_due, None).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    let status = create(&plan.installments[0].item_id, 3, &first_due, None)
+        .await
+        .unwrap_err();
+    assert_eq!(Code::NotFound, status.code());
+
+    let ItemList { items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(items.iter().any(|x| x.name == "phone (2/3)"));
+    assert_eq!(3 + 3 + 12, items.len());
+
+    // purchases up front by default
+    assert_eq!(-31200.0, yearly_expense().await);
+    assert!(spread(true).await.spread_installments);
+    // only the installments due so far
+    let paid = plan.installments.iter().filter(|x| x.paid).count() as f64;
+    assert_eq!(-10100.0 * paid, yearly_expense().await);
+    assert!(!spread(false).await.spread_installments);
+
+    let InstallmentPlanList { plans } = installment_api
+        .list_installment_plans(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(2, plans.len());
+    assert_eq!(future.id, plans[0].id);
+
+    installment_api
+        .delete_installment_plan(with_claims(
+            Request::new(DeleteInstallmentPlanRequest {
+                id: plan.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let ItemList { items } = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(3 + 12, items.len());
+    assert!(items.iter().any(|x| x.id == phone.id));
+    let status = installment_api
+        .delete_installment_plan(with_claims(
+            Request::new(DeleteInstallmentPlanRequest { id: plan.id }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::NotFound, status.code());
+}
+
+#[tokio::test]
+async fn test_merge_installment_purchases() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let server_state = Arc::new(server_state);
+    let accounting_api = AccountingApi::new(server_state.clone());
+    let installment_api = InstallmentApi::new(server_state);
+    let add = async |name: &str| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from("30000"),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let create = async |item_id: &str| {
+        installment_api
+            .create_installment_plan(with_claims(
+                Request::new(NewInstallmentPlan {
+                    item_id: String::from(item_id),
+                    installments: 3,
+                    first_due: String::from("2099-01-01"),
+                    fee: None,
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    let merge = async |keep_id: &str, remove_id: &str| {
+        accounting_api
+            .merge_duplicates(with_claims(
+                Request::new(MergeDuplicateRequest {
+                    keep_id: String::from(keep_id),
+                    remove_id: String::from(remove_id),
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+
+    let phone = add("phone").await;
+    let again = add("phone").await;
+    let plan = create(&phone).await;
+    let status = merge(&again, &plan.installments[0].item_id)
+        .await
+        .unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+    // the plan and its installments move to the kept purchase
+    merge(&again, &phone).await.unwrap();
+    let InstallmentPlanList { plans } = installment_api
+        .list_installment_plans(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(1, plans.len());
+    assert_eq!(again, plans[0].item_id);
+    assert_eq!(3, plans[0].installments.len());
+
+    let third = add("phone").await;
+    create(&third).await;
+    let status = merge(&again, &third).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+}
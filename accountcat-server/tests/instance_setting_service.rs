@@ -26,6 +26,7 @@ async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
         database: database.clone(),
         hashids: HashIds {
             salt: SecretString::from("dummy"),
+            previous_salts: None,
         },
         pki: Pki::default(),
     })
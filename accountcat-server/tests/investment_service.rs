@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    idl::{
+        accounting::accounting_server::Accounting,
+        investment::{NewTransaction, TransactionKind, investment_server::Investment},
+    },
+    investment::price,
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, investment::InvestmentApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+fn transaction(kind: TransactionKind, quantity: &str, amount: &str, fee: &str) -> NewTransaction {
+    NewTransaction {
+        symbol: String::from("0050"),
+        kind: kind as i32,
+        quantity: String::from(quantity),
+        amount: String::from(amount),
+        fee: String::from(fee),
+        currency: String::from("TWD"),
+        occurred_at: None,
+        account_id: None,
+    }
+}
+
+#[tokio::test]
+async fn test_holdings() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let prices =
+        price::parse_csv("symbol,date,close\n0050,2025-10-01,60\n0050,2025-10-02,62\n".as_bytes())
+            .unwrap();
+    assert_eq!(
+        2,
+        price::import(&server_state.database, &prices)
+            .await
+            .unwrap()
+    );
+    let state = Arc::new(server_state);
+    let investment_api = InvestmentApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let record = async |transaction: NewTransaction| {
+        investment_api
+            .record_transaction(with_claims(Request::new(transaction), USER_SUB))
+            .await
+    };
+    record(transaction(TransactionKind::Buy, "1000", "50000", "20"))
+        .await
+        .unwrap();
+    record(transaction(TransactionKind::Buy, "1000", "60000", "20"))
+        .await
+        .unwrap();
+    let sale = record(transaction(TransactionKind::Sell, "500", "35000", "50"))
+        .await
+        .unwrap()
+        .into_inner();
+    let oversold = record(transaction(TransactionKind::Sell, "5000", "1", "0"))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::FailedPrecondition, oversold.code());
+    record(transaction(TransactionKind::Dividend, "", "1500", "10"))
+        .await
+        .unwrap();
+
+    let holdings = investment_api
+        .list_holdings(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .holdings;
+    assert_eq!(1, holdings.len());
+    let holding = &holdings[0];
+    assert_eq!("1500", holding.quantity);
+    assert_eq!("82530", holding.cost_basis);
+    assert_eq!(Some("55.02"), holding.average_cost.as_deref());
+    assert_eq!(Some("62"), holding.price.as_deref());
+    assert_eq!(Some("2025-10-02"), holding.price_date.as_deref());
+    assert_eq!(Some("93000"), holding.market_value.as_deref());
+    assert_eq!(Some("10470"), holding.unrealized_gain.as_deref());
+    assert_eq!("7440", holding.realized_gain);
+
+    let dividends = investment_api
+        .get_dividend_income(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .years;
+    assert_eq!(1, dividends.len());
+    assert_eq!("1490", dividends[0].amount);
+
+    // the cash flows show up as items
+    let items = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .items;
+    assert_eq!(4, items.len());
+    let sale_item = items.iter().find(|x| x.id == sale.item_id).unwrap();
+    assert_eq!("Sell 0050", sale_item.name);
+    assert_eq!("34950", sale_item.amount.as_ref().unwrap().amount);
+}
@@ -0,0 +1,253 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    idl::{
+        accounting::{Amount, AmountType, NewItem, accounting_server::Accounting},
+        ledger::{
+            AcceptInvitationRequest, MemberListRequest, NewInvitation, NewLedger,
+            RemoveMemberRequest, Role, UpdateMemberRequest, ledger_server::Ledger,
+        },
+    },
+    ledger::LEDGER_ID_METADATA,
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, ledger::LedgerApi},
+    testing::{self, insert_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use tonic::{Code, Request};
+
+const OWNER_SUB: &str = "owner";
+const MEMBER_SUB: &str = "member";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+fn with_ledger<T>(mut request: Request<T>, ledger_id: &str) -> Request<T> {
+    request
+        .metadata_mut()
+        .insert(LEDGER_ID_METADATA, ledger_id.parse().unwrap());
+    request
+}
+
+fn new_item(name: &str) -> NewItem {
+    NewItem {
+        name: String::from(name),
+        amount: Some(Amount {
+            amount: String::from("100"),
+            currency: String::from("TWD"),
+        }),
+        r#type: AmountType::Expense as i32,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_shared_ledger() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_user(&server_state.database, OWNER_SUB)
+        .await
+        .unwrap();
+    insert_user(&server_state.database, MEMBER_SUB)
+        .await
+        .unwrap();
+    let state = Arc::new(server_state);
+    let ledger_api = LedgerApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let ledgers = ledger_api
+        .list(with_claims(Request::new(()), OWNER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .ledgers;
+    assert_eq!(1, ledgers.len(), "everyone has a personal ledger");
+    assert!(ledgers[0].personal);
+
+    let household = ledger_api
+        .create(with_claims(
+            Request::new(NewLedger {
+                name: String::from("Household"),
+            }),
+            OWNER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(Role::Owner as i32, household.role);
+    accounting_api
+        .add(with_ledger(
+            with_claims(Request::new(new_item("rent")), OWNER_SUB),
+            &household.id,
+        ))
+        .await
+        .unwrap();
+    // items of the shared ledger don't show up in the personal ledger
+    let personal_items = accounting_api
+        .list(with_claims(Request::new(()), OWNER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .items;
+    assert!(personal_items.is_empty());
+
+    let denied = accounting_api
+        .list(with_ledger(
+            with_claims(Request::new(()), MEMBER_SUB),
+            &household.id,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::PermissionDenied, denied.code());
+
+    let invitation = ledger_api
+        .create_invitation(with_claims(
+            Request::new(NewInvitation {
+                ledger_id: household.id.clone(),
+                role: Role::Viewer as i32,
+            }),
+            OWNER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let joined = ledger_api
+        .accept_invitation(with_claims(
+            Request::new(AcceptInvitationRequest {
+                token: invitation.token.clone(),
+            }),
+            MEMBER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(household.id, joined.id);
+    assert_eq!(Role::Viewer as i32, joined.role);
+    let reused = ledger_api
+        .accept_invitation(with_claims(
+            Request::new(AcceptInvitationRequest {
+                token: invitation.token,
+            }),
+            MEMBER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::NotFound, reused.code());
+
+    let items = accounting_api
+        .list(with_ledger(
+            with_claims(Request::new(()), MEMBER_SUB),
+            &household.id,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .items;
+    assert_eq!(1, items.len());
+    assert_eq!("rent", items[0].name);
+    let denied = accounting_api
+        .add(with_ledger(
+            with_claims(Request::new(new_item("groceries")), MEMBER_SUB),
+            &household.id,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::PermissionDenied, denied.code());
+
+    let members = ledger_api
+        .list_members(with_claims(
+            Request::new(MemberListRequest {
+                ledger_id: household.id.clone(),
+            }),
+            MEMBER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .members;
+    assert_eq!(2, members.len());
+    let member = members
+        .iter()
+        .find(|m| m.role == Role::Viewer as i32)
+        .unwrap();
+    let denied = ledger_api
+        .update_member(with_claims(
+            Request::new(UpdateMemberRequest {
+                ledger_id: household.id.clone(),
+                user_id: member.user_id.clone(),
+                role: Role::Owner as i32,
+            }),
+            MEMBER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::PermissionDenied, denied.code());
+    ledger_api
+        .update_member(with_claims(
+            Request::new(UpdateMemberRequest {
+                ledger_id: household.id.clone(),
+                user_id: member.user_id.clone(),
+                role: Role::Editor as i32,
+            }),
+            OWNER_SUB,
+        ))
+        .await
+        .unwrap();
+    accounting_api
+        .add(with_ledger(
+            with_claims(Request::new(new_item("groceries")), MEMBER_SUB),
+            &household.id,
+        ))
+        .await
+        .unwrap();
+
+    let owner = members
+        .iter()
+        .find(|m| m.role == Role::Owner as i32)
+        .unwrap();
+    let orphaned = ledger_api
+        .remove_member(with_claims(
+            Request::new(RemoveMemberRequest {
+                ledger_id: household.id.clone(),
+                user_id: owner.user_id.clone(),
+            }),
+            OWNER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::FailedPrecondition, orphaned.code());
+    ledger_api
+        .remove_member(with_claims(
+            Request::new(RemoveMemberRequest {
+                ledger_id: household.id.clone(),
+                user_id: member.user_id.clone(),
+            }),
+            MEMBER_SUB,
+        ))
+        .await
+        .unwrap();
+    let ledgers = ledger_api
+        .list(with_claims(Request::new(()), MEMBER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .ledgers;
+    assert_eq!(1, ledgers.len(), "only the personal ledger is left");
+}
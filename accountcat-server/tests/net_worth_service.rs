@@ -0,0 +1,450 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    idl::{
+        accounting::{
+            Amount, AmountType, NewItem, NewPayee, UpdateItemRequest, accounting_server::Accounting,
+        },
+        net_worth::{
+            AccountKind, NetWorthHistoryRequest, NewAccount, NewSnapshot, ReconcileRequest,
+            StatementRequest, net_worth_server::NetWorth,
+        },
+        user::{UpdatePreferenceRequest, user_server::User},
+    },
+    protobufutils::to_proto_timestamp,
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, net_worth::NetWorthApi, user::UserApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use time::{Date, Month, Time, UtcOffset};
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+#[tokio::test]
+async fn test_net_worth_history() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let state = Arc::new(server_state);
+    let net_worth_api = NetWorthApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let create_account = async |name: &str, kind: AccountKind, currency: &str, opening: &str| {
+        net_worth_api
+            .create_account(with_claims(
+                Request::new(NewAccount {
+                    name: String::from(name),
+                    kind: kind as i32,
+                    currency: String::from(currency),
+                    opening_balance: String::from(opening),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let bank = create_account("bank", AccountKind::Asset, "TWD", "10000").await;
+    let card = create_account("credit card", AccountKind::Liability, "TWD", "0").await;
+    let brokerage = create_account("brokerage", AccountKind::Asset, "USD", "100").await;
+
+    let add_item = async |amount: &str, amount_type: AmountType, account_id: &str| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("item"),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: amount_type as i32,
+                    account_id: Some(String::from(account_id)),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    let salary = add_item("5000", AmountType::Income, &bank).await;
+    assert_eq!(Some(bank.clone()), salary.account_id);
+    add_item("1200", AmountType::Expense, &card).await;
+
+    let record = async |account_id: &str, date: &str, value: Option<&str>| {
+        net_worth_api
+            .record_snapshot(with_claims(
+                Request::new(NewSnapshot {
+                    account_id: String::from(account_id),
+                    date: String::from(date),
+                    value: value.map(String::from),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    let snapshot = record(&bank, "2020-01-01", Some("8000")).await;
+    assert!(!snapshot.derived);
+    let snapshot = record(&bank, "2099-12-31", None).await;
+    assert!(snapshot.derived);
+    assert_eq!("15000", snapshot.value);
+    let snapshot = record(&card, "2099-12-31", None).await;
+    assert_eq!("1200", snapshot.value, "expenses increase the amount owed");
+    record(&brokerage, "2099-12-31", Some("120")).await;
+
+    let history = net_worth_api
+        .get_net_worth_history(with_claims(
+            Request::new(NetWorthHistoryRequest::default()),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("TWD", history.currency);
+    let points: Vec<(&str, &str, i64)> = history
+        .points
+        .iter()
+        .map(|x| (x.date.as_str(), x.net_worth.as_str(), x.unsupported_count))
+        .collect();
+    assert_eq!(
+        vec![("2020-01-01", "8000", 0), ("2099-12-31", "13800", 1)],
+        points
+    );
+
+    let accounts = net_worth_api
+        .list_accounts(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .accounts;
+    let latest = accounts
+        .iter()
+        .find(|x| x.id == bank)
+        .and_then(|x| x.latest.as_ref())
+        .unwrap();
+    assert_eq!("2099-12-31", latest.date);
+}
+
+#[tokio::test]
+async fn test_base_currency() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let state = Arc::new(server_state);
+    let user_api = UserApi::new(
+        state.clone(),
+        SecretString::from("dummy"),
+        Default::default(),
+    );
+    let net_worth_api = NetWorthApi::new(state);
+
+    let status = user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                base_currency: Some(String::from("XYZ")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    let preference = user_api
+        .update_preference(with_claims(
+            Request::new(UpdatePreferenceRequest {
+                base_currency: Some(String::from("USD")),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("USD", preference.base_currency);
+    let history = net_worth_api
+        .get_net_worth_history(with_claims(
+            Request::new(NetWorthHistoryRequest::default()),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("USD", history.currency);
+}
+
+#[tokio::test]
+async fn test_card_statement() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let state = Arc::new(server_state);
+    let net_worth_api = NetWorthApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let status = net_worth_api
+        .create_account(with_claims(
+            Request::new(NewAccount {
+                name: String::from("bank"),
+                kind: AccountKind::Asset as i32,
+                currency: String::from("TWD"),
+                closing_day: Some(15),
+                due_day: Some(5),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    let card = net_worth_api
+        .create_account(with_claims(
+            Request::new(NewAccount {
+                name: String::from("card"),
+                kind: AccountKind::Liability as i32,
+                currency: String::from("TWD"),
+                closing_day: Some(15),
+                due_day: Some(5),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(Some(15), card.closing_day);
+
+    let taipei = UtcOffset::from_hms(8, 0, 0).unwrap();
+    let add_item = async |name: &str, amount: &str, r#type: AmountType, date: Date| {
+        let id = accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: r#type as i32,
+                    account_id: Some(card.id.clone()),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        accounting_api
+            .update_item(with_claims(
+                Request::new(UpdateItemRequest {
+                    id: id.clone(),
+                    occurred_at: Some(to_proto_timestamp(
+                        date.with_time(Time::MIDNIGHT).assume_offset(taipei),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+        id
+    };
+    let date = |month, day| Date::from_calendar_date(2025, month, day).unwrap();
+    add_item(
+        "laptop",
+        "300",
+        AmountType::Expense,
+        date(Month::February, 10),
+    )
+    .await;
+    let dinner = add_item(
+        "dinner",
+        "100",
+        AmountType::Expense,
+        date(Month::February, 20),
+    )
+    .await;
+    let payment = add_item("payment", "300", AmountType::Income, date(Month::March, 1)).await;
+    add_item("coffee", "50", AmountType::Expense, date(Month::March, 15)).await;
+    add_item("phone", "999", AmountType::Expense, date(Month::March, 16)).await;
+
+    let get_statement = async |date: &str| {
+        net_worth_api
+            .get_statement(with_claims(
+                Request::new(StatementRequest {
+                    account_id: card.id.clone(),
+                    date: Some(String::from(date)),
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    let statement = get_statement("2025-03-01").await;
+    assert_eq!("2025-02-16", statement.start_date);
+    assert_eq!("2025-03-15", statement.closing_date);
+    assert_eq!("2025-04-05", statement.due_date);
+    assert_eq!("300", statement.previous_balance);
+    assert_eq!("150", statement.charges);
+    assert_eq!("300", statement.credits);
+    assert_eq!("150", statement.balance);
+    assert_eq!("150", statement.payment_due);
+    assert_eq!(3, statement.items.len());
+    assert!(!statement.reconciled);
+
+    // the coffee isn't on the bank statement yet
+    let statement = net_worth_api
+        .reconcile_statement(with_claims(
+            Request::new(ReconcileRequest {
+                account_id: card.id.clone(),
+                date: String::from("2025-03-10"),
+                statement_balance: String::from("140"),
+                item_ids: vec![dinner.clone(), payment.clone()],
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(statement.reconciled);
+    assert_eq!(Some(String::from("140")), statement.statement_balance);
+    assert_eq!(Some(String::from("40")), statement.discrepancy);
+    assert_eq!(
+        vec![true, true, false],
+        statement
+            .items
+            .iter()
+            .map(|x| x.cleared)
+            .collect::<Vec<_>>()
+    );
+
+    // the next cycle starts from the reconciled balance
+    let statement = get_statement("2025-03-20").await;
+    assert_eq!("140", statement.previous_balance);
+    assert_eq!("999", statement.charges);
+    assert_eq!("1139", statement.balance);
+
+    let bank = net_worth_api
+        .create_account(with_claims(
+            Request::new(NewAccount {
+                name: String::from("bank"),
+                kind: AccountKind::Asset as i32,
+                currency: String::from("TWD"),
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let status = net_worth_api
+        .get_statement(with_claims(
+            Request::new(StatementRequest {
+                account_id: bank.id,
+                date: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+}
+
+#[tokio::test]
+async fn test_payee_default_account() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let state = Arc::new(server_state);
+    let net_worth_api = NetWorthApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+    let create_account = async |name: &str| {
+        net_worth_api
+            .create_account(with_claims(
+                Request::new(NewAccount {
+                    name: String::from(name),
+                    kind: AccountKind::Liability as i32,
+                    currency: String::from("TWD"),
+                    opening_balance: String::from("0"),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let card = create_account("credit card").await;
+    let wallet = create_account("wallet").await;
+    let create_payee = async |name: &str, default_account_id: Option<String>| {
+        accounting_api
+            .create_payee(with_claims(
+                Request::new(NewPayee {
+                    name: String::from(name),
+                    default_account_id,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+    };
+    let payee = create_payee("Costco", Some(card.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(Some(card.clone()), payee.default_account_id);
+    let status = create_payee("Carrefour", Some(String::from("bad")))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+
+    let add_item = async |account_id: Option<String>| {
+        accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from("groceries"),
+                    amount: Some(Amount {
+                        amount: String::from("500"),
+                        currency: String::from("TWD"),
+                    }),
+                    r#type: AmountType::Expense as i32,
+                    payee_id: Some(payee.id.clone()),
+                    account_id,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+    };
+    assert_eq!(Some(card), add_item(None).await.account_id);
+    assert_eq!(
+        Some(wallet.clone()),
+        add_item(Some(wallet)).await.account_id
+    );
+}
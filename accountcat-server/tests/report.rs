@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    idl::accounting::{
+        Amount, AmountType, NewItem, NewTag, UpdateItemRequest, accounting_server::Accounting,
+    },
+    ledger::{self, Role},
+    protobufutils::to_proto_timestamp,
+    report::{self, Format, ReportError, data, format::Locale, render::PdfError},
+    server::{ServerState, init_state},
+    service::accounting::AccountingApi,
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use sqlx::types::BigDecimal;
+use time::{Date, Month, Time, UtcOffset};
+use tonic::Request;
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+#[tokio::test]
+async fn test_monthly_report() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let database = server_state.database.clone();
+    let accounting_api = AccountingApi::new(Arc::new(server_state));
+
+    let taipei = UtcOffset::from_hms(8, 0, 0).unwrap();
+    let food = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("food"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let add_item = async |name: &str,
+                          amount: &str,
+                          currency: &str,
+                          r#type: AmountType,
+                          tags: Vec<String>,
+                          date: Date| {
+        let id = accounting_api
+            .add(with_claims(
+                Request::new(NewItem {
+                    name: String::from(name),
+                    amount: Some(Amount {
+                        amount: String::from(amount),
+                        currency: String::from(currency),
+                    }),
+                    r#type: r#type as i32,
+                    tags,
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        accounting_api
+            .update_item(with_claims(
+                Request::new(UpdateItemRequest {
+                    id,
+                    occurred_at: Some(to_proto_timestamp(
+                        date.with_time(Time::MIDNIGHT).assume_offset(taipei),
+                    )),
+                    ..Default::default()
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap();
+    };
+    let date = |month, day| Date::from_calendar_date(2025, month, day).unwrap();
+    add_item(
+        "salary",
+        "50000",
+        "TWD",
+        AmountType::Income,
+        Vec::new(),
+        date(Month::February, 5),
+    )
+    .await;
+    add_item(
+        "rent",
+        "12000",
+        "TWD",
+        AmountType::Expense,
+        Vec::new(),
+        date(Month::March, 1),
+    )
+    .await;
+    add_item(
+        "dinner",
+        "1500",
+        "TWD",
+        AmountType::Expense,
+        vec![food.clone()],
+        date(Month::March, 1),
+    )
+    .await;
+    add_item(
+        "lunch",
+        "150",
+        "TWD",
+        AmountType::Expense,
+        vec![food],
+        date(Month::March, 31),
+    )
+    .await;
+    add_item(
+        "souvenir",
+        "20",
+        "USD",
+        AmountType::Expense,
+        Vec::new(),
+        date(Month::March, 10),
+    )
+    .await;
+
+    let access = ledger::authorize(&database, USER_SUB, None, Role::Viewer)
+        .await
+        .unwrap();
+    let march = date(Month::March, 1);
+    let monthly = data::load(&database, access.ledger_id, access.user_id, march)
+        .await
+        .unwrap();
+    assert_eq!("TWD", monthly.currency);
+    assert_eq!(BigDecimal::from(13650), monthly.current.expense);
+    assert_eq!(3, monthly.current.count);
+    assert_eq!(BigDecimal::from(50000), monthly.previous.income);
+    assert_eq!(1, monthly.unsupported_count);
+    assert_eq!(1, monthly.top_tags.len());
+    assert_eq!("food", monthly.top_tags[0].name);
+    assert_eq!(BigDecimal::from(1650), monthly.top_tags[0].expense);
+    assert_eq!(
+        vec!["rent", "dinner", "lunch"],
+        monthly
+            .biggest
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(31, monthly.days.len());
+    assert_eq!(2, monthly.days[0].count);
+    assert_eq!(BigDecimal::from(150), monthly.days[30].expense);
+
+    let html = report::monthly(&database, &access, march, Locale::De, Format::Html, None)
+        .await
+        .unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains(r#"<html lang="de">"#));
+    assert!(html.contains("13.650"));
+    assert!(html.contains("50.000"));
+    assert!(html.contains("1 items in other currencies"));
+
+    let err = report::monthly(&database, &access, march, Locale::En, Format::Pdf, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ReportError::Pdf(PdfError::NotConfigured)));
+    // any command converting stdin to stdout works
+    let command = vec![String::from("cat")];
+    let pdf = report::monthly(
+        &database,
+        &access,
+        march,
+        Locale::En,
+        Format::Pdf,
+        Some(&command),
+    )
+    .await
+    .unwrap();
+    assert!(String::from_utf8(pdf).unwrap().contains("13,650"));
+
+    // from payday to payday
+    sqlx::query!(
+        "update users set month_start_day = 5 where id = $1",
+        access.user_id
+    )
+    .execute(&database)
+    .await
+    .unwrap();
+    let monthly = data::load(&database, access.ledger_id, access.user_id, march)
+        .await
+        .unwrap();
+    assert_eq!(date(Month::March, 5), monthly.period.start);
+    assert_eq!(BigDecimal::from(150), monthly.current.expense);
+    assert_eq!(BigDecimal::from(13500), monthly.previous.expense);
+    assert_eq!(BigDecimal::from(50000), monthly.previous.income);
+    assert_eq!(31, monthly.days.len());
+    assert_eq!(date(Month::April, 4), monthly.days[30].date);
+    let html = report::monthly(&database, &access, march, Locale::En, Format::Html, None)
+        .await
+        .unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("2025-03-05 – 2025-04-04"));
+    assert!(html.contains("2025-02-05 – 2025-03-04"));
+
+    // midnight in Taipei is still the previous day in UTC
+    sqlx::query!(
+        "update users set month_start_day = 1, time_zone = 'UTC' where id = $1",
+        access.user_id
+    )
+    .execute(&database)
+    .await
+    .unwrap();
+    let monthly = data::load(&database, access.ledger_id, access.user_id, march)
+        .await
+        .unwrap();
+    assert_eq!(BigDecimal::from(150), monthly.current.expense);
+    assert_eq!(BigDecimal::from(13500), monthly.previous.expense);
+    assert_eq!(0, monthly.days[0].count);
+    assert_eq!(1, monthly.days[29].count);
+}
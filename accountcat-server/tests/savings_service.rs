@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    dateutils::{format_date, today},
+    idl::{
+        accounting::{Amount, AmountType, NewItem, NewTag, accounting_server::Accounting},
+        savings::{DeleteGoalRequest, NewContribution, NewGoal, savings_server::Savings},
+    },
+    period::Period,
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, savings::SavingsApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use time::{Duration, OffsetDateTime};
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+#[tokio::test]
+async fn test_savings_goal() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let database = server_state.database.clone();
+    let state = Arc::new(server_state);
+    let savings_api = SavingsApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let tag = accounting_api
+        .create_tag(with_claims(
+            Request::new(NewTag {
+                name: String::from("japan trip"),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    accounting_api
+        .add(with_claims(
+            Request::new(NewItem {
+                name: String::from("transfer to trip fund"),
+                amount: Some(Amount {
+                    amount: String::from("3000"),
+                    currency: String::from("TWD"),
+                }),
+                r#type: AmountType::Expense as i32,
+                tags: vec![tag.id.clone()],
+                ..Default::default()
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+
+    let deadline = format_date((OffsetDateTime::now_utc() + Duration::days(400)).date());
+    let goal = savings_api
+        .create_goal(with_claims(
+            Request::new(NewGoal {
+                name: String::from("Japan"),
+                target_amount: String::from("60000"),
+                currency: String::from("TWD"),
+                deadline: Some(deadline.clone()),
+                tag_id: Some(tag.id),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!("3000", goal.saved, "tagged items fund the goal");
+
+    savings_api
+        .add_contribution(with_claims(
+            Request::new(NewContribution {
+                goal_id: goal.id.clone(),
+                amount: String::from("9000"),
+                note: Some(String::from("bonus")),
+                occurred_at: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let bad = savings_api
+        .add_contribution(with_claims(
+            Request::new(NewContribution {
+                goal_id: goal.id.clone(),
+                amount: String::from("0"),
+                note: None,
+                occurred_at: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, bad.code());
+    for amount in ["1e30", "0.001"] {
+        let bad = savings_api
+            .add_contribution(with_claims(
+                Request::new(NewContribution {
+                    goal_id: goal.id.clone(),
+                    amount: String::from(amount),
+                    note: None,
+                    occurred_at: None,
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::InvalidArgument, bad.code(), "{amount}");
+    }
+
+    let goals = savings_api
+        .list_goals(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .goals;
+    assert_eq!(1, goals.len());
+    let goal = &goals[0];
+    assert_eq!("12000", goal.saved);
+    assert_eq!(Some(deadline), goal.deadline);
+    assert!((goal.progress - 0.2).abs() < f64::EPSILON);
+    let required_monthly: f64 = goal.required_monthly.as_ref().unwrap().parse().unwrap();
+    assert!(required_monthly > 0.0 && required_monthly < 48000.0);
+    assert_eq!(1, goal.history.len());
+    assert_eq!("12000", goal.history[0].amount);
+
+    // months run from payday to payday
+    sqlx::query!("update users set month_start_day = 25")
+        .execute(&database)
+        .await
+        .unwrap();
+    let goals = savings_api
+        .list_goals(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .goals;
+    let month = Period::containing(today(), 25).month;
+    assert_eq!(
+        format!("{:04}-{:02}", month.year(), u8::from(month.month())),
+        goals[0].history[0].month
+    );
+
+    savings_api
+        .delete_goal(with_claims(
+            Request::new(DeleteGoalRequest {
+                id: goal.id.clone(),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap();
+    let goals = savings_api
+        .list_goals(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .goals;
+    assert!(goals.is_empty());
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use accountcat::{
+    config::{Config, General, HashIds, Login, Pki},
+    idcodec::{IdCodec, Namespace},
+    idl::{
+        accounting::accounting_server::Accounting,
+        split::{
+            NewContact, NewSettlement, NewSharedExpense, Participant, SettlementDirection,
+            SplitMethod, split_server::Split,
+        },
+    },
+    server::{ServerState, init_state},
+    service::{accounting::AccountingApi, split::SplitApi},
+    testing::{self, insert_fake_user, test_database::TestDatabase, with_claims},
+};
+use secrecy::SecretString;
+use tonic::{Code, Request};
+
+const USER_SUB: &str = "testing";
+
+async fn init_test_database_and_server_state() -> (TestDatabase, ServerState) {
+    let test_database = testing::create_database().await;
+    let TestDatabase { database } = &test_database;
+    let server_state = init_state(&Config {
+        server: Default::default(),
+        general: General::default(),
+        login: Login {
+            client_id: SecretString::from("dummy"),
+        },
+        database: database.clone(),
+        hashids: HashIds {
+            salt: SecretString::from("dummy"),
+            previous_salts: None,
+        },
+        pki: Pki::default(),
+    })
+    .await;
+    (test_database, server_state)
+}
+
+fn participant(contact_id: &str, value: &str) -> Participant {
+    Participant {
+        contact_id: String::from(contact_id),
+        value: String::from(value),
+    }
+}
+
+#[tokio::test]
+async fn test_split_bill() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let state = Arc::new(server_state);
+    let split_api = SplitApi::new(state.clone());
+    let accounting_api = AccountingApi::new(state);
+
+    let create_contact = async |name: &str| {
+        split_api
+            .create_contact(with_claims(
+                Request::new(NewContact {
+                    name: String::from(name),
+                    user_id: None,
+                }),
+                USER_SUB,
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .id
+    };
+    let alice = create_contact("Alice").await;
+    let bob = create_contact("Bob").await;
+    let contacts = split_api
+        .list_contacts(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .contacts;
+    assert_eq!(3, contacts.len(), "the user has a contact as well");
+    let me = contacts.iter().find(|x| x.me).unwrap().id.clone();
+
+    let dinner = split_api
+        .add_shared_expense(with_claims(
+            Request::new(NewSharedExpense {
+                name: String::from("hot pot"),
+                currency: String::from("TWD"),
+                amount: String::from("1000"),
+                payer_id: String::new(),
+                method: SplitMethod::Equal as i32,
+                participants: vec![
+                    participant("", ""),
+                    participant(&alice, ""),
+                    participant(&bob, ""),
+                ],
+                occurred_at: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(
+        dinner.item_id.is_some(),
+        "the payment is recorded as an item"
+    );
+    let shares: Vec<&str> = dinner.shares.iter().map(|x| x.amount.as_str()).collect();
+    assert_eq!(vec!["333.34", "333.33", "333.33"], shares);
+
+    let groceries = split_api
+        .add_shared_expense(with_claims(
+            Request::new(NewSharedExpense {
+                name: String::from("groceries"),
+                currency: String::from("TWD"),
+                amount: String::from("600"),
+                payer_id: alice.clone(),
+                method: SplitMethod::Exact as i32,
+                participants: vec![participant(&me, "400"), participant(&alice, "200")],
+                occurred_at: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(groceries.item_id.is_none());
+
+    let mismatch = split_api
+        .add_shared_expense(with_claims(
+            Request::new(NewSharedExpense {
+                name: String::from("taxi"),
+                currency: String::from("TWD"),
+                amount: String::from("300"),
+                payer_id: bob.clone(),
+                method: SplitMethod::Exact as i32,
+                participants: vec![participant(&me, "100"), participant(&bob, "100")],
+                occurred_at: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, mismatch.code());
+
+    let get_balances = async || {
+        split_api
+            .get_balances(with_claims(Request::new(()), USER_SUB))
+            .await
+            .unwrap()
+            .into_inner()
+            .balances
+            .into_iter()
+            .map(|x| (x.contact_id, x.amount))
+            .collect::<Vec<(String, String)>>()
+    };
+    let balances = get_balances().await;
+    assert_eq!(2, balances.len());
+    assert!(balances.contains(&(alice.clone(), String::from("-66.67"))));
+    assert!(balances.contains(&(bob.clone(), String::from("333.33"))));
+
+    let settlement = split_api
+        .settle(with_claims(
+            Request::new(NewSettlement {
+                contact_id: bob.clone(),
+                direction: SettlementDirection::Received as i32,
+                currency: String::from("TWD"),
+                amount: String::from("333.33"),
+                occurred_at: None,
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(vec![(alice, String::from("-66.67"))], get_balances().await);
+
+    let items = accounting_api
+        .list(with_claims(Request::new(()), USER_SUB))
+        .await
+        .unwrap()
+        .into_inner()
+        .items;
+    assert_eq!(2, items.len());
+    let received = items.iter().find(|x| x.id == settlement.item_id).unwrap();
+    assert_eq!("Settlement from Bob", received.name);
+    assert_eq!("333.33", received.amount.as_ref().unwrap().amount);
+}
+
+#[tokio::test]
+async fn test_contact_of_non_member() {
+    let (_test_database, server_state) = init_test_database_and_server_state().await;
+    insert_fake_user(&server_state.database).await.unwrap();
+    let split_api = SplitApi::new(Arc::new(server_state));
+    let ids = IdCodec::new(&SecretString::from("dummy"), &[]);
+    let status = split_api
+        .create_contact(with_claims(
+            Request::new(NewContact {
+                name: String::from("stranger"),
+                user_id: Some(ids.encode(Namespace::User, 9999)),
+            }),
+            USER_SUB,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+}
@@ -34,8 +34,8 @@ export default function YearlySummary({ sx }: { sx?: SxProps }) {
 					dataset={dataset}
 					grid={{ horizontal: true }}
 					xAxis={[
-						{ dataKey: "date", scaleType: "band" },
-						{ dataKey: "date", scaleType: "band" },
+						{ dataKey: "label", scaleType: "band" },
+						{ dataKey: "label", scaleType: "band" },
 					]}
 					series={[
 						{